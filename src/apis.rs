@@ -0,0 +1,109 @@
+//! The runtime APIs a wasm implements, read from its `runtime_apis` custom section.
+//!
+//! `impl_runtime_apis!` emits one 12 bytes entry per API there: the blake2_64 hash of the trait
+//! name, then its version as a little endian `u32`.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+
+use crate::AnyResult;
+
+/// The APIs `debug_*` and `txpool_*` RPCs of the tracing nodes call into.
+const TRACING_APIS: [(&str, [u8; 8]); 2] = [
+	(
+		"DebugRuntimeApi",
+		[0xbd, 0x78, 0x25, 0x5d, 0x4f, 0xee, 0xea, 0x1f],
+	),
+	(
+		"TxPoolRuntimeApi",
+		[0xa3, 0x3d, 0x43, 0xf5, 0x87, 0x31, 0xad, 0x84],
+	),
+];
+
+/// Fail unless the uncompressed wasm at `path` implements every tracing API.
+pub fn check_tracing(path: &Path) -> AnyResult<()> {
+	let wasm = fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+	let apis = runtime_apis(&wasm)
+		.with_context(|| format!("failed to read the runtime APIs of `{}`", path.display()))?;
+	let missing = TRACING_APIS
+		.iter()
+		.filter(|(_, id)| !apis.iter().any(|(api, _)| api == id))
+		.map(|(name, _)| *name)
+		.collect::<Vec<_>>();
+
+	if !missing.is_empty() {
+		anyhow::bail!(
+			"`{}` doesn't implement {}, did the `evm-tracing` feature take effect?",
+			path.display(),
+			missing.join(", ")
+		);
+	}
+
+	Ok(())
+}
+
+/// The `(id, version)` of every runtime API of `wasm`.
+pub fn runtime_apis(wasm: &[u8]) -> AnyResult<Vec<([u8; 8], u32)>> {
+	if !wasm.starts_with(b"\0asm") {
+		anyhow::bail!("not a wasm module");
+	}
+
+	let mut apis = Vec::new();
+	let mut cursor = 8;
+
+	while cursor < wasm.len() {
+		let id = wasm[cursor];
+
+		cursor += 1;
+
+		let size = leb128(wasm, &mut cursor)? as usize;
+		let section = wasm
+			.get(cursor..cursor + size)
+			.context("truncated section")?;
+
+		cursor += size;
+
+		if id != 0 {
+			continue;
+		}
+
+		let mut offset = 0;
+		let name_len = leb128(section, &mut offset)? as usize;
+		let name = section
+			.get(offset..offset + name_len)
+			.context("truncated section name")?;
+
+		if name != b"runtime_apis" {
+			continue;
+		}
+
+		for entry in section[offset + name_len..].chunks_exact(12) {
+			let mut id = [0; 8];
+			let mut version = [0; 4];
+
+			id.copy_from_slice(&entry[..8]);
+			version.copy_from_slice(&entry[8..]);
+			apis.push((id, u32::from_le_bytes(version)));
+		}
+	}
+
+	Ok(apis)
+}
+
+fn leb128(bytes: &[u8], cursor: &mut usize) -> AnyResult<u32> {
+	let mut value = 0;
+
+	for shift in (0..35).step_by(7) {
+		let byte = *bytes.get(*cursor).context("truncated integer")?;
+
+		*cursor += 1;
+		value |= u32::from(byte & 0x7f) << shift;
+
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+
+	anyhow::bail!("integer too large")
+}
@@ -0,0 +1,108 @@
+//! Moving the artifacts of the old spec versions out of the output directory, to `archive/` or
+//! another storage, rather than deleting them as `prune` does.
+
+use std::{
+	collections::BTreeSet,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::info;
+
+use crate::{checksums, index, prune, registry::Runtime, store, AnyResult};
+
+/// The default archive, in the output directory.
+pub const DIR: &str = "archive";
+
+/// Move the artifacts of `runtimes` older than their `keep` latest spec versions to `archive_dir`,
+/// in the layout of the output directory, and regenerate the indexes of both.
+///
+/// Returns how many artifacts were archived.
+pub fn run(
+	output_dir: &Path,
+	runtimes: &[Runtime],
+	keep: usize,
+	archive_dir: &Path,
+) -> AnyResult<usize> {
+	let mut archived = 0;
+
+	for runtime in runtimes {
+		let chain_dir = output_dir.join(runtime.name());
+		let digests = crate::dir_entries(&chain_dir.join("digests"))
+			.into_iter()
+			.filter_map(|digest| {
+				let name = digest.file_name()?.to_str()?.strip_suffix(".json")?;
+				let spec_version = crate::artifact::read_digest(&digest)?["core_version"]
+					.as_str()
+					.and_then(crate::artifact::spec_version)?;
+
+				(!name.starts_with('.')).then(|| (name.to_owned(), spec_version))
+			})
+			.collect::<Vec<_>>();
+		let kept = digests
+			.iter()
+			.map(|(_, spec_version)| *spec_version)
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.rev()
+			.take(keep)
+			.collect::<BTreeSet<_>>();
+		let archivable = digests
+			.iter()
+			.filter(|(_, spec_version)| !kept.contains(spec_version))
+			.collect::<Vec<_>>();
+
+		if archivable.is_empty() {
+			continue;
+		}
+
+		for (name, spec_version) in archivable {
+			info!("Archiving {}/{} ({})", runtime.name(), name, spec_version);
+
+			for path in prune::files(&chain_dir, name) {
+				let relative = path.strip_prefix(output_dir).unwrap_or(&path);
+
+				move_to(&path, &archive_dir.join(relative))?;
+			}
+
+			archived += 1;
+		}
+
+		store::collect_garbage(&chain_dir.join("wasms"))?;
+	}
+
+	if archived != 0 {
+		for dir in [output_dir, archive_dir] {
+			index::write(dir)?;
+			checksums::write(dir)?;
+		}
+	}
+
+	Ok(archived)
+}
+
+/// Move `path` to `to`, copying the wasm a content addressed artifact links to instead.
+fn move_to(path: &Path, to: &Path) -> AnyResult<()> {
+	let failed = || {
+		format!(
+			"failed to archive `{}` to `{}`",
+			path.display(),
+			to.display()
+		)
+	};
+
+	fs::create_dir_all(to.parent().unwrap_or_else(|| Path::new("."))).with_context(failed)?;
+
+	if path.is_symlink() {
+		crate::copy_file(path, to)?;
+		fs::remove_file(path).with_context(failed)
+	} else {
+		crate::move_file(path, to).with_context(failed)
+	}
+}
+
+/// The archive of `output_dir` unless another one is given.
+pub fn dir(output_dir: &Path, archive_dir: Option<PathBuf>) -> PathBuf {
+	archive_dir.unwrap_or_else(|| output_dir.join(DIR))
+}
@@ -0,0 +1,368 @@
+//! The artifacts of a build, a wasm and its digest, and the files written next to them.
+
+use std::{
+	collections::BTreeSet,
+	fs::{self, File},
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use codec::Encode;
+
+use clap::ArgEnum;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use subwasmlib::Subwasm;
+
+use crate::{
+	copy_file, create_dir_unchecked, dir_entries, registry::Runtime, store, temporary_path, to_hex,
+	AnyResult,
+};
+
+/// The digest field telling how an artifact was built, when, with which toolchain...
+///
+/// It differs from a build to the other, so two artifacts are compared without it.
+pub const PROVENANCE: &str = "provenance";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataFormat {
+	/// The metadata as `state_getMetadata` returns it, SCALE encoded in hex.
+	Scale,
+	Json,
+}
+impl MetadataFormat {
+	pub(crate) fn extension(self) -> &'static str {
+		match self {
+			Self::Scale => "scale",
+			Self::Json => "json",
+		}
+	}
+}
+
+/// Everything worth knowing about a generated tracing runtime.
+#[derive(Debug, Serialize)]
+pub struct Artifact {
+	pub(crate) runtime: String,
+	pub(crate) target: String,
+	pub(crate) commit: String,
+	pub(crate) wasm: PathBuf,
+	pub(crate) digest: PathBuf,
+	pub(crate) spec_version: Option<u32>,
+	pub(crate) hashes: Hashes,
+	pub(crate) timings: Timings,
+	/// Reused from a previous build of the same commit with the same features.
+	pub(crate) cached: bool,
+	pub(crate) log: Option<PathBuf>,
+}
+impl Artifact {
+	/// Describe an artifact from its digest, the timings are left for the caller to fill in.
+	pub(crate) fn new(
+		runtime: &Runtime,
+		target: &str,
+		wasm: PathBuf,
+		digest: PathBuf,
+		runtime_info: serde_json::Value,
+	) -> AnyResult<Self> {
+		Ok(Self {
+			runtime: runtime.name().into(),
+			target: target.into(),
+			commit: runtime_info["commit"].as_str().unwrap_or_default().into(),
+			wasm,
+			digest,
+			spec_version: runtime_info["core_version"].as_str().and_then(spec_version),
+			log: runtime_info[PROVENANCE]["log"].as_str().map(PathBuf::from),
+			hashes: serde_json::from_value(runtime_info)?,
+			timings: Timings::default(),
+			cached: false,
+		})
+	}
+
+	/// The name of the runtime in the registry, e.g. `crab`.
+	pub fn runtime(&self) -> &str {
+		&self.runtime
+	}
+
+	/// The branch, tag or commit the runtime was requested at.
+	pub fn target(&self) -> &str {
+		&self.target
+	}
+
+	/// The commit the runtime was built from.
+	pub fn commit(&self) -> &str {
+		&self.commit
+	}
+
+	pub fn wasm(&self) -> &Path {
+		&self.wasm
+	}
+
+	pub fn digest(&self) -> &Path {
+		&self.digest
+	}
+
+	pub fn spec_version(&self) -> Option<u32> {
+		self.spec_version
+	}
+
+	/// Reused from a previous build instead of built again.
+	pub fn cached(&self) -> bool {
+		self.cached
+	}
+
+	/// The log of the build.
+	pub fn log(&self) -> Option<&Path> {
+		self.log.as_deref()
+	}
+}
+
+/// The hashes subwasm computes, named as in its runtime info.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hashes {
+	pub blake2_256: String,
+	pub proposal_hash: String,
+	pub parachain_authorize_upgrade_hash: String,
+	pub ipfs_hash: String,
+	/// Of the `system.setCode` preimage, added by the builds.
+	#[serde(default)]
+	pub set_code_hash: Option<String>,
+}
+
+/// Durations of the build steps, in seconds.
+#[derive(Debug, Default, Serialize)]
+pub struct Timings {
+	pub clean: f64,
+	pub build: f64,
+	pub total: f64,
+}
+
+/// Refuse to replace an artifact by a different one, unless forced to.
+pub fn check_overwrite(
+	wasm_path: &Path,
+	digest_path: &Path,
+	runtime_info: &serde_json::Value,
+	force: bool,
+) -> AnyResult<()> {
+	if !wasm_path.exists() && !digest_path.exists() {
+		return Ok(());
+	}
+
+	let existing = read_digest(digest_path).unwrap_or_default();
+	let diff = digest_diff(
+		&without_provenance(existing),
+		&without_provenance(runtime_info.clone()),
+	);
+
+	if diff.is_empty() {
+		return Ok(());
+	}
+	if force {
+		warn!("Overwriting `{}`:\n{}", wasm_path.display(), diff);
+
+		return Ok(());
+	}
+
+	anyhow::bail!(
+		"refusing to overwrite `{}` with a different build, pass `--force` to do it anyway:\n{}",
+		wasm_path.display(),
+		diff
+	)
+}
+
+/// The name and the compressed and uncompressed sizes of the artifact of the chain of `digest_path`
+/// built last before it.
+pub fn previous_sizes(digest_path: &Path) -> Option<(String, u64, u64)> {
+	dir_entries(digest_path.parent()?)
+		.into_iter()
+		.filter(|path| path != digest_path)
+		.filter_map(|path| {
+			let provenance = read_digest(&path)?.get(PROVENANCE)?.clone();
+
+			Some((
+				provenance["built_at"].as_str()?.to_owned(),
+				path.file_stem()?.to_string_lossy().into_owned(),
+				provenance["compressed_size"].as_u64()?,
+				provenance["uncompressed_size"].as_u64()?,
+			))
+		})
+		.max()
+		.map(|(_, name, compressed, uncompressed)| (name, compressed, uncompressed))
+}
+
+/// Refuse a wasm of the spec version of another one of the chain, with a different hash, the nodes
+/// would silently load either of them.
+pub fn check_duplicate(digest_path: &Path, runtime_info: &serde_json::Value) -> AnyResult<()> {
+	let version = match runtime_info["core_version"].as_str().and_then(spec_version) {
+		Some(version) => version,
+		None => return Ok(()),
+	};
+	let digests_dir = digest_path.parent().unwrap_or_else(|| Path::new("."));
+
+	for path in dir_entries(digests_dir) {
+		if path == digest_path
+			|| path
+				.extension()
+				.map_or(true, |extension| extension != "json")
+		{
+			continue;
+		}
+
+		// The srtool digests and the metadata have no core version.
+		let other = match read_digest(&path) {
+			Some(other) => other,
+			None => continue,
+		};
+
+		if other["core_version"].as_str().and_then(spec_version) == Some(version)
+			&& other["blake2_256"] != runtime_info["blake2_256"]
+		{
+			anyhow::bail!(
+				"`{}` is another wasm of spec version {}, the nodes would load only one of them, pass `--allow-duplicate` to write it anyway",
+				path.display(),
+				version
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Also without the schema version, the digests written before it existed being of the same
+/// artifacts.
+fn without_provenance(mut runtime_info: serde_json::Value) -> serde_json::Value {
+	if let Some(runtime_info) = runtime_info.as_object_mut() {
+		runtime_info.remove(PROVENANCE);
+		runtime_info.remove("schema_version");
+	}
+
+	runtime_info
+}
+
+pub fn read_digest(path: &Path) -> Option<serde_json::Value> {
+	serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// The fields changing from a digest to the other, one `-`/`+` line per value.
+pub fn digest_diff(old: &serde_json::Value, new: &serde_json::Value) -> String {
+	let empty = serde_json::Map::new();
+	let old = old.as_object().unwrap_or(&empty);
+	let new = new.as_object().unwrap_or(&empty);
+
+	old.keys()
+		.chain(new.keys())
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.filter(|key| old.get(*key) != new.get(*key))
+		.flat_map(|key| {
+			let old = old.get(key).map(|value| format!("- {}: {}", key, value));
+			let new = new.get(key).map(|value| format!("+ {}: {}", key, value));
+
+			old.into_iter().chain(new)
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Extract the spec version from subwasm's core version, e.g. `Crab-1210 (Darwinia Crab-0.tx0.au0)`.
+pub fn spec_version(core_version: &str) -> Option<u32> {
+	core_version
+		.split_whitespace()
+		.next()?
+		.rsplit('-')
+		.next()?
+		.parse()
+		.ok()
+}
+
+/// Copy the built wasm into place and write its digest next to it, both or none of them.
+///
+/// They are written to temporary files first so that a node never loads a half-written wasm. The
+/// built one stays in the target directory, cargo doesn't build it again while the crate is fresh.
+pub fn write_artifact(
+	built_wasm_path: &Path,
+	wasm_path: &Path,
+	digest_path: &Path,
+	runtime_info: &serde_json::Value,
+	content_addressed: bool,
+) -> AnyResult<()> {
+	let wasm_tmp = temporary_path(wasm_path);
+	let digest_tmp = temporary_path(digest_path);
+	let result = (|| -> AnyResult<()> {
+		if content_addressed {
+			let blake2_256 = runtime_info["blake2_256"]
+				.as_str()
+				.context("the digest has no blake2_256")?;
+
+			store::write(built_wasm_path, &wasm_tmp, blake2_256, true)?;
+		} else {
+			copy_file(built_wasm_path, &wasm_tmp).with_context(|| {
+				format!(
+					"failed to copy `{}` to `{}`",
+					built_wasm_path.display(),
+					wasm_tmp.display()
+				)
+			})?;
+		}
+
+		let mut digest = File::create(&digest_tmp)?;
+
+		serde_json::to_writer(&mut digest, runtime_info)?;
+		digest.sync_all()?;
+
+		// The digest last, it's what tells the build cache the wasm is there.
+		fs::rename(&wasm_tmp, wasm_path)?;
+		fs::rename(&digest_tmp, digest_path)?;
+
+		Ok(())
+	})();
+
+	if result.is_err() {
+		for path in [&wasm_tmp, &digest_tmp] {
+			let _ = fs::remove_file(path);
+		}
+	}
+
+	result
+}
+
+/// Extension of the `--keep-uncompressed` wasms, kept next to the compressed ones.
+pub const UNCOMPRESSED_EXTENSION: &str = "compact.wasm";
+
+/// Where to put a file describing the artifact of the digest, e.g. `crab/<dir>/<name>.json` for
+/// `crab/digests/<name>.json`.
+pub fn sibling_path(digest_path: &Path, dir: &str, extension: &str) -> PathBuf {
+	let chain_dir = digest_path
+		.parent()
+		.and_then(Path::parent)
+		.unwrap_or_else(|| Path::new("."));
+	let name = digest_path
+		.file_stem()
+		.unwrap_or_default()
+		.to_string_lossy();
+
+	chain_dir.join(dir).join(format!("{}.{}", name, extension))
+}
+
+/// Export the metadata next to the digests, e.g. `crab/metadata/<name>.json` for
+/// `crab/digests/<name>.json`.
+pub fn write_metadata(
+	wasm: &Subwasm,
+	format: MetadataFormat,
+	digest_path: &Path,
+) -> AnyResult<PathBuf> {
+	let path = sibling_path(digest_path, "metadata", format.extension());
+	let tmp = temporary_path(&path);
+	let metadata = wasm.runtime_metadata_prefixed();
+
+	create_dir_unchecked(path.parent().unwrap_or_else(|| Path::new(".")))?;
+
+	match format {
+		MetadataFormat::Scale => fs::write(&tmp, format!("0x{}", to_hex(&metadata.encode())))?,
+		MetadataFormat::Json => serde_json::to_writer_pretty(File::create(&tmp)?, metadata)?,
+	}
+
+	fs::rename(&tmp, &path).with_context(|| format!("failed to write `{}`", path.display()))?;
+
+	Ok(path)
+}
@@ -0,0 +1,200 @@
+//! Drive the builds from Rust instead of shelling out to `runtime-overrides`.
+
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+	cli::BuildArgs, config::Config, progress::Progress, registry::Registry, AnyResult, Artifact,
+	CommandRunner, SystemRunner,
+};
+
+/// The builds `runtime-overrides --runtime <CHAIN>` runs, configured through methods instead of
+/// options.
+///
+/// The config files are honored as on the command line, and the artifacts written to the output
+/// directory likewise.
+///
+/// ```no_run
+/// use runtime_overrides::RuntimeBuilder;
+///
+/// let artifacts = RuntimeBuilder::new("crab")
+/// 	.target("v0.12.3")
+/// 	.output_dir("/var/lib/overrides")
+/// 	.run()?;
+///
+/// for artifact in artifacts {
+/// 	println!("{}", artifact.wasm().display());
+/// }
+/// # Ok::<_, anyhow::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RuntimeBuilder {
+	args: BuildArgs,
+	build_dir: Option<PathBuf>,
+	output_dir: Option<PathBuf>,
+	registry: Option<PathBuf>,
+	config: Option<PathBuf>,
+	runner: Arc<dyn CommandRunner>,
+}
+impl RuntimeBuilder {
+	/// Build `runtime`, a runtime of the registry or `all`.
+	pub fn new(runtime: impl Into<String>) -> Self {
+		Self {
+			args: BuildArgs {
+				runtime: vec![runtime.into()],
+				..Default::default()
+			},
+			build_dir: None,
+			output_dir: None,
+			registry: None,
+			config: None,
+			runner: Arc::new(SystemRunner),
+		}
+	}
+
+	/// Also build `runtime`.
+	pub fn runtime(mut self, runtime: impl Into<String>) -> Self {
+		self.args.runtime.push(runtime.into());
+
+		self
+	}
+
+	/// Build from this branch, tag or commit, call it again to build several [default: main]
+	pub fn target(mut self, target: impl Into<String>) -> Self {
+		self.args
+			.target
+			.get_or_insert_with(Vec::new)
+			.push(target.into());
+
+		self
+	}
+
+	/// Enable these cargo features instead of the runtime's default ones.
+	pub fn features<I, S>(mut self, features: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.args.features = Some(features.into_iter().map(Into::into).collect());
+
+		self
+	}
+
+	pub fn no_default_features(mut self, no_default_features: bool) -> Self {
+		self.args.no_default_features = no_default_features;
+
+		self
+	}
+
+	/// Build from the existing clones without any network access, cargo using the vendored sources
+	/// or the registry cache.
+	pub fn offline(mut self, offline: bool) -> Self {
+		self.args.offline = offline;
+
+		self
+	}
+
+	/// Build from this working tree as it is instead of cloning the repository.
+	pub fn source_path(mut self, source_path: impl Into<PathBuf>) -> Self {
+		self.args.source_path = Some(source_path.into());
+
+		self
+	}
+
+	/// Apply this patch to the checkouts before building, call it again to apply several in order.
+	pub fn patch(mut self, patch: impl Into<PathBuf>) -> Self {
+		self.args.patch.push(patch.into());
+
+		self
+	}
+
+	/// Let cargo update the `Cargo.lock` of the checkouts instead of building with `--locked`.
+	pub fn allow_lock_update(mut self, allow_lock_update: bool) -> Self {
+		self.args.allow_lock_update = allow_lock_update;
+
+		self
+	}
+
+	/// Number of repositories or targets to build at the same time [default: 1]
+	pub fn concurrency(mut self, concurrency: usize) -> Self {
+		self.args.concurrency = Some(concurrency);
+
+		self
+	}
+
+	/// Retry the network operations this many times [default: 3]
+	pub fn retries(mut self, retries: u32) -> Self {
+		self.args.retries = Some(retries);
+
+		self
+	}
+
+	/// Where to keep the source checkouts and their cargo target directories [default: build]
+	pub fn build_dir(mut self, build_dir: impl Into<PathBuf>) -> Self {
+		self.build_dir = Some(build_dir.into());
+
+		self
+	}
+
+	/// Where to write the artifacts [default: overridden-runtimes]
+	pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+		self.output_dir = Some(output_dir.into());
+
+		self
+	}
+
+	/// Read the runtime definitions from this file instead of `runtimes.toml`.
+	pub fn registry(mut self, registry: impl Into<PathBuf>) -> Self {
+		self.registry = Some(registry.into());
+
+		self
+	}
+
+	/// Read the defaults from this file instead of `runtime-overrides.toml` and the user config.
+	pub fn config(mut self, config: impl Into<PathBuf>) -> Self {
+		self.config = Some(config.into());
+
+		self
+	}
+
+	/// Build again even if an artifact of the same commit, with the same features, exists.
+	pub fn rebuild(mut self, rebuild: bool) -> Self {
+		self.args.rebuild = rebuild;
+
+		self
+	}
+
+	/// Overwrite the existing artifacts built from another commit or with other features.
+	pub fn force(mut self, force: bool) -> Self {
+		self.args.force = force;
+
+		self
+	}
+
+	/// Run git, cargo and the others through `runner` [default: SystemRunner]
+	pub fn runner(mut self, runner: impl CommandRunner + 'static) -> Self {
+		self.runner = Arc::new(runner);
+
+		self
+	}
+
+	/// Run the builds, failing with the first error if any of them failed.
+	pub fn run(self) -> AnyResult<Vec<Artifact>> {
+		let config = Config::load(self.config.as_deref())?;
+		let registry = Registry::load(self.registry.as_deref().or(config.registry.as_deref()))?;
+		let (build_dir, output_dir) =
+			crate::pipeline::dirs(self.build_dir, self.output_dir, &config)?;
+		let (artifacts, errors) = crate::pipeline::build_artifacts(
+			self.args,
+			config,
+			&registry,
+			build_dir,
+			output_dir,
+			Progress::new(true),
+			self.runner,
+		)?;
+
+		crate::pipeline::first_error(errors)?;
+
+		Ok(artifacts)
+	}
+}
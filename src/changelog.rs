@@ -0,0 +1,315 @@
+//! What a runtime upgrade changes for the users of a chain: the pallets, calls, storage items and
+//! constants its metadata gained, lost or altered.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{json, Value};
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{cli::ChangelogArgs, AnyResult};
+
+#[derive(Debug, Default, Serialize)]
+struct Changes {
+	added: Vec<String>,
+	removed: Vec<String>,
+	changed: Vec<String>,
+}
+impl Changes {
+	/// Compare the items of two pallets by name, an item whose signature differs being changed.
+	fn between(previous: &BTreeMap<String, Value>, new: &BTreeMap<String, Value>) -> Self {
+		let mut changes = Self::default();
+
+		for (name, signature) in new {
+			match previous.get(name) {
+				None => changes.added.push(name.clone()),
+				Some(previous) if previous != signature => changes.changed.push(name.clone()),
+				_ => (),
+			}
+		}
+		for name in previous.keys() {
+			if !new.contains_key(name) {
+				changes.removed.push(name.clone());
+			}
+		}
+
+		changes
+	}
+
+	fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct PalletChanges {
+	name: String,
+	/// The new index, if it moved.
+	index: Option<u64>,
+	calls: Changes,
+	storage: Changes,
+	constants: Changes,
+}
+
+#[derive(Debug, Serialize)]
+struct Changelog {
+	from: String,
+	to: String,
+	added_pallets: Vec<String>,
+	removed_pallets: Vec<String>,
+	changed_pallets: Vec<PalletChanges>,
+}
+
+pub fn run(args: ChangelogArgs) -> AnyResult<()> {
+	let changelog = changelog(&args.previous, &args.new)?;
+
+	if args.json {
+		println!("{}", serde_json::to_string_pretty(&changelog)?);
+	} else {
+		print!("{}", markdown(&changelog));
+	}
+
+	Ok(())
+}
+
+fn changelog(previous: &Path, new: &Path) -> AnyResult<Changelog> {
+	let load = |path: &Path| -> AnyResult<(String, BTreeMap<String, Value>)> {
+		if !path.is_file() {
+			anyhow::bail!("`{}` is not a file", path.display());
+		}
+
+		let subwasm = Subwasm::new(&Source::File(path.to_owned()));
+		let core_version = serde_json::to_value(subwasm.runtime_info())?["core_version"]
+			.as_str()
+			.map_or_else(|| path.display().to_string(), ToOwned::to_owned);
+		let metadata = serde_json::to_value(subwasm.runtime_metadata_prefixed())?;
+		let pallets = pallets(&metadata)
+			.with_context(|| format!("`{}` doesn't have a V14 metadata", path.display()))?;
+
+		Ok((core_version, pallets))
+	};
+	let (from, previous) = load(previous)?;
+	let (to, new) = load(new)?;
+	let mut changelog = Changelog {
+		from,
+		to,
+		added_pallets: new
+			.keys()
+			.filter(|name| !previous.contains_key(*name))
+			.cloned()
+			.collect(),
+		removed_pallets: previous
+			.keys()
+			.filter(|name| !new.contains_key(*name))
+			.cloned()
+			.collect(),
+		changed_pallets: Vec::new(),
+	};
+
+	for (name, pallet) in &new {
+		let previous = match previous.get(name) {
+			Some(previous) => previous,
+			None => continue,
+		};
+		let items = |pallet: &Value, kind: &str| {
+			serde_json::from_value::<BTreeMap<String, Value>>(pallet[kind].clone())
+				.unwrap_or_default()
+		};
+		let changes = PalletChanges {
+			name: name.clone(),
+			index: (previous["index"] != pallet["index"])
+				.then(|| pallet["index"].as_u64())
+				.flatten(),
+			calls: Changes::between(&items(previous, "calls"), &items(pallet, "calls")),
+			storage: Changes::between(&items(previous, "storage"), &items(pallet, "storage")),
+			constants: Changes::between(&items(previous, "constants"), &items(pallet, "constants")),
+		};
+
+		if changes.index.is_some()
+			|| !changes.calls.is_empty()
+			|| !changes.storage.is_empty()
+			|| !changes.constants.is_empty()
+		{
+			changelog.changed_pallets.push(changes);
+		}
+	}
+
+	Ok(changelog)
+}
+
+/// The pallets of the JSON of a V14 metadata by name, their calls, storage items and constants
+/// reduced to signatures comparable from a runtime to the other.
+///
+/// Type ids aren't stable across runtimes, types are compared by their names instead.
+fn pallets(metadata: &Value) -> Option<BTreeMap<String, Value>> {
+	let metadata = &metadata[1]["V14"];
+	let types = metadata["types"]["types"]
+		.as_array()?
+		.iter()
+		.filter_map(|ty| Some((ty["id"].as_u64()?, &ty["type"])))
+		.collect::<BTreeMap<_, _>>();
+	let type_name = |id: &Value| type_name(&types, id);
+
+	metadata["pallets"].as_array().map(|pallets| {
+		pallets
+			.iter()
+			.filter_map(|pallet| {
+				let calls = pallet["calls"]["ty"]
+					.as_u64()
+					.and_then(|id| types.get(&id))
+					.and_then(|ty| ty["def"]["variant"]["variants"].as_array())
+					.into_iter()
+					.flatten()
+					.filter_map(|call| {
+						let fields = call["fields"]
+							.as_array()
+							.into_iter()
+							.flatten()
+							.map(|field| json!([field["name"], type_name(&field["type"])]))
+							.collect::<Vec<_>>();
+
+						Some((call["name"].as_str()?, json!([call["index"], fields])))
+					})
+					.collect::<BTreeMap<_, _>>();
+				let storage = pallet["storage"]["entries"]
+					.as_array()
+					.into_iter()
+					.flatten()
+					.filter_map(|entry| {
+						let ty = match &entry["ty"] {
+							Value::Object(ty) if ty.contains_key("Map") => {
+								let map = &ty["Map"];
+
+								json!([
+									map["hashers"],
+									type_name(&map["key"]),
+									type_name(&map["value"])
+								])
+							}
+							Value::Object(ty) => json!(type_name(&ty["Plain"])),
+							_ => Value::Null,
+						};
+
+						Some((entry["name"].as_str()?, json!([entry["modifier"], ty])))
+					})
+					.collect::<BTreeMap<_, _>>();
+				let constants = pallet["constants"]
+					.as_array()
+					.into_iter()
+					.flatten()
+					.filter_map(|constant| {
+						Some((
+							constant["name"].as_str()?,
+							json!([type_name(&constant["ty"]), constant["value"]]),
+						))
+					})
+					.collect::<BTreeMap<_, _>>();
+
+				Some((
+					pallet["name"].as_str()?.to_owned(),
+					json!({
+						"index": pallet["index"],
+						"calls": calls,
+						"storage": storage,
+						"constants": constants,
+					}),
+				))
+			})
+			.collect()
+	})
+}
+
+/// `frame_support::weights::Weight`, `Vec<u8>`, `(u32, bool)`...
+fn type_name(types: &BTreeMap<u64, &Value>, id: &Value) -> String {
+	let ty = match id.as_u64().and_then(|id| types.get(&id)) {
+		Some(ty) => ty,
+		None => return "?".into(),
+	};
+	let inner = |def: &Value| type_name(types, &def["type"]);
+
+	if let Some(path) = ty["path"].as_array().filter(|path| !path.is_empty()) {
+		return path
+			.iter()
+			.filter_map(Value::as_str)
+			.collect::<Vec<_>>()
+			.join("::");
+	}
+
+	let def = &ty["def"];
+
+	if let Some(primitive) = def["primitive"].as_str() {
+		primitive.into()
+	} else if def["sequence"].is_object() {
+		format!("Vec<{}>", inner(&def["sequence"]))
+	} else if def["array"].is_object() {
+		format!("[{}; {}]", inner(&def["array"]), def["array"]["len"])
+	} else if def["compact"].is_object() {
+		format!("Compact<{}>", inner(&def["compact"]))
+	} else if let Some(fields) = def["tuple"].as_array() {
+		format!(
+			"({})",
+			fields
+				.iter()
+				.map(|field| type_name(types, field))
+				.collect::<Vec<_>>()
+				.join(", ")
+		)
+	} else {
+		"?".into()
+	}
+}
+
+fn markdown(changelog: &Changelog) -> String {
+	let mut markdown = format!("# {} → {}\n", changelog.from, changelog.to);
+	let list = |markdown: &mut String, title: &str, names: &[String]| {
+		if !names.is_empty() {
+			let _ = write!(markdown, "\n## {}\n\n", title);
+
+			for name in names {
+				let _ = writeln!(markdown, "- {}", name);
+			}
+		}
+	};
+
+	list(&mut markdown, "Added Pallets", &changelog.added_pallets);
+	list(&mut markdown, "Removed Pallets", &changelog.removed_pallets);
+
+	if !changelog.changed_pallets.is_empty() {
+		markdown.push_str("\n## Changed Pallets\n");
+	}
+
+	for pallet in &changelog.changed_pallets {
+		let _ = write!(markdown, "\n### {}\n\n", pallet.name);
+
+		if let Some(index) = pallet.index {
+			let _ = writeln!(markdown, "- Moved to index {}", index);
+		}
+
+		for (kind, changes) in [
+			("call", &pallet.calls),
+			("storage item", &pallet.storage),
+			("constant", &pallet.constants),
+		] {
+			for (change, names) in [
+				("Added", &changes.added),
+				("Removed", &changes.removed),
+				("Changed", &changes.changed),
+			] {
+				for name in names {
+					let _ = writeln!(markdown, "- {} {} `{}`", change, kind, name);
+				}
+			}
+		}
+	}
+
+	if changelog.added_pallets.is_empty()
+		&& changelog.removed_pallets.is_empty()
+		&& changelog.changed_pallets.is_empty()
+	{
+		markdown.push_str("\nNo pallet, call, storage item or constant changed.\n");
+	}
+
+	markdown
+}
@@ -0,0 +1,137 @@
+//! `SHA256SUMS` and `BLAKE2SUMS` of each chain directory, in the format `sha256sum --check` and
+//! `b2sum --check` read, to verify the downloaded artifacts out-of-band.
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use blake2::Blake2b512;
+use sha2::{Digest, Sha256};
+
+use crate::{archive, AnyResult};
+
+/// The manifests and how to hash the files they list.
+const MANIFESTS: [(&str, fn(&[u8]) -> String); 2] = [
+	("SHA256SUMS", hash::<Sha256>),
+	("BLAKE2SUMS", hash::<Blake2b512>),
+];
+
+/// The subdirectories of a chain directory holding its artifacts.
+const DIRS: [&str; 4] = ["wasms", "digests", "metadata", "srtool"];
+
+/// Regenerate the manifests of every chain directory of `output_dir`.
+pub fn write(output_dir: &Path) -> AnyResult<()> {
+	for chain_dir in crate::dir_entries(output_dir) {
+		// The archive has manifests of its own.
+		if !chain_dir.is_dir() || chain_dir.ends_with(archive::DIR) {
+			continue;
+		}
+
+		let files = files(&chain_dir);
+
+		for (name, hash) in MANIFESTS {
+			let path = chain_dir.join(name);
+			let tmp = crate::temporary_path(&path);
+			let mut content = String::new();
+
+			for file in &files {
+				let bytes = fs::read(chain_dir.join(file))
+					.with_context(|| format!("failed to read `{}`", file))?;
+
+				content.push_str(&format!("{}  {}\n", hash(&bytes), file));
+			}
+
+			fs::write(&tmp, content)?;
+			fs::rename(&tmp, &path)
+				.with_context(|| format!("failed to write `{}`", path.display()))?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Check the files of `chain_dir` against its manifests, skipping the missing ones.
+///
+/// Returns the manifests checked and the problems found.
+pub fn check(chain_dir: &Path) -> (Vec<PathBuf>, Vec<String>) {
+	let files = files(chain_dir);
+	let mut checked = Vec::new();
+	let mut problems = Vec::new();
+
+	for (name, hash) in MANIFESTS {
+		let path = chain_dir.join(name);
+		let content = match fs::read_to_string(&path) {
+			Ok(content) => content,
+			Err(_) => continue,
+		};
+		let mut listed = Vec::new();
+
+		for line in content.lines() {
+			let (expected, file) = match line.split_once("  ") {
+				Some(entry) => entry,
+				None => {
+					problems.push(format!(
+						"`{}` has an invalid line: {}",
+						path.display(),
+						line
+					));
+
+					continue;
+				}
+			};
+
+			listed.push(file);
+
+			match fs::read(chain_dir.join(file)) {
+				Ok(bytes) if hash(&bytes) == expected => {}
+				Ok(_) => problems.push(format!(
+					"`{}` doesn't match its {} entry",
+					file,
+					path.display()
+				)),
+				Err(_) => problems.push(format!(
+					"`{}` is listed in {} but missing",
+					file,
+					path.display()
+				)),
+			}
+		}
+
+		for file in files.iter().filter(|file| !listed.contains(&file.as_str())) {
+			problems.push(format!("`{}` is missing from {}", file, path.display()));
+		}
+
+		checked.push(path);
+	}
+
+	(checked, problems)
+}
+
+/// The artifacts of `chain_dir`, relative to it and sorted, temporary ones aside.
+pub fn files(chain_dir: &Path) -> Vec<String> {
+	let mut files = DIRS
+		.iter()
+		.flat_map(|dir| {
+			crate::dir_entries(&chain_dir.join(dir))
+				.into_iter()
+				.filter_map(move |path| {
+					let name = path.file_name()?.to_str()?;
+
+					(path.is_file() && !name.starts_with('.')).then(|| format!("{}/{}", dir, name))
+				})
+		})
+		.collect::<Vec<_>>();
+
+	files.sort();
+
+	files
+}
+
+fn hash<D: Digest>(bytes: &[u8]) -> String {
+	D::digest(bytes)
+		.iter()
+		.map(|byte| format!("{:02x}", byte))
+		.collect()
+}
@@ -0,0 +1,1488 @@
+//! The `runtime-overrides` command line, its arguments and the subcommands.
+
+use std::{
+	collections::BTreeSet,
+	env,
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+	process,
+	sync::Arc,
+	time::Duration,
+};
+
+use anyhow::Context;
+
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use log::{error, info, LevelFilter};
+
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{
+	archive,
+	artifact::{digest_diff, MetadataFormat},
+	changelog, completions,
+	config::{rpc_url, Config},
+	doctor, history, interactive, ipfs, list, migrate, oci, package, parse_duration,
+	pipeline::{build_artifacts, dirs, first_error},
+	progress::Progress,
+	proposal, prune, publish,
+	registry::{Layout, Registry, Runtime},
+	rpc,
+	runner::{CommandFailed, TimedOut},
+	serve,
+	settings::Builder,
+	signing::{self, Signer},
+	smoke, targets, to_hex, try_runtime, verify, watch, AnyResult, BuildError, SystemRunner,
+};
+
+/// Build the EVM tracing runtimes loaded by Darwinia nodes through `--wasm-runtime-overrides`.
+#[derive(Debug, Parser)]
+#[clap(subcommand_negates_reqs = true)]
+pub struct Cli {
+	#[clap(subcommand)]
+	pub action: Option<Action>,
+	#[clap(flatten)]
+	pub build: BuildArgs,
+	/// Root directory of the generated artifacts [default: overridden-runtimes]
+	#[clap(
+		long,
+		global = true,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_OUTPUT_DIR"
+	)]
+	pub output_dir: Option<PathBuf>,
+	/// Directory holding the source checkouts and their cargo target directories [default: build]
+	#[clap(
+		long,
+		global = true,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_BUILD_DIR"
+	)]
+	pub build_dir: Option<PathBuf>,
+	/// Read the runtime definitions from this file instead of `runtimes.toml`.
+	#[clap(
+		long,
+		global = true,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_REGISTRY"
+	)]
+	pub registry: Option<PathBuf>,
+	/// Read the defaults from this file instead of `runtime-overrides.toml` and the user config.
+	#[clap(
+		long,
+		global = true,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_CONFIG"
+	)]
+	pub config: Option<PathBuf>,
+	/// Show more details, repeat it to show even more (e.g. `-vv`).
+	#[clap(short, long, global = true, parse(from_occurrences))]
+	pub verbose: u64,
+	/// Only show warnings and errors.
+	#[clap(
+		short,
+		long,
+		global = true,
+		conflicts_with = "verbose",
+		env = "RO_QUIET"
+	)]
+	pub quiet: bool,
+	/// Also write the full log, including every subprocess output, to this file.
+	#[clap(
+		long,
+		global = true,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_LOG_FILE"
+	)]
+	pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Action {
+	/// Move the artifacts older than the latest spec versions of their chain to an archive.
+	Archive(ArchiveArgs),
+	/// Print the hex encoded call upgrading a chain to a runtime wasm.
+	CallData(CallDataArgs),
+	/// Summarize the pallets, calls, storage items and constants a runtime upgrade changes.
+	Changelog(ChangelogArgs),
+	/// Print the completion script of a shell, runtime names included.
+	Completions(CompletionsArgs),
+	/// Compare the versions and the metadata of two runtime wasms.
+	Diff(DiffArgs),
+	/// Check that everything needed to build the runtimes is available.
+	Doctor,
+	/// Save the runtime code of a live chain to a wasm file.
+	Download(DownloadArgs),
+	/// Write the overrides of some chains to an OCI image, for the nodes to mount.
+	ExportImage(ExportImageArgs),
+	/// Download a wasm from IPFS by its CID.
+	Fetch(FetchArgs),
+	/// Query the builds this machine ran, e.g. when and from what commit a spec version was last
+	/// built.
+	History(HistoryArgs),
+	/// Describe a runtime wasm, a local file or an HTTP(S) URL.
+	Info(InfoArgs),
+	/// Print a table of the overrides of each chain, flagging the wasms or digests missing.
+	List(ListArgs),
+	/// List the branches and tags a runtime can be built from.
+	ListTargets(ListTargetsArgs),
+	/// Rename the artifacts named after their target after their spec version, as
+	/// `--name-by-spec-version` does.
+	MigrateNaming(MigrateNamingArgs),
+	/// Bundle the artifacts of some chains, their index and checksums included, into an archive.
+	Package(PackageArgs),
+	/// Flag the artifacts older than the runtime the live chains are running.
+	Prune(PruneArgs),
+	/// Upload the artifacts of a build, their checksums included, out of the git history.
+	Publish(PublishArgs),
+	/// Serve on HTTP the GitHub webhook building the released tags, the REST API or both.
+	Serve(ServeArgs),
+	/// Trace a transaction on a dev node running a runtime override.
+	SmokeTest(SmokeTestArgs),
+	/// Check every artifact of the output directory against its digest.
+	Verify,
+	/// Check the signatures of every artifact of the output directory.
+	VerifySignature(VerifySignatureArgs),
+	/// Keep polling the repositories and build the new release tags.
+	Watch(WatchArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ArchiveArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// archive several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// How many of the latest spec versions of each chain to keep [default: 3]
+	#[clap(long, takes_value = true, value_name = "N")]
+	pub keep: Option<usize>,
+	/// Directory to move the artifacts to, e.g. a mounted bucket [default:
+	/// <OUTPUT_DIR>/archive]
+	#[clap(long, takes_value = true, value_name = "PATH")]
+	pub to: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct CallDataArgs {
+	/// The runtime to upgrade to
+	#[clap(value_name = "WASM")]
+	pub wasm: PathBuf,
+	/// Upgrade call to encode
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		value_name = "CALL",
+		default_value = "set-code"
+	)]
+	pub call: proposal::Call,
+	/// Encode the call with the metadata of the runtime this chain is running, the one executing
+	/// it, instead of the wasm's
+	#[clap(long, takes_value = true, value_name = "CHAIN")]
+	pub chain: Option<String>,
+	/// RPC endpoint of the chain, HTTP(S) or WS(S) [default: the `rpc` of the runtime]
+	#[clap(long, takes_value = true, value_name = "URL", requires = "chain")]
+	pub rpc: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ChangelogArgs {
+	/// Runtime upgraded from, e.g. the previous override
+	#[clap(value_name = "PREVIOUS")]
+	pub previous: PathBuf,
+	/// Runtime upgraded to
+	#[clap(value_name = "NEW")]
+	pub new: PathBuf,
+	/// Print the changes as JSON rather than markdown
+	#[clap(long)]
+	pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+	/// Shell to complete the command line of
+	#[clap(arg_enum, value_name = "SHELL")]
+	pub shell: clap_complete::Shell,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+	/// Reference runtime, e.g. the canonical build of a release
+	#[clap(value_name = "A")]
+	pub a: PathBuf,
+	/// Runtime compared to the reference, e.g. its tracing build
+	#[clap(value_name = "B")]
+	pub b: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct DownloadArgs {
+	/// Runtime from the registry (non case sensitive)
+	#[clap(value_name = "CHAIN")]
+	pub runtime: String,
+	/// Number or hash of the block to download the code at [default: the best block]
+	#[clap(short, long, takes_value = true, value_name = "BLOCK")]
+	pub block: Option<String>,
+	/// RPC endpoint of the chain, HTTP(S) or WS(S) [default: the `rpc` of the runtime]
+	#[clap(long, takes_value = true, value_name = "URL")]
+	pub rpc: Option<String>,
+	/// Where to save the code [default: <CHAIN>-<SPEC_VERSION>.compact.compressed.wasm]
+	#[clap(short, long, takes_value = true, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportImageArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// export several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Name of the image
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "REFERENCE",
+		default_value = "runtime-overrides:latest"
+	)]
+	pub tag: String,
+	/// Directory of the overrides in the image
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "PATH",
+		default_value = "/overrides"
+	)]
+	pub path: String,
+	/// Architecture the image is for, the wasms themselves run on any
+	#[clap(long, takes_value = true, value_name = "ARCH", default_value = "amd64")]
+	pub arch: String,
+	/// Where to write the OCI archive
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		value_name = "PATH",
+		default_value = "runtime-overrides.oci.tar"
+	)]
+	pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct FetchArgs {
+	/// CID of the wasm, e.g. the `cid` of the provenance of its digest
+	#[clap(long, takes_value = true, value_name = "CID")]
+	pub cid: String,
+	/// Fetch it through the IPFS daemon with this API rather than a gateway
+	#[clap(long, takes_value = true, value_name = "URL", env = "RO_IPFS")]
+	pub ipfs: Option<String>,
+	/// HTTP gateway to fetch it from
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "URL",
+		default_value = ipfs::DEFAULT_GATEWAY
+	)]
+	pub gateway: String,
+	/// Where to save the wasm [default: <CID>.compact.compressed.wasm]
+	#[clap(short, long, takes_value = true, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+	/// Only the builds of this runtime, repeat it or separate with commas for several
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Only the builds of this branch, tag or commit
+	#[clap(short, long, takes_value = true, value_name = "TARGET")]
+	pub target: Option<String>,
+	/// Only the builds of this spec version
+	#[clap(long, takes_value = true, value_name = "VERSION")]
+	pub spec_version: Option<u32>,
+	/// Only the builds of the commit starting with this
+	#[clap(long, takes_value = true, value_name = "COMMIT")]
+	pub commit: Option<String>,
+	/// Only the builds with exactly these features, e.g. `evm-tracing`
+	#[clap(long, takes_value = true, value_name = "FEATURES")]
+	pub features: Option<String>,
+	/// Only the failed builds
+	#[clap(long, conflicts_with = "succeeded")]
+	pub failed: bool,
+	/// Only the successful builds
+	#[clap(long)]
+	pub succeeded: bool,
+	/// Print the latest N builds only
+	#[clap(short = 'n', long, takes_value = true, value_name = "N")]
+	pub limit: Option<usize>,
+	/// Print the builds as JSON
+	#[clap(long)]
+	pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+	/// Path or HTTP(S) URL of the wasm
+	#[clap(value_name = "PATH|URL")]
+	pub wasm: String,
+	/// Print the runtime info as JSON, like the digests
+	#[clap(long)]
+	pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// list several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Print the overrides as JSON
+	#[clap(long)]
+	pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ListTargetsArgs {
+	/// Runtime from the registry (non case sensitive) or `all`
+	#[clap(value_name = "CHAIN")]
+	pub runtime: String,
+	/// Only list the names matching this glob, e.g. `v6.*`
+	#[clap(long, takes_value = true, value_name = "GLOB")]
+	pub filter: Option<String>,
+	/// Only list the repository of this layout, both are by default
+	#[clap(arg_enum, long, takes_value = true, value_name = "LAYOUT")]
+	pub layout: Option<Layout>,
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateNamingArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// migrate several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Only tell what would be renamed
+	#[clap(long)]
+	pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PackageArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// package several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Version of the package, in its name and the one of its root directory [default: the date,
+	/// YYYYMMDD]
+	#[clap(long, takes_value = true, value_name = "VERSION")]
+	pub version: Option<String>,
+	/// Kind of archive
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		value_name = "FORMAT",
+		default_value = "tar.zst"
+	)]
+	pub format: package::Format,
+	/// Where to write the package [default: runtime-overrides-<VERSION>.<FORMAT>]
+	#[clap(short, long, takes_value = true, value_name = "PATH")]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct PruneArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// prune several [default: all]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN"
+	)]
+	pub runtime: Vec<String>,
+	/// Keep the artifacts of every spec version the chain ever ran, requires an archive node
+	#[clap(long)]
+	pub historic: bool,
+	/// Delete the prunable artifacts instead of listing them
+	#[clap(long)]
+	pub delete: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PublishArgs {
+	#[clap(subcommand)]
+	pub destination: Destination,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Destination {
+	/// Create or update a GitHub release holding the artifacts.
+	Github(GithubPublishArgs),
+	/// Upload the artifacts to an S3 compatible bucket, keyed by their hash, and add them to its
+	/// index.
+	S3(S3PublishArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PublishedBuild {
+	/// Runtime from the registry (non case sensitive)
+	#[clap(value_name = "CHAIN")]
+	pub runtime: String,
+	/// Branch, tag or commit the runtime was built from
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		value_name = "TARGET",
+		default_value = "main"
+	)]
+	pub target: String,
+}
+
+#[derive(Debug, Args)]
+pub struct GithubPublishArgs {
+	#[clap(flatten)]
+	published: PublishedBuild,
+	/// Repository of the release, `OWNER/NAME` [default: the `origin` of the current directory]
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "REPOSITORY",
+		env = "RO_GITHUB_REPOSITORY"
+	)]
+	pub repository: Option<String>,
+	/// Tag of the release, created if missing [default: <CHAIN>-<TARGET>]
+	#[clap(long, takes_value = true, value_name = "TAG")]
+	pub tag: Option<String>,
+	/// Token allowed to write the releases of the repository
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "TOKEN",
+		env = "GITHUB_TOKEN",
+		hide_env_values = true
+	)]
+	pub token: String,
+}
+
+#[derive(Debug, Args)]
+pub struct S3PublishArgs {
+	#[clap(flatten)]
+	published: PublishedBuild,
+	/// Bucket to upload to
+	#[clap(long, takes_value = true, value_name = "BUCKET", env = "RO_S3_BUCKET")]
+	pub bucket: String,
+	/// Prepended to the keys, e.g. `overrides/`
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "PREFIX",
+		default_value = "",
+		env = "RO_S3_PREFIX"
+	)]
+	pub prefix: String,
+	/// Storage API, e.g. `https://storage.googleapis.com` for GCS [default: the AWS S3 endpoint of
+	/// the region]
+	#[clap(long, takes_value = true, value_name = "URL", env = "RO_S3_ENDPOINT")]
+	pub endpoint: Option<String>,
+	/// Region of the bucket, `auto` for GCS
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "REGION",
+		default_value = "us-east-1",
+		env = "AWS_REGION"
+	)]
+	pub region: String,
+	/// Access key of the HMAC credentials allowed to write the bucket
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "KEY",
+		env = "AWS_ACCESS_KEY_ID",
+		hide_env_values = true
+	)]
+	pub access_key_id: String,
+	/// Secret of the access key
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "SECRET",
+		env = "AWS_SECRET_ACCESS_KEY",
+		hide_env_values = true
+	)]
+	pub secret_access_key: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+	/// Address to listen on
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "ADDRESS",
+		default_value = "127.0.0.1:8080"
+	)]
+	pub listen: String,
+	/// Build the tags of the GitHub push and release events posted to `/webhook`
+	#[clap(long, requires = "webhook_secret")]
+	pub webhook: bool,
+	/// Secret of the webhook, the events not signed with it are rejected
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "SECRET",
+		env = "RO_WEBHOOK_SECRET",
+		hide_env_values = true
+	)]
+	pub webhook_secret: Option<String>,
+	/// Serve the REST API: `POST /builds`, `GET /builds/<ID>` and `GET /artifacts/<PATH>`
+	#[clap(long)]
+	pub api: bool,
+	/// Require this bearer token on every endpoint of the API
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "TOKEN",
+		env = "RO_API_TOKEN",
+		hide_env_values = true,
+		requires = "api"
+	)]
+	pub api_token: Option<String>,
+	/// Number of builds running at the same time, each worker with a build directory of its own:
+	/// `<BUILD_DIR>/worker-<N>`
+	#[clap(long, takes_value = true, value_name = "N", default_value = "1")]
+	pub workers: usize,
+	#[clap(flatten)]
+	pub build: BuildArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct SmokeTestArgs {
+	/// The override to test
+	#[clap(value_name = "WASM")]
+	pub wasm: PathBuf,
+	/// Node binary to run, with the EVM tracing support
+	#[clap(long, takes_value = true, value_name = "PATH", env = "RO_NODE")]
+	pub node: PathBuf,
+	/// Chain spec of the node, its genesis runtime having the spec version of the override
+	#[clap(long, takes_value = true, value_name = "SPEC", default_value = "dev")]
+	pub chain: String,
+	/// Pass this argument to the node too, repeat it to pass several
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		allow_hyphen_values = true,
+		value_name = "ARG"
+	)]
+	pub node_arg: Vec<String>,
+	/// Key of the funded account sending the transaction [default: the dev account Alith]
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "HEX",
+		env = "RO_SMOKE_TEST_KEY",
+		default_value = "5fb92d6e98884f76de468fa3f6278f8807c48bebc13595d45af5bdc4da702133",
+		hide_default_value = true
+	)]
+	pub private_key: String,
+	/// Give up when the test takes longer than this
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "DURATION",
+		default_value = "5m",
+		parse(try_from_str = parse_duration)
+	)]
+	pub timeout: Duration,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifySignatureArgs {
+	/// Tool the artifacts were signed with [default: minisign]
+	#[clap(arg_enum, long, takes_value = true, value_name = "SIGNER")]
+	pub signer: Option<Signer>,
+	/// Public key file of minisign, GPG checks against its keyring
+	#[clap(long, takes_value = true, value_name = "KEY", env = "RO_VERIFY_KEY")]
+	pub key: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+	/// Only build the tags matching this glob
+	#[clap(long, takes_value = true, value_name = "GLOB", default_value = "v*")]
+	pub pattern: String,
+	/// Time between two polls, e.g. `30m` or `1h`
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "DURATION",
+		default_value = "10m",
+		parse(try_from_str = parse_duration)
+	)]
+	pub interval: Duration,
+	/// Serve the Prometheus metrics of the builds on this address, at `/metrics`
+	#[clap(long, takes_value = true, value_name = "ADDRESS")]
+	pub metrics_listen: Option<String>,
+	#[clap(flatten)]
+	pub build: BuildArgs,
+}
+
+// The arguments of a build, which is what happens when no subcommand is given.
+#[derive(Clone, Debug, Default, Args)]
+pub struct BuildArgs {
+	/// Runtime from the registry (non case sensitive) or `all`, repeat it or separate with commas to
+	/// build several
+	#[clap(
+		short,
+		long,
+		required = true,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "CHAIN",
+		env = "RO_RUNTIME"
+	)]
+	pub runtime: Vec<String>,
+	/// Specific branch/commit/tag or `latest-release`, separate with commas to build several
+	/// [default: main]
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "VALUE",
+		env = "RO_TARGET"
+	)]
+	pub target: Option<Vec<String>>,
+	/// Number of builds to run at the same time, each one in its own git worktree [default: 1]
+	#[clap(long, takes_value = true, value_name = "N", env = "RO_CONCURRENCY")]
+	pub concurrency: Option<usize>,
+	/// Number of times a failed clone or fetch is retried, waiting twice as long each time
+	/// [default: 3]
+	#[clap(long, takes_value = true, value_name = "N", env = "RO_RETRIES")]
+	pub retries: Option<u32>,
+	/// Seconds to wait for another invocation to release the build directory [default: 0]
+	#[clap(long, takes_value = true, value_name = "SECONDS", env = "RO_LOCK_WAIT")]
+	pub lock_wait: Option<u64>,
+	/// Cargo features to enable, separate with commas [default: evm-tracing]
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "FEATURES",
+		env = "RO_FEATURES"
+	)]
+	pub features: Option<Vec<String>>,
+	/// Do not activate the runtime's `default` feature.
+	#[clap(long, env = "RO_NO_DEFAULT_FEATURES")]
+	pub no_default_features: bool,
+	/// Build this manifest, relative to the repository root, instead of the runtime's default one.
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_MANIFEST_PATH"
+	)]
+	pub manifest_path: Option<String>,
+	/// Name of the runtime crate, when it differs from `<CHAIN>-runtime`.
+	#[clap(long, takes_value = true, value_name = "NAME", env = "RO_PACKAGE")]
+	pub package: Option<String>,
+	/// Repository layout of the targets, detected from their version by default.
+	#[clap(
+		arg_enum,
+		long,
+		ignore_case = true,
+		takes_value = true,
+		value_name = "LAYOUT",
+		env = "RO_LAYOUT"
+	)]
+	pub layout: Option<Layout>,
+	/// Clone from this URL (e.g. a fork or a mirror) instead of the official repository.
+	#[clap(long, takes_value = true, value_name = "URL", env = "RO_REPO_URL")]
+	pub repo_url: Option<String>,
+	/// Build from this working tree as it is, nothing is cloned, fetched or checked out. Its commit
+	/// and whether it has uncommitted changes are recorded in the digests, `--target` only names
+	/// the artifacts [default target: local]
+	#[clap(long, takes_value = true, value_name = "DIR", env = "RO_SOURCE_PATH")]
+	pub source_path: Option<PathBuf>,
+	/// Print the commands and the artifact paths without executing anything.
+	#[clap(long, env = "RO_DRY_RUN")]
+	pub dry_run: bool,
+	/// Do not install the missing toolchains, components and targets through rustup.
+	#[clap(long, env = "RO_NO_INSTALL")]
+	pub no_install: bool,
+	/// Build from the existing clones without any network access: the targets are looked up
+	/// locally and cargo is passed `--offline`, using the vendored sources or the registry cache.
+	/// Implies `--no-install`
+	#[clap(long, env = "RO_OFFLINE")]
+	pub offline: bool,
+	/// Clone and fetch the whole history instead of only the last commit of each target.
+	#[clap(long, env = "RO_FULL_CLONE")]
+	pub full_clone: bool,
+	/// Only check out the crates the runtime's workspace is made of.
+	#[clap(long, env = "RO_SPARSE")]
+	pub sparse: bool,
+	/// Append the abbreviated commit to the artifact names, e.g. `crab-main-1a2b3c4-tracing-runtime`.
+	#[clap(long, env = "RO_COMMIT_IN_NAME")]
+	pub commit_in_name: bool,
+	/// Name the artifacts after their spec version instead, e.g. `crab-1210-tracing-runtime`, to
+	/// drop them straight into a node's `--wasm-runtime-overrides` directory.
+	#[clap(long, env = "RO_NAME_BY_SPEC_VERSION")]
+	pub name_by_spec_version: bool,
+	/// Store each wasm once, as `wasms/by-hash/<BLAKE2_256>.wasm`, the artifacts being symlinks to
+	/// it.
+	#[clap(long, env = "RO_CONTENT_ADDRESSED")]
+	pub content_addressed: bool,
+	/// Also export the metadata of the built runtimes to `<CHAIN>/metadata/<NAME>.<FORMAT>`, repeat
+	/// it or separate with commas to export several formats
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		use_value_delimiter = true,
+		value_name = "FORMAT",
+		env = "RO_METADATA"
+	)]
+	pub metadata: Vec<MetadataFormat>,
+	/// Sign the wasms and digests into `.sig` files with this minisign secret key file, or GPG key
+	#[clap(long, takes_value = true, value_name = "KEY", env = "RO_SIGN_KEY")]
+	pub sign_key: Option<String>,
+	/// Tool signing the artifacts [default: minisign]
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		value_name = "SIGNER",
+		env = "RO_SIGNER"
+	)]
+	pub signer: Option<Signer>,
+	/// Build each runtime a second time, in a fresh target directory, and fail unless both wasms
+	/// are identical.
+	#[clap(long, env = "RO_VERIFY_REPRODUCIBLE")]
+	pub verify_reproducible: bool,
+	/// Run this `try-runtime` check of each runtime against the state of its live chain before
+	/// writing the artifacts, the runtimes need the `try-runtime` feature
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		value_name = "CHECK",
+		env = "RO_TRY_RUNTIME"
+	)]
+	pub try_runtime: Option<try_runtime::Check>,
+	/// How to build the runtimes, docker and podman need an `--image` [default: cargo]
+	#[clap(
+		arg_enum,
+		long,
+		takes_value = true,
+		value_name = "BUILDER",
+		env = "RO_BUILDER"
+	)]
+	pub builder: Option<Builder>,
+	/// Container image of the builder [default: paritytech/srtool for srtool]
+	#[clap(long, takes_value = true, value_name = "IMAGE", env = "RO_IMAGE")]
+	pub image: Option<String>,
+	/// Number of parallel jobs of cargo [default: the number of CPUs]
+	#[clap(short, long, takes_value = true, value_name = "N", env = "RO_JOBS")]
+	pub jobs: Option<u32>,
+	/// Run cargo at the lowest priority, not to starve the other workloads of a shared server.
+	#[clap(long, env = "RO_NICE")]
+	pub nice: bool,
+	/// Cargo profile to build with, e.g. the `production` one of the official runtimes
+	/// [default: release]
+	#[clap(long, takes_value = true, value_name = "PROFILE", env = "RO_PROFILE")]
+	pub profile: Option<String>,
+	/// Set this environment variable for the builds, e.g. `RUSTFLAGS=...` or `WASM_BUILD_TYPE=...`,
+	/// repeat it to set several
+	#[clap(
+		short,
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		value_name = "KEY=VALUE",
+		parse(try_from_str = parse_env),
+		env = "RO_ENV"
+	)]
+	pub env: Vec<(String, String)>,
+	/// Pass this argument to `cargo build` too, e.g. `--timings` or `--config=...`, repeat it to
+	/// pass several
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		allow_hyphen_values = true,
+		value_name = "ARG",
+		env = "RO_CARGO_ARG"
+	)]
+	pub cargo_arg: Vec<String>,
+	/// Apply this patch to the checkout before building, e.g. to fix the dependencies of an old tag,
+	/// repeat it to apply several in order. Their hashes are recorded in the digests
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		value_name = "PATH",
+		env = "RO_PATCH"
+	)]
+	pub patch: Vec<PathBuf>,
+	/// Let cargo update the `Cargo.lock` of the checkout, the runtimes are built with `--locked`
+	/// otherwise, from the exact dependencies of the target.
+	#[clap(long, env = "RO_ALLOW_LOCK_UPDATE")]
+	pub allow_lock_update: bool,
+	/// Also write the uncompressed wasm, `<NAME>.compact.wasm`, for the tools which can't read the
+	/// zstd compressed ones.
+	#[clap(long, env = "RO_KEEP_UNCOMPRESSED")]
+	pub keep_uncompressed: bool,
+	/// Run `wasm-opt` on each runtime before compressing it again, recorded in the digest.
+	#[clap(long, env = "RO_WASM_OPT")]
+	pub wasm_opt: bool,
+	/// Pass this argument to `wasm-opt` instead of `-Oz`, e.g. `-O3` or `--strip-debug`, repeat it
+	/// to pass several
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		allow_hyphen_values = true,
+		value_name = "ARG",
+		env = "RO_WASM_OPT_ARG"
+	)]
+	pub wasm_opt_arg: Vec<String>,
+	/// Cache the compilations with sccache, through `RUSTC_WRAPPER`.
+	#[clap(long, env = "RO_SCCACHE")]
+	pub sccache: bool,
+	/// Post a message to this Slack, Discord or Matrix webhook URL when a build finishes or fails,
+	/// the JSON event to any other URL, repeat it to notify several
+	#[clap(
+		long,
+		takes_value = true,
+		multiple_occurrences = true,
+		value_name = "URL",
+		env = "RO_NOTIFY"
+	)]
+	pub notify: Vec<String>,
+	/// Run this shell command when a build finishes or fails, the event in `RO_*` environment
+	/// variables
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "COMMAND",
+		env = "RO_NOTIFY_COMMAND"
+	)]
+	pub notify_command: Option<String>,
+	/// Copy the wasms into this `--wasm-runtime-overrides` directory of a node, one per spec
+	/// version
+	#[clap(long, takes_value = true, value_name = "DIR", env = "RO_INSTALL_TO")]
+	pub install_to: Option<PathBuf>,
+	/// Commit the new artifacts and the index onto a branch, push it and open a GitHub pull request
+	/// of it, authenticated with `GITHUB_TOKEN`
+	#[clap(long, env = "RO_PUBLISH_PR")]
+	publish_pr: bool,
+	/// Add and pin the wasms on the IPFS daemon with this API, e.g. `http://127.0.0.1:5001`, their
+	/// CID recorded in the digests
+	#[clap(long, takes_value = true, value_name = "URL", env = "RO_IPFS")]
+	pub ipfs: Option<String>,
+	/// Pin the wasms on this IPFS Pinning Service API too
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "URL",
+		env = "RO_IPFS_PIN_SERVICE"
+	)]
+	pub ipfs_pin_service: Option<String>,
+	/// Access token of the pinning service
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "TOKEN",
+		env = "RO_IPFS_PIN_TOKEN",
+		hide_env_values = true
+	)]
+	pub ipfs_pin_token: Option<String>,
+	/// Write the build logs to `<PATH>/<CHAIN>/` [default: <OUTPUT_DIR>/<CHAIN>/logs]
+	#[clap(
+		long,
+		takes_value = true,
+		value_name = "PATH",
+		env = "RO_BUILD_LOG_DIR"
+	)]
+	pub build_log_dir: Option<PathBuf>,
+	/// Kill the cargo build of a runtime taking longer than this, e.g. `90m` or `2h`
+	#[clap(long, takes_value = true, value_name = "DURATION", parse(try_from_str = parse_duration), env = "RO_TIMEOUT")]
+	pub timeout: Option<Duration>,
+	/// Never `cargo clean` the runtime, by default it is whenever its commit or features changed
+	/// since its last build.
+	#[clap(long, env = "RO_NO_CLEAN")]
+	pub no_clean: bool,
+	/// Warn when the spec name or version of a build differs from the one of its live chain.
+	#[clap(long, env = "RO_CHECK_CHAIN")]
+	pub check_chain: bool,
+	/// Fail instead of warning when a compressed wasm is larger than its chain accepts.
+	#[clap(long, env = "RO_STRICT_SIZE")]
+	pub strict_size: bool,
+	/// Build again even if an artifact of the same commit, with the same features, exists.
+	#[clap(long, env = "RO_REBUILD")]
+	pub rebuild: bool,
+	/// Overwrite the existing artifacts built from another commit or with other features.
+	#[clap(long, env = "RO_FORCE")]
+	pub force: bool,
+	/// Write an artifact even if the chain already has another wasm of its spec version, only one
+	/// of which the nodes would load.
+	#[clap(long, env = "RO_ALLOW_DUPLICATE")]
+	pub allow_duplicate: bool,
+	/// Format of the final report printed to stdout.
+	#[clap(
+		arg_enum,
+		long,
+		ignore_case = true,
+		takes_value = true,
+		value_name = "FORMAT",
+		default_value = "text",
+		env = "RO_OUTPUT"
+	)]
+	pub output: OutputFormat,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, ArgEnum)]
+pub enum OutputFormat {
+	#[default]
+	Text,
+	Json,
+}
+
+/// Run the `runtime-overrides` command line and exit.
+pub fn cli() {
+	let cli = if interactive::wanted() {
+		match interactive::prompt() {
+			Ok(Some(args)) => Cli::parse_from(env::args().take(1).chain(args)),
+			Ok(None) => return,
+			Err(e) => {
+				eprintln!("Error: {:?}", e);
+
+				process::exit(1);
+			}
+		}
+	} else {
+		Cli::parse()
+	};
+
+	let progress = Progress::new(cli.quiet);
+
+	if let Err(e) = init_logger(cli.verbose, cli.quiet, cli.log_file.as_deref(), &progress) {
+		eprintln!("Error: {:?}", e);
+
+		process::exit(1);
+	}
+	if let Err(e) = try_main(cli, progress) {
+		error!("{:?}", e);
+
+		process::exit(exit_code(&e));
+	}
+}
+
+/// What `e` failed at, as documented in the README.
+fn exit_code(e: &anyhow::Error) -> i32 {
+	if e.downcast_ref::<TimedOut>().is_some() {
+		TimedOut::EXIT_CODE
+	} else if let Some(e) = e.downcast_ref::<BuildError>() {
+		e.exit_code()
+	} else {
+		e.downcast_ref::<CommandFailed>()
+			.map(CommandFailed::exit_code)
+			.unwrap_or(1)
+	}
+}
+
+/// Terminals get the requested verbosity, above the spinners of `progress`, while the log file
+/// always records everything.
+fn init_logger(
+	verbose: u64,
+	quiet: bool,
+	log_file: Option<&Path>,
+	progress: &Progress,
+) -> AnyResult<()> {
+	let level = match (quiet, verbose) {
+		(true, _) => LevelFilter::Warn,
+		(false, 0) => LevelFilter::Info,
+		(false, 1) => LevelFilter::Debug,
+		_ => LevelFilter::Trace,
+	};
+	let mut dispatch = fern::Dispatch::new().level(LevelFilter::Trace).chain(
+		fern::Dispatch::new()
+			.level(level)
+			.format(|out, message, record| match record.level() {
+				log::Level::Error => out.finish(format_args!("Error: {}", message)),
+				log::Level::Warn => out.finish(format_args!("Warning: {}", message)),
+				_ => out.finish(*message),
+			})
+			.chain(progress.output()),
+	);
+
+	if let Some(log_file) = log_file {
+		dispatch = dispatch.chain(
+			fern::Dispatch::new()
+				.format(|out, message, record| {
+					out.finish(format_args!(
+						"{} {:<5} {}",
+						chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+						record.level(),
+						message
+					))
+				})
+				.chain(
+					fern::log_file(log_file)
+						.with_context(|| format!("failed to open `{}`", log_file.display()))?,
+				),
+		);
+	}
+
+	dispatch.apply()?;
+
+	Ok(())
+}
+
+fn try_main(cli: Cli, progress: Progress) -> AnyResult<()> {
+	let config = Config::load(cli.config.as_deref())?;
+	let registry = Registry::load(cli.registry.as_deref().or(config.registry.as_deref()))?;
+	let (build_dir, output_dir) = dirs(cli.build_dir, cli.output_dir, &config)?;
+
+	match cli.action {
+		Some(Action::Archive(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+			let archive_dir = archive::dir(&output_dir, args.to.or(config.archive_dir));
+			let archived = archive::run(
+				&output_dir,
+				&registry.resolve(&names)?,
+				args.keep.or(config.archive_keep).unwrap_or(3),
+				&archive_dir,
+			)?;
+
+			info!(
+				"Archived {} artifact(s) to {}",
+				archived,
+				archive_dir.display()
+			);
+
+			Ok(())
+		}
+		Some(Action::CallData(args)) => call_data(args, &config, &registry),
+		Some(Action::Changelog(args)) => changelog::run(args),
+		Some(Action::Completions(args)) => {
+			completions::print(args.shell, &registry);
+
+			Ok(())
+		}
+		Some(Action::Diff(args)) => diff(args),
+		Some(Action::Doctor) => doctor::run(&registry, &build_dir, &config.git),
+		Some(Action::Download(args)) => download_code(args, &config, &registry),
+		Some(Action::ExportImage(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+			let image = oci::Image {
+				reference: &args.tag,
+				path: &args.path,
+				architecture: &args.arch,
+			};
+			let count = oci::export(
+				&output_dir,
+				&registry.resolve(&names)?,
+				&image,
+				&args.output,
+			)?;
+
+			info!(
+				"Exported {} override(s) as {} to {}",
+				count,
+				args.tag,
+				args.output.display()
+			);
+
+			Ok(())
+		}
+		Some(Action::Fetch(args)) => {
+			let path = args
+				.output
+				.unwrap_or_else(|| format!("{}.compact.compressed.wasm", args.cid).into());
+
+			ipfs::fetch(
+				&args.cid,
+				args.ipfs.or(config.ipfs).as_deref(),
+				&args.gateway,
+				&path,
+			)?;
+			info!("Saved {} to `{}`", args.cid, path.display());
+
+			Ok(())
+		}
+		Some(Action::History(args)) => history::run(args, &build_dir),
+		Some(Action::Info(args)) => print_info(args),
+		Some(Action::List(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+
+			list::run(&output_dir, &registry.resolve(&names)?, args.json)
+		}
+		Some(Action::ListTargets(args)) => list_targets(args, &config, &registry),
+		Some(Action::MigrateNaming(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+
+			migrate::run(&output_dir, &registry.resolve(&names)?, args.dry_run)
+		}
+		Some(Action::Package(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+			let version = args
+				.version
+				.unwrap_or_else(|| chrono::Utc::now().format("%Y%m%d").to_string());
+			let path = package::run(
+				&output_dir,
+				&registry.resolve(&names)?,
+				&version,
+				args.format,
+				args.output,
+			)?;
+
+			info!("Packaged the artifacts to {}", path.display());
+
+			Ok(())
+		}
+		Some(Action::Prune(args)) => {
+			let names = if args.runtime.is_empty() {
+				vec!["all".into()]
+			} else {
+				args.runtime
+			};
+
+			prune::run(
+				&output_dir,
+				&registry.resolve(&names)?,
+				&config,
+				args.historic,
+				args.delete,
+			)
+		}
+		Some(Action::Publish(args)) => publish::run(args, &config, &registry, &output_dir),
+		Some(Action::Serve(args)) => {
+			serve::run(args, config, &registry, build_dir, output_dir, progress)
+		}
+		Some(Action::SmokeTest(args)) => smoke::run(args),
+		Some(Action::Verify) => verify::run(&output_dir),
+		Some(Action::VerifySignature(args)) => signing::verify_all(
+			&output_dir,
+			args.signer.or(config.signer).unwrap_or(Signer::Minisign),
+			args.key.as_deref(),
+		),
+		Some(Action::Watch(args)) => {
+			watch::run(args, config, &registry, build_dir, output_dir, progress)
+		}
+		None => build_all(
+			cli.build, config, &registry, build_dir, output_dir, progress,
+		),
+	}
+}
+
+/// Print the runtime info fields which differ, then the metadata differences as subwasm reports
+/// them: pallets, calls, events, storage items...
+fn diff(args: DiffArgs) -> AnyResult<()> {
+	for path in [&args.a, &args.b] {
+		if !path.is_file() {
+			anyhow::bail!("`{}` is not a file", path.display());
+		}
+	}
+
+	let runtime_info = |path: &PathBuf| {
+		serde_json::to_value(Subwasm::new(&Source::File(path.clone())).runtime_info())
+	};
+	let info_diff = digest_diff(&runtime_info(&args.a)?, &runtime_info(&args.b)?);
+
+	if info_diff.is_empty() {
+		println!("Same runtime info");
+	} else {
+		println!("{}", info_diff);
+	}
+
+	subwasmlib::diff(Source::File(args.a), Source::File(args.b));
+
+	Ok(())
+}
+
+fn download_code(args: DownloadArgs, config: &Config, registry: &Registry) -> AnyResult<()> {
+	let runtime = single_runtime(registry, args.runtime)?;
+	let client = live_chain(&runtime, args.rpc.as_deref(), config)?;
+	let hash = match args.block {
+		Some(block) if block.starts_with("0x") => Some(block),
+		Some(block) => {
+			let number = block.parse().with_context(|| {
+				format!("invalid block `{}`, expected a number or a hash", block)
+			})?;
+
+			Some(
+				client
+					.block_hash(number)?
+					.with_context(|| format!("block {} doesn't exist yet", number))?,
+			)
+		}
+		None => None,
+	};
+	let code = client.code(hash.as_deref())?;
+	let path = match args.output {
+		Some(path) => path,
+		None => format!(
+			"{}-{}.compact.compressed.wasm",
+			runtime.name(),
+			client.spec_version(hash.as_deref())?
+		)
+		.into(),
+	};
+
+	fs::write(&path, code).with_context(|| format!("failed to write `{}`", path.display()))?;
+	info!(
+		"Saved the runtime code of {} to `{}`",
+		runtime.name(),
+		path.display()
+	);
+
+	Ok(())
+}
+
+fn call_data(args: CallDataArgs, config: &Config, registry: &Registry) -> AnyResult<()> {
+	let code = fs::read(&args.wasm)
+		.with_context(|| format!("failed to read `{}`", args.wasm.display()))?;
+	let metadata = match args.chain {
+		Some(chain) => {
+			let runtime = single_runtime(registry, chain)?;
+			let path = temporary_wasm_path();
+			let metadata = live_chain(&runtime, args.rpc.as_deref(), config)?
+				.code(None)
+				.and_then(|code| Ok(fs::write(&path, code)?))
+				.and_then(|_| {
+					Ok(serde_json::to_value(
+						Subwasm::new(&Source::File(path.clone())).runtime_metadata_prefixed(),
+					)?)
+				});
+			let _ = fs::remove_file(&path);
+
+			metadata?
+		}
+		None => serde_json::to_value(
+			Subwasm::new(&Source::File(args.wasm.clone())).runtime_metadata_prefixed(),
+		)?,
+	};
+
+	println!(
+		"0x{}",
+		to_hex(&proposal::encode(args.call, &metadata, &code)?)
+	);
+
+	Ok(())
+}
+
+pub fn single_runtime(registry: &Registry, name: String) -> AnyResult<Runtime> {
+	let mut runtimes = registry.resolve(&[name])?;
+
+	if runtimes.len() != 1 {
+		anyhow::bail!("expected a single runtime, not `all`");
+	}
+
+	Ok(runtimes.remove(0))
+}
+
+/// A client of the runtime's chain, at `rpc` if given.
+fn live_chain(runtime: &Runtime, rpc: Option<&str>, config: &Config) -> AnyResult<rpc::Client> {
+	let rpc = rpc
+		.or_else(|| rpc_url(&config.runtimes, runtime))
+		.with_context(|| {
+			format!(
+				"no RPC endpoint is known for {}, pass `--rpc`",
+				runtime.name()
+			)
+		})?;
+
+	Ok(rpc::Client::new(rpc))
+}
+
+/// Where to put a downloaded wasm while subwasm reads it.
+fn temporary_wasm_path() -> PathBuf {
+	env::temp_dir().join(format!("runtime-overrides-{}.wasm", process::id()))
+}
+
+fn print_info(args: InfoArgs) -> AnyResult<()> {
+	let runtime_info = if args.wasm.starts_with("http://") || args.wasm.starts_with("https://") {
+		let path = temporary_wasm_path();
+		let runtime_info = download(&args.wasm, &path).and_then(|_| {
+			Ok(serde_json::to_value(
+				Subwasm::new(&Source::File(path.clone())).runtime_info(),
+			)?)
+		});
+		let _ = fs::remove_file(&path);
+
+		runtime_info?
+	} else {
+		let path = PathBuf::from(&args.wasm);
+
+		if !path.is_file() {
+			anyhow::bail!("`{}` is not a file", path.display());
+		}
+
+		serde_json::to_value(Subwasm::new(&Source::File(path)).runtime_info())?
+	};
+
+	if args.json {
+		println!("{}", serde_json::to_string_pretty(&runtime_info)?);
+	} else if let Some(fields) = runtime_info.as_object() {
+		for (key, value) in fields {
+			match value {
+				serde_json::Value::String(value) => println!("{}: {}", key, value),
+				value => println!("{}: {}", key, value),
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn download(url: &str, path: &Path) -> AnyResult<()> {
+	let response = ureq::get(url)
+		.call()
+		.with_context(|| format!("failed to download `{}`", url))?;
+
+	io::copy(&mut response.into_reader(), &mut File::create(path)?)
+		.with_context(|| format!("failed to download `{}`", url))?;
+
+	Ok(())
+}
+
+fn list_targets(args: ListTargetsArgs, config: &Config, registry: &Registry) -> AnyResult<()> {
+	let layouts = match args.layout {
+		Some(layout) => vec![layout],
+		None => Layout::value_variants().to_vec(),
+	};
+	let mut urls = BTreeSet::new();
+
+	for runtime in registry.resolve(&[args.runtime])? {
+		let repo_url = config
+			.runtimes
+			.get(runtime.name())
+			.and_then(|r| r.repo_url.clone());
+
+		for layout in &layouts {
+			urls.insert(repo_url.clone().unwrap_or_else(|| runtime.github(*layout)));
+		}
+	}
+
+	targets::list(
+		&urls.into_iter().collect::<Vec<_>>(),
+		args.filter.as_deref(),
+		&config.git,
+	)
+}
+
+fn build_all(
+	args: BuildArgs,
+	config: Config,
+	registry: &Registry,
+	build_dir: PathBuf,
+	output_dir: PathBuf,
+	progress: Progress,
+) -> AnyResult<()> {
+	let output = args.output.clone();
+	let (artifacts, errors) = build_artifacts(
+		args,
+		config,
+		registry,
+		build_dir,
+		output_dir,
+		progress,
+		Arc::new(SystemRunner),
+	)?;
+
+	if output == OutputFormat::Json {
+		println!(
+			"{}",
+			serde_json::to_string_pretty(&serde_json::json!({
+				"builds": artifacts,
+				"errors": errors.iter().map(|e| format!("{:#}", e)).collect::<Vec<_>>(),
+			}))?
+		);
+	}
+
+	first_error(errors)
+}
+
+fn parse_env(env: &str) -> Result<(String, String), String> {
+	match env.split_once('=') {
+		Some((key, value)) if !key.is_empty() => Ok((key.into(), value.into())),
+		_ => Err(format!("expected `KEY=VALUE`, got `{}`", env)),
+	}
+}
@@ -0,0 +1,42 @@
+//! Shell completions, the runtime names of the registry included.
+
+use std::io;
+
+use clap::{Command, CommandFactory};
+use clap_complete::Shell;
+
+use crate::{cli::Cli, registry::Registry};
+
+/// Print the completion script of `shell`.
+pub fn print(shell: Shell, registry: &Registry) {
+	let names = registry
+		.runtimes()
+		.map(|runtime| runtime.name())
+		.chain(["all"])
+		.collect::<Vec<_>>();
+	let mut command = with_runtimes(Cli::command(), &names);
+	let name = command.get_name().to_owned();
+
+	clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
+
+/// Offer `names` for every `<CHAIN>` of `command` and its subcommands.
+fn with_runtimes<'a>(mut command: Command<'a>, names: &[&'a str]) -> Command<'a> {
+	let chains = command
+		.get_arguments()
+		.filter(|arg| {
+			arg.get_value_names()
+				.map_or(false, |names| names == ["CHAIN"])
+		})
+		.map(|arg| arg.get_id())
+		.collect::<Vec<_>>();
+
+	for id in chains {
+		command = command.mut_arg(id, |arg| arg.possible_values(names.iter().copied()));
+	}
+	for subcommand in command.get_subcommands_mut() {
+		*subcommand = with_runtimes(std::mem::replace(subcommand, Command::new("")), names);
+	}
+
+	command
+}
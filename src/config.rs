@@ -0,0 +1,461 @@
+//! Defaults loaded from `runtime-overrides.toml` and the user's config directory.
+//!
+//! ```toml
+//! target               = ["main"]
+//! features             = ["evm-tracing"]
+//! concurrency          = 2
+//! retries              = 5
+//! lock-wait            = 600
+//! output-dir           = "overridden-runtimes"
+//! build-dir            = "/mnt/fast/build"
+//! full-clone           = false
+//! sparse               = true
+//! offline              = false
+//! commit-in-name       = true
+//! name-by-spec-version = false
+//! content-addressed    = true
+//! check-chain          = true
+//! strict-size          = true
+//! verify-reproducible  = false
+//! try-runtime          = "on-runtime-upgrade"
+//! builder              = "srtool"
+//! image                = "paritytech/srtool:1.62.0"
+//! sccache              = true
+//! jobs                 = 8
+//! nice                 = true
+//! profile              = "production"
+//! no-clean             = false
+//! timeout              = "2h"
+//! build-log-dir        = "/var/log/runtime-overrides"
+//! cargo-args           = ["--timings"]
+//! patches              = ["patches/fix-evm-tracing.diff"]
+//! allow-lock-update    = false
+//! keep-uncompressed    = true
+//! wasm-opt             = true
+//! wasm-opt-args        = ["-Oz", "--strip-debug"]
+//! notify               = ["https://hooks.slack.com/services/..."]
+//! notify-command       = "mail -s \"$RO_MESSAGE\" team@example.com < /dev/null"
+//! metadata             = ["json"]
+//! signer               = "minisign"
+//! sign-key             = "/etc/runtime-overrides/minisign.key"
+//! install-to           = "/var/lib/darwinia/overrides"
+//! ipfs                 = "http://127.0.0.1:5001"
+//! ipfs-pin-service     = "https://api.pinata.cloud/psa"
+//! ipfs-pin-token-env   = "PINATA_TOKEN"
+//! archive-keep         = 3
+//! archive-dir          = "/mnt/archive/overridden-runtimes"
+//! github-repository    = "darwinia-network/runtime-overrides"
+//! publish-pr           = true
+//!
+//! [env]
+//! WASM_BUILD_RUSTFLAGS = "-C debuginfo=0"
+//!
+//! [cargo-patch."https://github.com/paritytech/frontier"]
+//! fp-evm = { git = "https://github.com/darwinia-network/frontier", rev = "a1b2c3d" }
+//!
+//! [runtimes.pangolin]
+//! features = ["evm-tracing", "try-runtime"]
+//! repo-url = "https://github.com/someone/darwinia-common"
+//! rpc      = "http://127.0.0.1:9933"
+//!
+//! [runtimes.crab-parachain]
+//! # The `max_code_size` of the relay chain.
+//! max-code-size = 3145728
+//!
+//! # The remotes whose URL starts with this, the longest prefix winning.
+//! [git."https://github.com/someone/"]
+//! proxy     = "http://proxy.example.com:3128"
+//! token-env = "GITHUB_TOKEN"
+//!
+//! [git."git@github.com:"]
+//! ssh-key = "~/.ssh/runtime-overrides"
+//! ```
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	env, fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+	artifact::MetadataFormat, registry::Runtime, settings::Builder, signing::Signer,
+	try_runtime::Check, AnyResult,
+};
+
+/// The project config, looked up in the current directory.
+pub const FILE_NAME: &str = "runtime-overrides.toml";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+	pub target: Option<Vec<String>>,
+	pub features: Option<Vec<String>>,
+	pub no_default_features: Option<bool>,
+	pub concurrency: Option<usize>,
+	pub retries: Option<u32>,
+	pub lock_wait: Option<u64>,
+	pub output_dir: Option<PathBuf>,
+	pub build_dir: Option<PathBuf>,
+	pub registry: Option<PathBuf>,
+	pub full_clone: Option<bool>,
+	pub sparse: Option<bool>,
+	pub offline: Option<bool>,
+	pub commit_in_name: Option<bool>,
+	pub name_by_spec_version: Option<bool>,
+	pub content_addressed: Option<bool>,
+	pub check_chain: Option<bool>,
+	pub strict_size: Option<bool>,
+	pub verify_reproducible: Option<bool>,
+	pub try_runtime: Option<Check>,
+	pub builder: Option<Builder>,
+	pub image: Option<String>,
+	pub sccache: Option<bool>,
+	pub timeout: Option<String>,
+	pub build_log_dir: Option<PathBuf>,
+	pub cargo_args: Option<Vec<String>>,
+	pub patches: Option<Vec<PathBuf>>,
+	pub allow_lock_update: Option<bool>,
+	pub keep_uncompressed: Option<bool>,
+	pub wasm_opt: Option<bool>,
+	pub wasm_opt_args: Option<Vec<String>>,
+	pub notify: Option<Vec<String>>,
+	pub notify_command: Option<String>,
+	pub jobs: Option<u32>,
+	pub nice: Option<bool>,
+	pub profile: Option<String>,
+	pub no_clean: Option<bool>,
+	/// Environment variables of the builds, e.g. `RUSTFLAGS`.
+	pub env: Option<HashMap<String, String>>,
+	/// The `[patch.<SOURCE>]` sections added to the workspace manifest before building, keyed by
+	/// source.
+	pub cargo_patch: Option<BTreeMap<String, toml::value::Table>>,
+	pub metadata: Option<Vec<MetadataFormat>>,
+	pub signer: Option<Signer>,
+	pub sign_key: Option<String>,
+	pub install_to: Option<PathBuf>,
+	pub ipfs: Option<String>,
+	pub ipfs_pin_service: Option<String>,
+	/// The environment variable holding the token of `ipfs-pin-service`.
+	pub ipfs_pin_token_env: Option<String>,
+	/// Archive the artifacts older than this many spec versions after every build.
+	pub archive_keep: Option<usize>,
+	pub archive_dir: Option<PathBuf>,
+	/// Where `publish github` creates the releases.
+	pub github_repository: Option<String>,
+	pub publish_pr: Option<bool>,
+	/// Per-runtime settings, keyed by the lowercase runtime name.
+	pub runtimes: HashMap<String, RuntimeConfig>,
+	/// How to reach the remotes, keyed by URL prefix.
+	pub git: HashMap<String, GitConfig>,
+}
+impl Config {
+	/// Load the config at `path` if given, otherwise merge the user config with the project one.
+	///
+	/// The project config takes precedence, missing files are simply skipped.
+	pub fn load(path: Option<&Path>) -> AnyResult<Self> {
+		if let Some(path) = path {
+			return Self::read(path);
+		}
+
+		let mut config = Self::default();
+
+		for path in user_config_path()
+			.into_iter()
+			.chain([PathBuf::from(FILE_NAME)])
+		{
+			if path.is_file() {
+				config = config.merge(Self::read(&path)?);
+			}
+		}
+
+		Ok(config)
+	}
+
+	fn read(path: &Path) -> AnyResult<Self> {
+		let content = fs::read_to_string(path)
+			.with_context(|| format!("failed to read `{}`", path.display()))?;
+
+		toml::from_str(&content).with_context(|| format!("failed to parse `{}`", path.display()))
+	}
+
+	/// Overlay `other` on top of `self`.
+	fn merge(mut self, other: Self) -> Self {
+		for (name, runtime) in other.runtimes {
+			let merged = self
+				.runtimes
+				.remove(&name)
+				.unwrap_or_default()
+				.merge(runtime);
+
+			self.runtimes.insert(name, merged);
+		}
+		for (prefix, git) in other.git {
+			let merged = self.git.remove(&prefix).unwrap_or_default().merge(git);
+
+			self.git.insert(prefix, merged);
+		}
+
+		Self {
+			target: other.target.or(self.target),
+			features: other.features.or(self.features),
+			no_default_features: other.no_default_features.or(self.no_default_features),
+			concurrency: other.concurrency.or(self.concurrency),
+			retries: other.retries.or(self.retries),
+			lock_wait: other.lock_wait.or(self.lock_wait),
+			output_dir: other.output_dir.or(self.output_dir),
+			build_dir: other.build_dir.or(self.build_dir),
+			registry: other.registry.or(self.registry),
+			full_clone: other.full_clone.or(self.full_clone),
+			sparse: other.sparse.or(self.sparse),
+			offline: other.offline.or(self.offline),
+			commit_in_name: other.commit_in_name.or(self.commit_in_name),
+			name_by_spec_version: other.name_by_spec_version.or(self.name_by_spec_version),
+			content_addressed: other.content_addressed.or(self.content_addressed),
+			check_chain: other.check_chain.or(self.check_chain),
+			strict_size: other.strict_size.or(self.strict_size),
+			verify_reproducible: other.verify_reproducible.or(self.verify_reproducible),
+			try_runtime: other.try_runtime.or(self.try_runtime),
+			builder: other.builder.or(self.builder),
+			image: other.image.or(self.image),
+			sccache: other.sccache.or(self.sccache),
+			timeout: other.timeout.or(self.timeout),
+			build_log_dir: other.build_log_dir.or(self.build_log_dir),
+			cargo_args: other.cargo_args.or(self.cargo_args),
+			patches: other.patches.or(self.patches),
+			allow_lock_update: other.allow_lock_update.or(self.allow_lock_update),
+			keep_uncompressed: other.keep_uncompressed.or(self.keep_uncompressed),
+			wasm_opt: other.wasm_opt.or(self.wasm_opt),
+			wasm_opt_args: other.wasm_opt_args.or(self.wasm_opt_args),
+			notify: other.notify.or(self.notify),
+			notify_command: other.notify_command.or(self.notify_command),
+			jobs: other.jobs.or(self.jobs),
+			nice: other.nice.or(self.nice),
+			profile: other.profile.or(self.profile),
+			no_clean: other.no_clean.or(self.no_clean),
+			env: other.env.or(self.env),
+			cargo_patch: other.cargo_patch.or(self.cargo_patch),
+			metadata: other.metadata.or(self.metadata),
+			signer: other.signer.or(self.signer),
+			sign_key: other.sign_key.or(self.sign_key),
+			install_to: other.install_to.or(self.install_to),
+			ipfs: other.ipfs.or(self.ipfs),
+			ipfs_pin_service: other.ipfs_pin_service.or(self.ipfs_pin_service),
+			ipfs_pin_token_env: other.ipfs_pin_token_env.or(self.ipfs_pin_token_env),
+			archive_keep: other.archive_keep.or(self.archive_keep),
+			archive_dir: other.archive_dir.or(self.archive_dir),
+			github_repository: other.github_repository.or(self.github_repository),
+			publish_pr: other.publish_pr.or(self.publish_pr),
+			runtimes: self.runtimes,
+			git: self.git,
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RuntimeConfig {
+	pub features: Option<Vec<String>>,
+	pub no_default_features: Option<bool>,
+	pub repo_url: Option<String>,
+	pub manifest_path: Option<String>,
+	pub package: Option<String>,
+	pub rpc: Option<String>,
+	/// The largest compressed wasm the chain accepts, read from the runtime by default.
+	pub max_code_size: Option<u32>,
+}
+impl RuntimeConfig {
+	fn merge(self, other: Self) -> Self {
+		Self {
+			features: other.features.or(self.features),
+			no_default_features: other.no_default_features.or(self.no_default_features),
+			repo_url: other.repo_url.or(self.repo_url),
+			manifest_path: other.manifest_path.or(self.manifest_path),
+			package: other.package.or(self.package),
+			rpc: other.rpc.or(self.rpc),
+			max_code_size: other.max_code_size.or(self.max_code_size),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct GitConfig {
+	/// The HTTP(S) proxy, e.g. `http://proxy.example.com:3128`.
+	pub proxy: Option<String>,
+	/// The environment variable holding the token of the HTTPS remotes.
+	pub token_env: Option<String>,
+	/// The private key of the SSH remotes.
+	pub ssh_key: Option<String>,
+}
+impl GitConfig {
+	fn merge(self, other: Self) -> Self {
+		Self {
+			proxy: other.proxy.or(self.proxy),
+			token_env: other.token_env.or(self.token_env),
+			ssh_key: other.ssh_key.or(self.ssh_key),
+		}
+	}
+}
+
+/// The `-c` options of git reaching the remote at `url`, as configured for the longest prefix of
+/// it in `git`.
+pub fn git_options(git: &HashMap<String, GitConfig>, url: &str) -> Vec<String> {
+	let git = match git
+		.iter()
+		.filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+		.max_by_key(|(prefix, _)| prefix.len())
+	{
+		Some((_, git)) => git,
+		None => return Vec::new(),
+	};
+	let mut options = Vec::new();
+
+	if let Some(proxy) = &git.proxy {
+		options.push(format!("http.proxy={}", proxy));
+	}
+	if let Some(token_env) = &git.token_env {
+		// The helper reads the token once git asks for it, keeping it out of the arguments.
+		options.push("credential.helper=".into());
+		options.push(format!(
+			"credential.helper=!f() {{ echo username=x-access-token; echo \"password=${}\"; }}; f",
+			token_env
+		));
+	}
+	if let Some(ssh_key) = &git.ssh_key {
+		options.push(format!(
+			"core.sshCommand=ssh -i {} -o IdentitiesOnly=yes",
+			ssh_key
+		));
+	}
+
+	options
+		.into_iter()
+		.flat_map(|option| ["-c".into(), option])
+		.collect()
+}
+
+/// The RPC endpoint of the runtime's chain, the configuration's first.
+pub fn rpc_url<'a>(
+	runtimes: &'a HashMap<String, RuntimeConfig>,
+	runtime: &'a Runtime,
+) -> Option<&'a str> {
+	runtimes
+		.get(runtime.name())
+		.and_then(|r| r.rpc.as_deref())
+		.or_else(|| runtime.rpc())
+}
+
+/// `$XDG_CONFIG_HOME/runtime-overrides/config.toml`, defaulting to `~/.config`.
+fn user_config_path() -> Option<PathBuf> {
+	let config_dir = env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+	Some(config_dir.join("runtime-overrides").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(toml: &str) -> Config {
+		toml::from_str(toml).unwrap()
+	}
+
+	#[test]
+	fn overlays_the_configs() {
+		let config = parse(
+			r#"
+			target = ["main"]
+			jobs = 4
+
+			[runtimes.crab]
+			features = ["evm-tracing"]
+			rpc = "http://127.0.0.1:9933"
+
+			[git."https://github.com/"]
+			proxy = "http://proxy:3128"
+			"#,
+		)
+		.merge(parse(
+			r#"
+			target = ["v0.12.3"]
+
+			[runtimes.crab]
+			rpc = "http://127.0.0.1:9944"
+
+			[runtimes.pangolin]
+			package = "pangolin-runtime"
+
+			[git."https://github.com/"]
+			token-env = "GITHUB_TOKEN"
+			"#,
+		));
+
+		assert_eq!(config.target, Some(vec!["v0.12.3".into()]));
+		assert_eq!(config.jobs, Some(4));
+
+		let crab = &config.runtimes["crab"];
+
+		assert_eq!(crab.features, Some(vec!["evm-tracing".into()]));
+		assert_eq!(crab.rpc.as_deref(), Some("http://127.0.0.1:9944"));
+		assert_eq!(
+			config.runtimes["pangolin"].package.as_deref(),
+			Some("pangolin-runtime")
+		);
+
+		let git = &config.git["https://github.com/"];
+
+		assert_eq!(git.proxy.as_deref(), Some("http://proxy:3128"));
+		assert_eq!(git.token_env.as_deref(), Some("GITHUB_TOKEN"));
+	}
+
+	#[test]
+	fn picks_the_git_options_of_the_longest_prefix() {
+		let git = parse(
+			r#"
+			[git."https://github.com/"]
+			proxy = "http://proxy:3128"
+
+			[git."https://github.com/someone/"]
+			ssh-key = "~/.ssh/someone"
+			"#,
+		)
+		.git;
+
+		for (url, options) in [
+			("https://gitlab.com/someone/darwinia", &[][..]),
+			(
+				"https://github.com/darwinia-network/darwinia",
+				&["-c", "http.proxy=http://proxy:3128"][..],
+			),
+			(
+				"https://github.com/someone/darwinia",
+				&[
+					"-c",
+					"core.sshCommand=ssh -i ~/.ssh/someone -o IdentitiesOnly=yes",
+				][..],
+			),
+		] {
+			assert_eq!(git_options(&git, url), options, "{}", url);
+		}
+	}
+
+	#[test]
+	fn keeps_the_tokens_out_of_the_arguments() {
+		let git = parse(
+			r#"
+			[git."https://github.com/"]
+			token-env = "GITHUB_TOKEN"
+			"#,
+		)
+		.git;
+		let options = git_options(&git, "https://github.com/darwinia-network/darwinia");
+
+		assert_eq!(options.len(), 4);
+		assert!(options[3].contains("$GITHUB_TOKEN"));
+	}
+}
@@ -0,0 +1,59 @@
+//! Builds inside a container image bringing the toolchains and system libraries, run by docker or
+//! podman.
+
+use std::path::Path;
+
+use crate::{AnyResult, Builder};
+
+/// Where the workspace is mounted.
+const WORKSPACE: &str = "/build";
+/// The target directory of the containers, apart from the host's one its artifacts aren't
+/// compatible with.
+pub const TARGET_DIR: &str = "target/container";
+
+/// The `engine run` command executing `program` in `image`, in the workspace of `source_dir`, with
+/// the environment `envs`, cut off from the network if `offline`.
+pub fn command(
+	builder: Builder,
+	image: &str,
+	source_dir: &Path,
+	program: &str,
+	args: &[&str],
+	envs: &[(String, String)],
+	offline: bool,
+) -> AnyResult<(&'static str, Vec<String>)> {
+	let engine = match builder {
+		Builder::Docker => "docker",
+		Builder::Podman => "podman",
+		_ => anyhow::bail!("{:?} isn't a container builder", builder),
+	};
+	let mut command = vec![
+		"run".into(),
+		"--rm".into(),
+		"-v".into(),
+		format!("{}:{}", crate::path_arg(source_dir), WORKSPACE),
+		"-w".into(),
+		WORKSPACE.into(),
+	];
+
+	// Rootless podman maps root to the user already, docker would leave root owned files behind.
+	if builder == Builder::Docker {
+		let uid = crate::runner::capture(source_dir, "id", &["-u"])?;
+		let gid = crate::runner::capture(source_dir, "id", &["-g"])?;
+
+		command.extend(["--user".into(), format!("{}:{}", uid, gid)]);
+	}
+
+	if offline {
+		command.push("--network=none".into());
+	}
+	for (key, value) in envs {
+		command.extend(["-e".into(), format!("{}={}", key, value)]);
+	}
+
+	command.push(image.into());
+	command.push(program.into());
+	command.extend(args.iter().map(|arg| arg.to_string()));
+
+	Ok((engine, command))
+}
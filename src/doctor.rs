@@ -0,0 +1,123 @@
+//! Preflight checks of the build environment.
+
+use std::{
+	collections::{BTreeSet, HashMap},
+	path::Path,
+};
+
+use clap::ArgEnum;
+
+use crate::{
+	config::{self, GitConfig},
+	registry::{Layout, Registry},
+	toolchain::WASM_TARGET,
+	AnyResult,
+};
+
+/// A runtime build, its cargo target directory included, easily takes this much.
+const MIN_FREE_SPACE: u64 = 20 * 1024 * 1024 * 1024;
+
+/// Run every check, print how to fix the failing ones and fail if there is any.
+pub fn run(
+	registry: &Registry,
+	build_dir: &Path,
+	git: &HashMap<String, GitConfig>,
+) -> AnyResult<()> {
+	let mut failures = 0;
+	let mut report = |name: &str, result: Result<String, String>| match result {
+		Ok(detail) => println!("✔ {}: {}", name, detail),
+		Err(fix) => {
+			failures += 1;
+
+			println!("✘ {}: {}", name, fix);
+		}
+	};
+
+	for (program, fix) in [
+		("git", "install git, see https://git-scm.com/downloads"),
+		(
+			"cargo",
+			"install Rust through rustup, see https://rustup.rs",
+		),
+		("rustup", "install rustup, see https://rustup.rs"),
+	] {
+		report(program, version(program).ok_or_else(|| fix.to_owned()));
+	}
+
+	report(WASM_TARGET, wasm_target());
+	report("disk space", free_space(build_dir));
+
+	let urls = registry
+		.runtimes()
+		.flat_map(|runtime| {
+			Layout::value_variants()
+				.iter()
+				.map(|layout| runtime.github(*layout))
+		})
+		.collect::<BTreeSet<_>>();
+
+	for url in urls {
+		let options = config::git_options(git, &url);
+		let args = options
+			.iter()
+			.map(String::as_str)
+			.chain(["ls-remote", "--exit-code", &url, "HEAD"])
+			.collect::<Vec<_>>();
+		let result = crate::runner::capture(Path::new("."), "git", &args)
+			.map(|_| "reachable".into())
+			.map_err(|_| {
+				"unreachable, check the network, the proxy settings and the repository URL".into()
+			});
+
+		report(&url, result);
+	}
+
+	if failures != 0 {
+		anyhow::bail!("{} check(s) failed", failures);
+	}
+
+	Ok(())
+}
+
+fn version(program: &str) -> Option<String> {
+	crate::runner::capture(Path::new("."), program, &["--version"]).ok()
+}
+
+fn wasm_target() -> Result<String, String> {
+	let installed =
+		crate::runner::capture(Path::new("."), "rustup", &["target", "list", "--installed"])
+			.map_err(|_| "could not list the installed targets, is rustup installed?".to_owned())?;
+
+	if installed.lines().any(|line| line == WASM_TARGET) {
+		Ok("installed".into())
+	} else {
+		Err(format!(
+			"missing from the default toolchain, run `rustup target add {}`",
+			WASM_TARGET
+		))
+	}
+}
+
+fn free_space(build_dir: &Path) -> Result<String, String> {
+	// The build directory may not exist yet, measure the disk it is going to be created on.
+	let existing = build_dir
+		.ancestors()
+		.find(|dir| dir.exists())
+		.unwrap_or_else(|| Path::new("."));
+	let available = fs2::available_space(existing)
+		.map_err(|e| format!("could not measure `{}`: {}", existing.display(), e))?;
+	let detail = format!(
+		"{:.1} GiB available in `{}`",
+		available as f64 / (1024 * 1024 * 1024) as f64,
+		existing.display()
+	);
+
+	if available >= MIN_FREE_SPACE {
+		Ok(detail)
+	} else {
+		Err(format!(
+			"{}, free some space or point `--build-dir` to a larger disk",
+			detail
+		))
+	}
+}
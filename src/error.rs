@@ -0,0 +1,48 @@
+//! The steps a build fails at, each with its own exit code so that CI can tell a network flake,
+//! worth a retry, from a runtime which doesn't compile.
+
+use std::{
+	error::Error,
+	fmt::{self, Display, Formatter},
+	path::PathBuf,
+};
+
+/// Attached as the context of the underlying error, find it with `anyhow::Error::downcast_ref`.
+#[derive(Debug)]
+pub enum BuildError {
+	/// Listing the refs of, cloning or fetching the repository at `url` failed.
+	CloneFailed { url: String },
+	/// Checking `label`, e.g. `darwinia@v0.12.3`, out or installing its toolchain failed.
+	CheckoutFailed { label: String },
+	/// Cargo, srtool or the container failed to build `label`, e.g. `crab@main`.
+	BuildFailed { label: String },
+	/// The build succeeded without leaving the wasm at `path`.
+	WasmMissing { path: PathBuf },
+	/// Reading the wasm of `label` or writing its artifacts failed.
+	DigestFailed { label: String },
+}
+impl BuildError {
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			Self::CloneFailed { .. } => 10,
+			Self::CheckoutFailed { .. } => 11,
+			Self::BuildFailed { .. } => 12,
+			Self::WasmMissing { .. } => 13,
+			Self::DigestFailed { .. } => 14,
+		}
+	}
+}
+impl Display for BuildError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			Self::CloneFailed { url } => write!(f, "failed to fetch {}", url),
+			Self::CheckoutFailed { label } => write!(f, "failed to check {} out", label),
+			Self::BuildFailed { label } => write!(f, "failed to compile {}", label),
+			Self::WasmMissing { path } => {
+				write!(f, "the build left no wasm at `{}`", path.display())
+			}
+			Self::DigestFailed { label } => write!(f, "failed to package {}", label),
+		}
+	}
+}
+impl Error for BuildError {}
@@ -0,0 +1,410 @@
+//! The clones, worktrees and checkouts the runtimes are built from.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use log::{debug, info, warn};
+
+use crate::{path_arg, pipeline::Job, settings::Settings, sparse, targets, AnyResult};
+
+/// Clone the job's repository if needed and fetch the latest refs, offline only check the clone.
+pub fn sync(job: &Job, clone_dir: &Path, settings: &Settings) -> AnyResult<()> {
+	let label = job.repository();
+	let repo_url = settings.repo_url(&job.runtimes[0], job.layout);
+	let broken = clone_dir.exists() && !is_intact(clone_dir, label, true, settings);
+
+	if settings.offline {
+		if broken || !clone_dir.exists() {
+			anyhow::bail!(
+				"there is no usable clone of {} in `{}` to build offline from",
+				label,
+				clone_dir.display()
+			);
+		}
+
+		return Ok(());
+	}
+	if broken {
+		warn!("{} is broken, cloning it again", clone_dir.display());
+		remove_dir(clone_dir, settings)?;
+	}
+	// In dry-run mode the broken clone is still there.
+	if broken || !clone_dir.exists() {
+		let parent = clone_dir.parent().unwrap_or_else(|| Path::new("."));
+
+		if !settings.dry_run {
+			fs::create_dir_all(parent)?;
+		}
+
+		let clone_dir = path_arg(clone_dir);
+		// The targets are fetched and checked out on their own afterwards.
+		let mut args = vec!["clone", "--no-checkout"];
+
+		if settings.shallow {
+			args.push("--depth=1");
+		}
+
+		args.extend([repo_url.as_str(), clone_dir.as_str()]);
+
+		info!("Cloning {}", repo_url);
+		settings.retry(label, || {
+			settings
+				.remote_git(parent, label, &repo_url, &args)
+				.map_err(|e| {
+					// Otherwise the next attempt would refuse to clone into it.
+					let _ = fs::remove_dir_all(&clone_dir);
+
+					e
+				})
+		})?;
+	} else if settings.capture(clone_dir, "git", &["remote", "get-url", "origin"])? != repo_url {
+		info!("Pointing {} to {}", label, repo_url);
+		settings.run(
+			clone_dir,
+			label,
+			"git",
+			&["remote", "set-url", "origin", &repo_url],
+		)?;
+	}
+
+	if settings.shallow {
+		return Ok(());
+	}
+
+	info!("Fetching {}", label);
+	settings.retry(label, || {
+		settings.remote_git(clone_dir, label, &repo_url, &["fetch", "--all"])
+	})
+}
+
+/// Fetch `job.target` into `job.revision()`, only its last commit if the clone is shallow.
+///
+/// Offline, it is only looked up in the clone.
+pub fn fetch_target(job: &Job, clone_dir: &Path, settings: &Settings) -> AnyResult<()> {
+	let label = job.label();
+	let revision = job.revision();
+
+	if !settings.offline && fetch_ref(job, clone_dir, settings)? {
+		return Ok(());
+	}
+
+	let commit = local_commit(clone_dir, &job.target, settings).ok_or_else(|| {
+		anyhow::anyhow!(
+			"`{}` is neither a branch, a tag nor a commit of {}",
+			job.target,
+			job.repository()
+		)
+	})?;
+
+	settings.run(
+		clone_dir,
+		&label,
+		"git",
+		&["update-ref", &revision, &commit],
+	)
+}
+
+/// Fetch `job.target` alone, or the whole history if it can't be.
+///
+/// Returns whether it was fetched into `job.revision()`, it has to be looked up otherwise.
+fn fetch_ref(job: &Job, clone_dir: &Path, settings: &Settings) -> AnyResult<bool> {
+	let label = job.label();
+	let revision = job.revision();
+	let repo_url = settings.repo_url(&job.runtimes[0], job.layout);
+	let refspec = format!("+{}:{}", job.target, revision);
+	// Deepening an existing complete clone would only throw its history away.
+	let shallow = settings.shallow
+		&& (settings.dry_run
+			|| settings.capture(clone_dir, "git", &["rev-parse", "--is-shallow-repository"])?
+				== "true");
+	let mut args = vec!["fetch", "origin", refspec.as_str()];
+
+	if shallow {
+		args.insert(1, "--depth=1");
+	}
+
+	info!("Fetching {}", label);
+
+	// Abbreviated commits can't be fetched, they are only found in the whole history.
+	let result = if targets::is_commit(&job.target) && job.target.len() < 40 {
+		Err(anyhow::anyhow!("`{}` is an abbreviated commit", job.target))
+	} else {
+		settings.retry(&label, || {
+			settings.remote_git(clone_dir, &label, &repo_url, &args)
+		})
+	};
+
+	match result {
+		Ok(()) => return Ok(true),
+		Err(e) if shallow => {
+			warn!(
+				"Failed to fetch {} alone, fetching the whole history: {:#}",
+				label, e
+			);
+
+			settings.run(
+				clone_dir,
+				&label,
+				"git",
+				&[
+					"config",
+					"remote.origin.fetch",
+					"+refs/heads/*:refs/remotes/origin/*",
+				],
+			)?;
+			settings.retry(&label, || {
+				settings.remote_git(
+					clone_dir,
+					&label,
+					&repo_url,
+					&["fetch", "--unshallow", "--tags", "origin"],
+				)
+			})?;
+		}
+		Err(e) => debug!(
+			"Failed to fetch {} alone, looking it up locally: {:#}",
+			label, e
+		),
+	}
+
+	Ok(false)
+}
+
+/// The commit `target` stands for in the clone, a remote branch first.
+pub fn local_commit(clone_dir: &Path, target: &str, settings: &Settings) -> Option<String> {
+	[format!("origin/{}", target), target.to_owned()]
+		.iter()
+		.find_map(|name| {
+			settings
+				.capture(
+					clone_dir,
+					"git",
+					&[
+						"rev-parse",
+						"--verify",
+						"--quiet",
+						&format!("{}^{{commit}}", name),
+					],
+				)
+				.ok()
+		})
+}
+
+/// Check `job.revision()` out in `dir`, discarding whatever a previous build left behind.
+pub fn checkout(dir: &Path, job: &Job, settings: &Settings) -> AnyResult<()> {
+	let label = job.label();
+
+	if settings.sparse {
+		sparse::set(dir, job, settings)?;
+	} else if !settings.dry_run
+		&& settings
+			.capture(dir, "git", &["config", "--get", "core.sparseCheckout"])
+			.map_or(false, |sparse| sparse == "true")
+	{
+		settings.run(dir, &label, "git", &["sparse-checkout", "disable"])?;
+	}
+
+	settings.run(
+		dir,
+		&label,
+		"git",
+		&["checkout", "--force", "--detach", &job.revision()],
+	)?;
+
+	// Some older branches vendor their dependencies as submodules.
+	if dir.join(".gitmodules").is_file() {
+		let repo_url = settings.repo_url(&job.runtimes[0], job.layout);
+		let mut args = vec!["submodule", "update", "--init", "--recursive", "--force"];
+
+		if settings.offline {
+			args.push("--no-fetch");
+		}
+
+		info!("Updating the submodules of {}", label);
+		settings.run(dir, &label, "git", &["submodule", "sync", "--recursive"])?;
+		settings.retry(&label, || {
+			settings.remote_git(dir, &label, &repo_url, &args)
+		})?;
+	}
+
+	for (patch, _) in &settings.patches {
+		info!("Applying {} to {}", patch.display(), label);
+		settings
+			.run(dir, &label, "git", &["apply", &path_arg(patch)])
+			.with_context(|| format!("failed to apply `{}`", patch.display()))?;
+	}
+
+	if !settings.cargo_patch.is_empty() {
+		info!("Patching the dependencies of {}", label);
+
+		if !settings.dry_run {
+			patch_manifest(dir, settings)
+				.with_context(|| format!("failed to patch the workspace manifest of {}", label))?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Add the `[cargo-patch]` sections of the config to the workspace manifest in `dir`, keeping the
+/// original as `Cargo.toml.orig` for `revert_patches`.
+///
+/// The crates the manifest patches already are overridden.
+fn patch_manifest(dir: &Path, settings: &Settings) -> AnyResult<()> {
+	let path = dir.join("Cargo.toml");
+	let content = fs::read_to_string(&path)?;
+	let mut manifest = content.parse::<toml::Value>()?;
+	let patch = manifest
+		.as_table_mut()
+		.context("the manifest isn't a table")?
+		.entry("patch")
+		.or_insert_with(|| toml::value::Table::new().into())
+		.as_table_mut()
+		.context("`patch` isn't a table")?;
+
+	for (source, crates) in &settings.cargo_patch {
+		let sources = patch
+			.entry(source.clone())
+			.or_insert_with(|| toml::value::Table::new().into())
+			.as_table_mut()
+			.with_context(|| format!("`patch.{}` isn't a table", source))?;
+
+		for (name, dependency) in crates {
+			sources.insert(name.clone(), dependency.clone());
+		}
+	}
+
+	fs::write(dir.join("Cargo.toml.orig"), &content)?;
+	fs::write(&path, toml::to_string(&manifest)?)?;
+
+	Ok(())
+}
+
+/// The commit of each submodule of the checkout in `dir`, keyed by path, null if it has none.
+pub fn submodules(dir: &Path, settings: &Settings) -> AnyResult<serde_json::Value> {
+	if !dir.join(".gitmodules").is_file() {
+		return Ok(serde_json::Value::Null);
+	}
+
+	let status = settings.capture(dir, "git", &["submodule", "status", "--recursive"])?;
+
+	// ` <COMMIT> <PATH> (<DESCRIBE>)`, the first column telling whether it is checked out.
+	Ok(status
+		.lines()
+		.filter_map(|line| {
+			let mut columns = line
+				.trim_start_matches(|c| matches!(c, ' ' | '-' | '+' | 'U'))
+				.split_whitespace();
+			let commit = columns.next()?;
+
+			Some((columns.next()?.to_owned(), commit.into()))
+		})
+		.collect::<serde_json::Map<_, _>>()
+		.into())
+}
+
+/// Whether the working tree in `dir` has uncommitted changes, untracked files included.
+pub fn is_dirty(dir: &Path, settings: &Settings) -> AnyResult<bool> {
+	Ok(!settings
+		.capture(dir, "git", &["status", "--porcelain"])?
+		.is_empty())
+}
+
+/// Undo the patches `checkout` applied, not to leave the files they add in the next checkouts.
+pub fn revert_patches(dir: &Path, label: &str, settings: &Settings) {
+	let original = dir.join("Cargo.toml.orig");
+
+	if !settings.cargo_patch.is_empty() && original.exists() {
+		if let Err(e) = fs::rename(&original, dir.join("Cargo.toml")) {
+			warn!(
+				"Failed to restore the workspace manifest of {}: {}",
+				label, e
+			);
+		}
+	}
+	for (patch, _) in settings.patches.iter().rev() {
+		if let Err(e) = settings.run(dir, label, "git", &["apply", "--reverse", &path_arg(patch)]) {
+			warn!("Failed to revert `{}`: {:#}", patch.display(), e);
+		}
+	}
+}
+
+/// Create or update the detached worktree dedicated to `job`.
+pub fn add_worktree(job: &Job, settings: &Settings) -> AnyResult<()> {
+	let label = job.label();
+	let worktree_dir = job.worktree_dir(&settings.build_dir);
+
+	info!("Preparing the worktree of {}", label);
+
+	// Its clone may have been cloned again since.
+	let broken = worktree_dir.exists() && !is_intact(&worktree_dir, &label, false, settings);
+
+	if broken {
+		warn!("{} is broken, adding it again", worktree_dir.display());
+		remove_dir(&worktree_dir, settings)?;
+	}
+	if broken || !worktree_dir.exists() {
+		let clone_dir = job.clone_dir(&settings.build_dir);
+
+		settings.run(&clone_dir, &label, "git", &["worktree", "prune"])?;
+		settings.run(
+			&clone_dir,
+			&label,
+			"git",
+			&[
+				"worktree",
+				"add",
+				"--force",
+				"--detach",
+				"--no-checkout",
+				&path_arg(&worktree_dir),
+				&job.revision(),
+			],
+		)?;
+	}
+
+	checkout(&worktree_dir, job, settings)
+}
+
+/// Whether `dir` holds a usable checkout, an interrupted clone or a dangling worktree doesn't.
+///
+/// Checking the connectivity of the objects takes a while, it's only worth it for the clones.
+fn is_intact(dir: &Path, label: &str, fsck: bool, settings: &Settings) -> bool {
+	// Without its own `.git`, git would happily pick up a parent repository.
+	if !dir.join(".git").exists() {
+		return false;
+	}
+	if settings
+		.capture(
+			dir,
+			"git",
+			&["rev-parse", "--verify", "--quiet", "HEAD^{commit}"],
+		)
+		.is_err()
+	{
+		return false;
+	}
+
+	!fsck
+		|| settings
+			.runner
+			.run(
+				dir,
+				label,
+				"git",
+				&["fsck", "--connectivity-only", "--no-dangling"],
+				&[],
+				None,
+			)
+			.is_ok()
+}
+
+fn remove_dir(dir: &Path, settings: &Settings) -> AnyResult<()> {
+	if settings.dry_run {
+		info!("[dry-run] rm -r {}", dir.display());
+
+		Ok(())
+	} else {
+		fs::remove_dir_all(dir).with_context(|| format!("failed to remove `{}`", dir.display()))
+	}
+}
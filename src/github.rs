@@ -0,0 +1,166 @@
+//! The bits of the GitHub API the pseudo-targets are resolved and the artifacts published with.
+
+use std::{env, fs, path::PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::AnyResult;
+
+/// The pseudo-target standing for the newest stable release of the runtime's repository.
+pub const LATEST_RELEASE: &str = "latest-release";
+
+const API: &str = "https://api.github.com";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+	id: u64,
+	tag_name: String,
+	html_url: String,
+	upload_url: String,
+	assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+	id: u64,
+	name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+	html_url: String,
+}
+
+/// `owner/name` of a GitHub repository URL, e.g. `https://github.com/darwinia-network/darwinia.git`.
+pub fn repository(url: &str) -> Option<&str> {
+	let path = url
+		.strip_prefix("https://github.com/")
+		.or_else(|| url.strip_prefix("git@github.com:"))?
+		.trim_end_matches('/');
+	let path = path.strip_suffix(".git").unwrap_or(path);
+
+	(path.split('/').count() == 2).then_some(path)
+}
+
+/// The tag of the repository's newest release, drafts and pre-releases aside.
+pub fn latest_release(url: &str) -> AnyResult<String> {
+	let repository = repository(url).with_context(|| {
+		format!(
+			"`{}` isn't a GitHub repository, `{}` can't be resolved",
+			url, LATEST_RELEASE
+		)
+	})?;
+	let url = format!("{}/repos/{}/releases/latest", API, repository);
+	let release = request("GET", &url, env::var("GITHUB_TOKEN").ok().as_deref())
+		.call()
+		.with_context(|| format!("failed to query `{}`", url))?
+		.into_json::<Release>()
+		.with_context(|| format!("failed to parse the latest release of {}", repository))?;
+
+	Ok(release.tag_name)
+}
+
+/// Create the release `tag` of `repository`, or update its notes if it exists, and upload `files`
+/// to it, replacing the assets of the same name.
+///
+/// Returns the URL of the release.
+pub fn publish_release(
+	repository: &str,
+	tag: &str,
+	notes: &str,
+	files: &[PathBuf],
+	token: &str,
+) -> AnyResult<String> {
+	let url = format!("{}/repos/{}/releases", API, repository);
+	let existing = match request("GET", &format!("{}/tags/{}", url, tag), Some(token)).call() {
+		Ok(response) => Some(response.into_json::<Release>()?),
+		Err(ureq::Error::Status(404, _)) => None,
+		Err(e) => return Err(e).with_context(|| format!("failed to query the release {}", tag)),
+	};
+	let release = match existing {
+		Some(release) => request("PATCH", &format!("{}/{}", url, release.id), Some(token))
+			.send_json(serde_json::json!({ "body": notes })),
+		None => request("POST", &url, Some(token)).send_json(serde_json::json!({
+			"tag_name": tag,
+			"name": tag,
+			"body": notes,
+		})),
+	}
+	.with_context(|| format!("failed to write the release {} of {}", tag, repository))?
+	.into_json::<Release>()?;
+	// `https://uploads.github.com/repos/<REPOSITORY>/releases/<ID>/assets{?name,label}`
+	let upload_url = release
+		.upload_url
+		.split('{')
+		.next()
+		.unwrap_or(&release.upload_url);
+
+	for file in files {
+		let name = file.file_name().unwrap_or_default().to_string_lossy();
+
+		if let Some(asset) = release.assets.iter().find(|asset| asset.name == name) {
+			request(
+				"DELETE",
+				&format!("{}/assets/{}", url, asset.id),
+				Some(token),
+			)
+			.call()
+			.with_context(|| format!("failed to replace the asset {}", name))?;
+		}
+
+		request("POST", upload_url, Some(token))
+			.query("name", &name)
+			.set("Content-Type", "application/octet-stream")
+			.send_bytes(&fs::read(file)?)
+			.with_context(|| format!("failed to upload `{}`", file.display()))?;
+	}
+
+	Ok(release.html_url)
+}
+
+/// A request to the API, authenticated with `token` if any, to get past the rate limit.
+fn request(method: &str, url: &str, token: Option<&str>) -> ureq::Request {
+	let request = ureq::request(method, url)
+		.set("Accept", "application/vnd.github+json")
+		.set(
+			"User-Agent",
+			concat!("runtime-overrides/", env!("CARGO_PKG_VERSION")),
+		);
+
+	match token {
+		Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+		None => request,
+	}
+}
+
+/// Open a pull request of `head` into `base`, returning its URL.
+pub fn open_pull_request(
+	repository: &str,
+	head: &str,
+	base: &str,
+	title: &str,
+	body: &str,
+	token: &str,
+) -> AnyResult<String> {
+	let pull_request = request(
+		"POST",
+		&format!("{}/repos/{}/pulls", API, repository),
+		Some(token),
+	)
+	.send_json(serde_json::json!({
+		"title": title,
+		"head": head,
+		"base": base,
+		"body": body,
+	}))
+	.with_context(|| {
+		format!(
+			"failed to open a pull request of {} on {}",
+			head, repository
+		)
+	})?
+	.into_json::<PullRequest>()?;
+
+	Ok(pull_request.html_url)
+}
@@ -0,0 +1,163 @@
+//! Every build this machine ran, successful or not, appended to `<BUILD_DIR>/history.jsonl`, for
+//! `history` to tell when and from what a runtime was last built.
+
+use std::{
+	fs::{self, OpenOptions},
+	io::Write,
+	path::Path,
+	time::Duration,
+};
+
+use anyhow::Context;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::HistoryArgs, registry::Runtime, AnyResult, Artifact};
+
+pub const FILE_NAME: &str = "history.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Record {
+	/// RFC 3339.
+	finished_at: String,
+	runtime: String,
+	target: String,
+	commit: Option<String>,
+	features: String,
+	spec_version: Option<u32>,
+	blake2_256: Option<String>,
+	/// In seconds.
+	duration: f64,
+	/// Reused from a previous build instead of built again.
+	cached: bool,
+	/// The error of a failed build.
+	error: Option<String>,
+}
+impl Record {
+	pub fn built(artifact: &Artifact, features: String) -> Self {
+		Self {
+			finished_at: chrono::Utc::now().to_rfc3339(),
+			runtime: artifact.runtime.clone(),
+			target: artifact.target.clone(),
+			commit: Some(artifact.commit.clone()),
+			features,
+			spec_version: artifact.spec_version,
+			blake2_256: Some(artifact.hashes.blake2_256.clone()),
+			duration: artifact.timings.total,
+			cached: artifact.cached,
+			error: None,
+		}
+	}
+
+	pub fn failed(
+		runtime: &Runtime,
+		target: &str,
+		features: String,
+		duration: Duration,
+		error: &anyhow::Error,
+	) -> Self {
+		Self {
+			finished_at: chrono::Utc::now().to_rfc3339(),
+			runtime: runtime.name().into(),
+			target: target.into(),
+			commit: None,
+			features,
+			spec_version: None,
+			blake2_256: None,
+			duration: duration.as_secs_f64(),
+			cached: false,
+			error: Some(format!("{:#}", error)),
+		}
+	}
+
+	fn matches(&self, args: &HistoryArgs) -> bool {
+		let runtime = args.runtime.is_empty()
+			|| args
+				.runtime
+				.iter()
+				.any(|runtime| runtime.eq_ignore_ascii_case(&self.runtime));
+		let commit = args.commit.as_ref().map_or(true, |prefix| {
+			self.commit
+				.as_ref()
+				.map_or(false, |commit| commit.starts_with(prefix.as_str()))
+		});
+		let target = args.target.as_ref().map_or(true, |t| *t == self.target);
+		let spec_version = args
+			.spec_version
+			.map_or(true, |v| self.spec_version == Some(v));
+		let features = args.features.as_ref().map_or(true, |f| *f == self.features);
+		let result = match (args.failed, args.succeeded) {
+			(true, _) => self.error.is_some(),
+			(_, true) => self.error.is_none(),
+			_ => true,
+		};
+
+		runtime && commit && target && spec_version && features && result
+	}
+}
+
+/// Append `record` to the history of `build_dir`, locked against the concurrent builds.
+pub fn append(build_dir: &Path, record: &Record) -> AnyResult<()> {
+	let path = build_dir.join(FILE_NAME);
+	let mut line = serde_json::to_vec(record)?;
+
+	line.push(b'\n');
+
+	fs::create_dir_all(build_dir)
+		.and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+		.and_then(|mut file| {
+			file.lock_exclusive()?;
+			file.write_all(&line)?;
+			file.unlock()
+		})
+		.with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+/// Print the builds matching the filters of `args`, the latest first.
+pub fn run(args: HistoryArgs, build_dir: &Path) -> AnyResult<()> {
+	let path = build_dir.join(FILE_NAME);
+	let content = match fs::read_to_string(&path) {
+		Ok(content) => content,
+		Err(_) => {
+			anyhow::bail!("`{}` doesn't exist, nothing was built yet", path.display())
+		}
+	};
+	let records = content
+		.lines()
+		.rev()
+		// A line cut short by a killed build is skipped.
+		.filter_map(|line| serde_json::from_str::<Record>(line).ok())
+		.filter(|record| record.matches(&args))
+		.take(args.limit.unwrap_or(usize::MAX))
+		.collect::<Vec<_>>();
+
+	if args.json {
+		println!("{}", serde_json::to_string_pretty(&records)?);
+
+		return Ok(());
+	}
+
+	for record in records {
+		let result = match &record.error {
+			Some(error) => format!("failed: {}", error.lines().next().unwrap_or_default()),
+			None if record.cached => "cached".into(),
+			None => "built".into(),
+		};
+
+		println!(
+			"{}  {}@{}  spec {}  commit {}  features {}  {:.0}s  {}",
+			record.finished_at,
+			record.runtime,
+			record.target,
+			record
+				.spec_version
+				.map_or_else(|| "-".into(), |version| version.to_string()),
+			record.commit.as_deref().unwrap_or("-"),
+			record.features,
+			record.duration,
+			result
+		);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,89 @@
+//! `index.json`, the manifest of every artifact of the output directory.
+
+use std::{
+	fs::{self, File},
+	path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{archive, AnyResult};
+
+pub const FILE_NAME: &str = "index.json";
+
+#[derive(Debug, Serialize)]
+struct Entry {
+	chain: String,
+	spec_version: Option<u32>,
+	/// Relative to the output directory, e.g. `crab/wasms/<name>.compact.compressed.wasm`.
+	file: String,
+	size: u64,
+	blake2_256: Option<String>,
+	commit: Option<String>,
+	/// Of the wasm on IPFS, if it was added.
+	cid: Option<String>,
+}
+
+/// Regenerate the index from the digests found in `output_dir`.
+pub fn write(output_dir: &Path) -> AnyResult<PathBuf> {
+	let mut entries = Vec::new();
+
+	for chain_dir in crate::dir_entries(output_dir) {
+		// The archive has an index of its own.
+		if chain_dir.ends_with(archive::DIR) {
+			continue;
+		}
+
+		let chain = chain_dir
+			.file_name()
+			.unwrap_or_default()
+			.to_string_lossy()
+			.into_owned();
+
+		for digest in crate::dir_entries(&chain_dir.join("digests")) {
+			let name = match digest.file_name().unwrap_or_default().to_str() {
+				Some(name) if !name.starts_with('.') => name,
+				_ => continue,
+			};
+			let name = match name.strip_suffix(".json") {
+				Some(name) => format!("{}.compact.compressed.wasm", name),
+				None => continue,
+			};
+			let runtime_info = match crate::artifact::read_digest(&digest) {
+				Some(runtime_info) => runtime_info,
+				None => continue,
+			};
+			let size = match fs::metadata(chain_dir.join("wasms").join(&name)) {
+				Ok(metadata) => metadata.len(),
+				Err(_) => continue,
+			};
+			let field = |name: &str| runtime_info[name].as_str().map(String::from);
+
+			entries.push(Entry {
+				chain: chain.clone(),
+				spec_version: runtime_info["core_version"]
+					.as_str()
+					.and_then(crate::artifact::spec_version),
+				file: format!("{}/wasms/{}", chain, name),
+				size,
+				blake2_256: field("blake2_256"),
+				commit: field("commit"),
+				cid: runtime_info[crate::artifact::PROVENANCE]["cid"]
+					.as_str()
+					.map(String::from),
+			});
+		}
+	}
+
+	entries.sort_by(|a, b| {
+		(&a.chain, a.spec_version, &a.file).cmp(&(&b.chain, b.spec_version, &b.file))
+	});
+
+	let path = output_dir.join(FILE_NAME);
+	let tmp = crate::temporary_path(&path);
+
+	serde_json::to_writer_pretty(File::create(&tmp)?, &entries)?;
+	fs::rename(&tmp, &path)?;
+
+	Ok(path)
+}
@@ -0,0 +1,156 @@
+//! Installing the artifacts into the `--wasm-runtime-overrides` directory of a node, one wasm per
+//! spec version, listed in `index.json` there.
+
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{AnyResult, Artifact};
+
+const INDEX: &str = "index.json";
+
+/// An installed wasm, keyed by its file name in the index.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+	runtime: Option<String>,
+	target: Option<String>,
+	commit: Option<String>,
+	spec_name: String,
+	spec_version: u32,
+	blake2_256: Option<String>,
+}
+
+/// The wasms of a node's override directory.
+pub struct Overrides {
+	dir: PathBuf,
+	entries: BTreeMap<String, Entry>,
+}
+impl Overrides {
+	/// Read the index of `dir`, describing the wasms it doesn't list from their runtime version.
+	pub fn load(dir: &Path) -> AnyResult<Self> {
+		fs::create_dir_all(dir).with_context(|| format!("failed to create `{}`", dir.display()))?;
+
+		let index_path = dir.join(INDEX);
+		let mut entries = match fs::read(&index_path) {
+			Ok(content) => serde_json::from_slice::<BTreeMap<String, Entry>>(&content)
+				.with_context(|| format!("failed to parse `{}`", index_path.display()))?,
+			Err(_) => BTreeMap::new(),
+		};
+
+		entries.retain(|name, _| dir.join(name).is_file());
+
+		for path in crate::dir_entries(dir) {
+			let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+			if !name.ends_with(".wasm") || entries.contains_key(name.as_ref()) {
+				continue;
+			}
+
+			let runtime_info =
+				serde_json::to_value(Subwasm::new(&Source::File(path.clone())).runtime_info())?;
+			let (spec_name, spec_version) = spec(&runtime_info).with_context(|| {
+				format!("the runtime version of `{}` is unknown", path.display())
+			})?;
+
+			entries.insert(
+				name.into_owned(),
+				Entry {
+					runtime: None,
+					target: None,
+					commit: None,
+					spec_name,
+					spec_version,
+					blake2_256: None,
+				},
+			);
+		}
+
+		Ok(Self {
+			dir: dir.into(),
+			entries,
+		})
+	}
+
+	/// Copy the wasm of `artifact` as `<SPEC_NAME>-<SPEC_VERSION>.compact.compressed.wasm`, unless
+	/// another wasm of the same spec version is there, replaced only if `force`.
+	pub fn install(&mut self, artifact: &Artifact, force: bool) -> AnyResult<PathBuf> {
+		let runtime_info = crate::artifact::read_digest(&artifact.digest)
+			.with_context(|| format!("failed to read `{}`", artifact.digest.display()))?;
+		let (spec_name, spec_version) = spec(&runtime_info).with_context(|| {
+			format!(
+				"the spec version of `{}` is unknown",
+				artifact.wasm.display()
+			)
+		})?;
+		let name = format!("{}-{}.compact.compressed.wasm", spec_name, spec_version);
+		let path = self.dir.join(&name);
+		let blake2_256 = &artifact.hashes.blake2_256;
+
+		if let Some((other, entry)) = self
+			.entries
+			.iter()
+			.find(|(_, entry)| entry.spec_name == spec_name && entry.spec_version == spec_version)
+		{
+			if *other == name && entry.blake2_256.as_ref() == Some(blake2_256) {
+				return Ok(path);
+			}
+			if !force {
+				anyhow::bail!(
+					"`{}` already holds {}-{}, the node would load only one of them, pass `--force` to replace it",
+					self.dir.join(other).display(),
+					spec_name,
+					spec_version
+				);
+			}
+
+			let other = other.clone();
+
+			fs::remove_file(self.dir.join(&other))
+				.with_context(|| format!("failed to remove `{}`", other))?;
+			self.entries.remove(&other);
+		}
+
+		crate::copy_file(&artifact.wasm, &path)?;
+		self.entries.insert(
+			name,
+			Entry {
+				runtime: Some(artifact.runtime.clone()),
+				target: Some(artifact.target.clone()),
+				commit: Some(artifact.commit.clone()),
+				spec_name,
+				spec_version,
+				blake2_256: Some(blake2_256.clone()),
+			},
+		);
+		self.write()?;
+
+		Ok(path)
+	}
+
+	fn write(&self) -> AnyResult<()> {
+		let path = self.dir.join(INDEX);
+		let tmp = crate::temporary_path(&path);
+
+		fs::write(&tmp, serde_json::to_vec_pretty(&self.entries)?)
+			.and_then(|()| fs::rename(&tmp, &path))
+			.with_context(|| format!("failed to write `{}`", path.display()))
+	}
+}
+
+/// The spec name and version of a runtime info, e.g. `("crab", 1230)` for `crab-1230 (...)`.
+fn spec(runtime_info: &serde_json::Value) -> Option<(String, u32)> {
+	let (spec_name, spec_version) = runtime_info["core_version"]
+		.as_str()?
+		.split_whitespace()
+		.next()?
+		.rsplit_once('-')?;
+
+	Some((spec_name.into(), spec_version.parse().ok()?))
+}
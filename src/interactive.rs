@@ -0,0 +1,112 @@
+//! Prompting for a build when run without arguments on a terminal.
+
+use std::env;
+
+use clap::ArgEnum;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+
+use crate::{
+	config::{self, Config},
+	registry::{Layout, Registry},
+	targets::{self, RefKind},
+	AnyResult, SystemRunner,
+};
+
+/// Number of tags offered, the most recent ones.
+const RECENT_TAGS: usize = 10;
+
+/// Whether nothing was asked for, neither through the arguments nor the environment, by someone at a
+/// terminal.
+pub fn wanted() -> bool {
+	env::args_os().len() == 1
+		&& env::var_os("RO_RUNTIME").is_none()
+		&& unsafe {
+			libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDERR_FILENO) == 1
+		}
+}
+
+/// Ask for the runtime, the target and the features, then return the arguments building them, none
+/// if the plan isn't confirmed.
+pub fn prompt() -> AnyResult<Option<Vec<String>>> {
+	let config = Config::load(None)?;
+	let registry = Registry::load(config.registry.as_deref())?;
+	let theme = ColorfulTheme::default();
+	let runtimes = registry.runtimes().collect::<Vec<_>>();
+	let runtime = runtimes[Select::with_theme(&theme)
+		.with_prompt("Runtime")
+		.items(&runtimes.iter().map(|r| r.name()).collect::<Vec<_>>())
+		.default(0)
+		.interact()?];
+	let overrides = config.runtimes.get(runtime.name());
+	let mut tags = Vec::<String>::new();
+
+	eprintln!("Fetching the tags of {}...", runtime.name());
+
+	for layout in Layout::value_variants() {
+		let url = overrides
+			.and_then(|r| r.repo_url.clone())
+			.unwrap_or_else(|| runtime.github(*layout));
+		let git = config::git_options(&config.git, &url);
+
+		for r in targets::remote_refs(&url, &git, &SystemRunner)? {
+			if r.kind == RefKind::Tag {
+				tags.retain(|tag| *tag != r.name);
+				tags.push(r.name);
+			}
+		}
+	}
+
+	let mut items = tags.into_iter().rev().take(RECENT_TAGS).collect::<Vec<_>>();
+
+	items.extend(["main".into(), "another branch, tag or commit".into()]);
+
+	let choice = Select::with_theme(&theme)
+		.with_prompt("Target")
+		.items(&items)
+		.default(0)
+		.interact()?;
+	let target = if choice == items.len() - 1 {
+		Input::<String>::with_theme(&theme)
+			.with_prompt("Branch, tag or commit")
+			.interact_text()?
+	} else {
+		items.swap_remove(choice)
+	};
+	let features = Input::<String>::with_theme(&theme)
+		.with_prompt("Features")
+		.default(
+			overrides
+				.and_then(|r| r.features.as_deref())
+				.or(config.features.as_deref())
+				.or_else(|| runtime.features())
+				.map(|features| features.join(","))
+				.unwrap_or_else(|| "evm-tracing".into()),
+		)
+		.interact_text()?;
+	let output_dir = config
+		.output_dir
+		.unwrap_or_else(|| "overridden-runtimes".into());
+
+	if !Confirm::with_theme(&theme)
+		.with_prompt(format!(
+			"Build {} at {} with `{}` into {}?",
+			runtime.name(),
+			target,
+			features,
+			output_dir.display()
+		))
+		.default(true)
+		.interact()?
+	{
+		return Ok(None);
+	}
+
+	Ok(Some(vec![
+		"--runtime".into(),
+		runtime.name().into(),
+		"--target".into(),
+		target,
+		"--features".into(),
+		features,
+	]))
+}
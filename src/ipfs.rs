@@ -0,0 +1,92 @@
+//! Distributing the wasms over IPFS: added to a daemon, pinned by a remote pinning service too if
+//! asked, and fetched back by CID.
+
+use std::{
+	fs::{self, File},
+	io,
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::AnyResult;
+
+pub const DEFAULT_GATEWAY: &str = "https://ipfs.io";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Added {
+	hash: String,
+}
+
+/// Add and pin `wasm` on the daemon whose API is at `api`, e.g. `http://127.0.0.1:5001`, returning
+/// its CID.
+pub fn add(api: &str, wasm: &Path) -> AnyResult<String> {
+	let boundary = format!(
+		"runtime-overrides-{}",
+		SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_nanos()
+	);
+	let mut body = format!(
+		"--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+		boundary,
+		wasm.file_name().unwrap_or_default().to_string_lossy()
+	)
+	.into_bytes();
+
+	body.extend(fs::read(wasm)?);
+	body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+
+	let url = format!(
+		"{}/api/v0/add?pin=true&cid-version=1",
+		api.trim_end_matches('/')
+	);
+	let added = ureq::post(&url)
+		.set(
+			"Content-Type",
+			&format!("multipart/form-data; boundary={}", boundary),
+		)
+		.send_bytes(&body)
+		.with_context(|| format!("failed to add `{}` to {}", wasm.display(), api))?
+		.into_json::<Added>()?;
+
+	Ok(added.hash)
+}
+
+/// Ask the service at `service`, implementing the IPFS Pinning Service API, to pin `cid` as `name`.
+pub fn pin_remotely(service: &str, token: &str, cid: &str, name: &str) -> AnyResult<()> {
+	ureq::post(&format!("{}/pins", service.trim_end_matches('/')))
+		.set("Authorization", &format!("Bearer {}", token))
+		.send_json(serde_json::json!({ "cid": cid, "name": name }))
+		.with_context(|| format!("failed to pin {} on {}", cid, service))?;
+
+	Ok(())
+}
+
+/// Download `cid` to `path`, through the daemon whose API is at `api` if any, from `gateway`
+/// otherwise.
+pub fn fetch(cid: &str, api: Option<&str>, gateway: &str, path: &Path) -> AnyResult<()> {
+	if cid.is_empty() || !cid.chars().all(|c| c.is_ascii_alphanumeric()) {
+		anyhow::bail!("invalid CID `{}`", cid);
+	}
+
+	let response = match api {
+		Some(api) => ureq::post(&format!(
+			"{}/api/v0/cat?arg={}",
+			api.trim_end_matches('/'),
+			cid
+		))
+		.call(),
+		None => ureq::get(&format!("{}/ipfs/{}", gateway.trim_end_matches('/'), cid)).call(),
+	}
+	.with_context(|| format!("failed to fetch {}", cid))?;
+
+	io::copy(&mut response.into_reader(), &mut File::create(path)?)
+		.with_context(|| format!("failed to fetch {}", cid))?;
+
+	Ok(())
+}
@@ -0,0 +1,225 @@
+//! Build the EVM tracing runtimes loaded by Darwinia nodes through `--wasm-runtime-overrides`, from
+//! the command line with [`cli()`] or from Rust with [`RuntimeBuilder`].
+
+pub use anyhow::Result as AnyResult;
+
+mod apis;
+mod archive;
+mod artifact;
+mod builder;
+mod changelog;
+mod checksums;
+mod cli;
+mod completions;
+mod config;
+mod container;
+mod doctor;
+mod error;
+mod git;
+mod github;
+mod history;
+mod index;
+mod install;
+mod interactive;
+mod ipfs;
+mod list;
+mod metrics;
+mod migrate;
+mod notify;
+mod oci;
+mod package;
+mod pipeline;
+mod progress;
+mod proposal;
+mod prune;
+mod publish;
+mod pull_request;
+mod registry;
+mod rpc;
+mod runner;
+mod s3;
+mod schema;
+mod serve;
+mod settings;
+mod signing;
+mod size;
+mod smoke;
+mod sparse;
+mod srtool;
+mod store;
+mod targets;
+mod toolchain;
+mod try_runtime;
+mod verify;
+mod wasm_opt;
+mod watch;
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use anyhow::Context;
+
+pub use artifact::{Artifact, MetadataFormat};
+pub use builder::RuntimeBuilder;
+pub use cli::cli;
+pub use error::BuildError;
+pub use runner::{CommandRunner, SystemRunner};
+pub use settings::Builder;
+
+fn create_dir_unchecked(path: &Path) -> AnyResult<()> {
+	if !path.exists() {
+		fs::create_dir_all(path)?;
+	}
+
+	Ok(())
+}
+
+/// Rename `from` to `to`, copying instead if they live on different file systems.
+fn move_file(from: &Path, to: &Path) -> AnyResult<()> {
+	if fs::rename(from, to).is_err() {
+		fs::copy(from, to)?;
+		fs::remove_file(from)?;
+	}
+
+	Ok(())
+}
+
+/// Match a glob, only made of literals and `*` wildcards, against a whole name.
+fn glob_match(pattern: &str, name: &str) -> bool {
+	match pattern.split_once('*') {
+		None => pattern == name,
+		Some((prefix, rest)) => name.strip_prefix(prefix).map_or(false, |name| {
+			(0..=name.len()).any(|i| name.is_char_boundary(i) && glob_match(rest, &name[i..]))
+		}),
+	}
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Copy through a temporary file, `to` is either the previous file or the complete copy.
+fn copy_file(from: &Path, to: &Path) -> AnyResult<()> {
+	let tmp = temporary_path(to);
+
+	fs::copy(from, &tmp)
+		.and_then(|_| fs::rename(&tmp, to))
+		.map_err(|e| {
+			let _ = fs::remove_file(&tmp);
+
+			e
+		})
+		.with_context(|| format!("failed to copy `{}` to `{}`", from.display(), to.display()))
+}
+
+/// A hidden sibling of `path` to write it to before renaming it into place.
+fn temporary_path(path: &Path) -> PathBuf {
+	path.with_file_name(format!(
+		".{}.tmp",
+		path.file_name().unwrap_or_default().to_string_lossy()
+	))
+}
+
+/// The entries of `dir`, none if it's missing.
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+	fs::read_dir(dir)
+		.into_iter()
+		.flatten()
+		.filter_map(|entry| Some(entry.ok()?.path()))
+		.collect()
+}
+
+/// The directory of the target directory a cargo profile builds to.
+fn profile_dir(profile: &str) -> &str {
+	match profile {
+		"dev" | "test" => "debug",
+		"bench" => "release",
+		profile => profile,
+	}
+}
+
+/// A number of seconds, optionally suffixed by `s`, `m` or `h`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+	let (number, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+		Some(i) => duration.split_at(i),
+		None => (duration, "s"),
+	};
+	let multiplier = match unit {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		_ => {
+			return Err(format!(
+				"expected e.g. `90s`, `30m` or `2h`, got `{}`",
+				duration
+			))
+		}
+	};
+
+	number
+		.parse::<u64>()
+		.map(|number| Duration::from_secs(number * multiplier))
+		.map_err(|_| format!("expected e.g. `90s`, `30m` or `2h`, got `{}`", duration))
+}
+
+fn path_arg(path: &Path) -> String {
+	path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_durations() {
+		for (duration, seconds) in [
+			("90", Some(90)),
+			("90s", Some(90)),
+			("30m", Some(30 * 60)),
+			("2h", Some(2 * 60 * 60)),
+			("0s", Some(0)),
+			("", None),
+			("m", None),
+			("2d", None),
+			("1h30m", None),
+			("-1s", None),
+		] {
+			assert_eq!(
+				parse_duration(duration)
+					.ok()
+					.map(|duration| duration.as_secs()),
+				seconds,
+				"`{}`",
+				duration
+			);
+		}
+	}
+
+	#[test]
+	fn matches_globs() {
+		for (pattern, name, matches) in [
+			("v0.12.3", "v0.12.3", true),
+			("v0.12.3", "v0.12.3-1", false),
+			("v0.12.*", "v0.12.3", true),
+			("v0.12.*", "v0.12.", true),
+			("v0.12.*", "v0.13.0", false),
+			("*-rc*", "v0.12.3-rc1", true),
+			("*-rc*", "v0.12.3", false),
+			("*", "", true),
+			("v*.3", "v0.12.3", true),
+			("v*.3", "v0.12.4", false),
+			("ü*", "über", true),
+		] {
+			assert_eq!(
+				glob_match(pattern, name),
+				matches,
+				"`{}` and `{}`",
+				pattern,
+				name
+			);
+		}
+	}
+}
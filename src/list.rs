@@ -0,0 +1,160 @@
+//! The overrides of the output directory at a glance, a table per chain, and what's wrong with
+//! them: wasms without a digest, digests without a wasm and spec versions held twice.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+	registry::Runtime,
+	verify::{self, WASM_SUFFIX},
+	AnyResult,
+};
+
+#[derive(Debug, Serialize)]
+struct Row {
+	chain: String,
+	spec_version: Option<u32>,
+	file: String,
+	size: Option<u64>,
+	blake2_256: Option<String>,
+	commit: Option<String>,
+	/// What's wrong with it, if anything.
+	problem: Option<String>,
+}
+
+/// Print the overrides of `runtimes`, the problematic ones marked with `✘`.
+pub fn run(output_dir: &Path, runtimes: &[Runtime], json: bool) -> AnyResult<()> {
+	let mut rows = Vec::new();
+
+	for runtime in runtimes {
+		rows.extend(chain(&output_dir.join(runtime.name()), runtime.name()));
+	}
+
+	if json {
+		println!("{}", serde_json::to_string_pretty(&rows)?);
+	} else {
+		print(&rows);
+	}
+
+	Ok(())
+}
+
+fn chain(chain_dir: &Path, name: &str) -> Vec<Row> {
+	let wasms_dir = chain_dir.join("wasms");
+	let digests_dir = chain_dir.join("digests");
+	let wasms = verify::names(&wasms_dir, WASM_SUFFIX);
+	let digests = verify::names(&digests_dir, ".json");
+	let mut rows = wasms
+		.union(&digests)
+		.map(|file| {
+			let wasm = wasms_dir.join(format!("{}{}", file, WASM_SUFFIX));
+			let runtime_info =
+				crate::artifact::read_digest(&digests_dir.join(format!("{}.json", file)));
+			let field = |name: &str| {
+				runtime_info
+					.as_ref()
+					.and_then(|runtime_info| runtime_info[name].as_str())
+					.map(String::from)
+			};
+			let problem = match (wasms.contains(file), digests.contains(file)) {
+				(true, false) => Some("no digest".into()),
+				(false, true) => Some("wasm missing".into()),
+				_ if runtime_info.is_none() => Some("digest unreadable".into()),
+				_ => None,
+			};
+
+			Row {
+				chain: name.into(),
+				spec_version: field("core_version")
+					.as_deref()
+					.and_then(crate::artifact::spec_version),
+				file: format!("{}{}", file, WASM_SUFFIX),
+				size: fs::metadata(&wasm).ok().map(|metadata| metadata.len()),
+				blake2_256: field("blake2_256"),
+				commit: field("commit"),
+				problem,
+			}
+		})
+		.collect::<Vec<_>>();
+	let mut spec_versions = BTreeMap::<_, usize>::new();
+
+	for row in &rows {
+		if let Some(spec_version) = row.spec_version {
+			*spec_versions.entry(spec_version).or_default() += 1;
+		}
+	}
+	for row in &mut rows {
+		if row.problem.is_none()
+			&& row
+				.spec_version
+				.map_or(false, |spec_version| spec_versions[&spec_version] > 1)
+		{
+			// The nodes would load only one of them.
+			row.problem = Some("spec version held twice".into());
+		}
+	}
+
+	rows.sort_by(|a, b| (a.spec_version, &a.file).cmp(&(b.spec_version, &b.file)));
+
+	rows
+}
+
+fn print(rows: &[Row]) {
+	let mut chains = BTreeMap::<_, Vec<_>>::new();
+
+	for row in rows {
+		chains.entry(&row.chain).or_default().push(row);
+	}
+
+	for (chain, rows) in chains {
+		let short = |hash: &Option<String>, length: usize| {
+			hash.as_deref()
+				.map_or_else(|| "-".into(), |hash| hash.chars().take(length).collect())
+		};
+		let cells = rows
+			.iter()
+			.map(|row| {
+				[
+					row.spec_version
+						.map_or_else(|| "-".into(), |spec_version| spec_version.to_string()),
+					row.file.clone(),
+					row.size.map_or_else(|| "-".into(), |size| size.to_string()),
+					short(&row.blake2_256, 18),
+					short(&row.commit, 10),
+				]
+			})
+			.collect::<Vec<_>>();
+		let header = ["SPEC", "FILE", "SIZE", "BLAKE2_256", "COMMIT"];
+		let widths = (0..header.len())
+			.map(|i| {
+				cells
+					.iter()
+					.map(|row| row[i].chars().count())
+					.chain([header[i].len()])
+					.max()
+					.unwrap_or_default()
+			})
+			.collect::<Vec<_>>();
+		let line = |cells: &[String]| {
+			cells
+				.iter()
+				.zip(&widths)
+				.map(|(cell, width)| format!("{:<1$}", cell, width))
+				.collect::<Vec<_>>()
+				.join("  ")
+		};
+
+		println!("{}", chain);
+		println!("  {}", line(&header.map(String::from)).trim_end());
+
+		for (row, cells) in rows.iter().zip(&cells) {
+			match &row.problem {
+				Some(problem) => println!("✘ {}  {}", line(cells), problem),
+				None => println!("  {}", line(cells).trim_end()),
+			}
+		}
+
+		println!();
+	}
+}
@@ -0,0 +1,149 @@
+//! Prometheus metrics of the builds of the long running modes, `watch` and `serve`.
+
+use std::{
+	fmt::Write,
+	io::Cursor,
+	sync::atomic::{AtomicU64, Ordering},
+	thread,
+	time::Duration,
+};
+
+use log::{info, warn};
+use tiny_http::{Header, Response, Server};
+
+use crate::{progress::Phase, AnyResult};
+
+/// Upper bounds of the phase duration buckets, in seconds.
+const BUCKETS: [u64; 10] = [10, 30, 60, 120, 300, 600, 1200, 1800, 3600, 7200];
+const PHASES: [Phase; 6] = [
+	Phase::Clone,
+	Phase::Checkout,
+	Phase::Clean,
+	Phase::Build,
+	Phase::Package,
+	Phase::Digest,
+];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_BUCKETS: [AtomicU64; BUCKETS.len() + 1] = [ZERO; BUCKETS.len() + 1];
+
+static STARTED: AtomicU64 = ZERO;
+static SUCCEEDED: AtomicU64 = ZERO;
+static FAILED: AtomicU64 = ZERO;
+/// Per phase, the count of each bucket, the last one being `+Inf`, not cumulated.
+static PHASE_BUCKETS: [[AtomicU64; BUCKETS.len() + 1]; PHASES.len()] = [NO_BUCKETS; PHASES.len()];
+/// Per phase, in milliseconds.
+static PHASE_SUMS: [AtomicU64; PHASES.len()] = [ZERO; PHASES.len()];
+
+/// A build, as `build_job` runs it, started.
+pub fn started() {
+	STARTED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn finished(succeeded: bool) {
+	let counter = if succeeded { &SUCCEEDED } else { &FAILED };
+
+	counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A build spent `duration` in `phase`.
+pub fn observe(phase: Phase, duration: Duration) {
+	let i = PHASES.iter().position(|p| *p == phase).unwrap_or_default();
+	let bucket = BUCKETS
+		.iter()
+		.position(|bound| duration.as_secs_f64() <= *bound as f64)
+		.unwrap_or(BUCKETS.len());
+
+	PHASE_BUCKETS[i][bucket].fetch_add(1, Ordering::Relaxed);
+	PHASE_SUMS[i].fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// The metrics in the Prometheus text format.
+pub fn render() -> String {
+	let mut text = String::new();
+
+	for (name, help, counter) in [
+		("started", "Builds started", &STARTED),
+		("succeeded", "Builds which succeeded", &SUCCEEDED),
+		("failed", "Builds which failed", &FAILED),
+	] {
+		let _ = writeln!(
+			text,
+			"# HELP runtime_overrides_builds_{name}_total {help}.\n\
+			 # TYPE runtime_overrides_builds_{name}_total counter\n\
+			 runtime_overrides_builds_{name}_total {}",
+			counter.load(Ordering::Relaxed),
+			name = name,
+			help = help
+		);
+	}
+
+	text.push_str(
+		"# HELP runtime_overrides_phase_duration_seconds Time the builds spent in each phase.\n\
+		 # TYPE runtime_overrides_phase_duration_seconds histogram\n",
+	);
+
+	for (i, phase) in PHASES.iter().enumerate() {
+		let mut count = 0;
+
+		for (j, bucket) in PHASE_BUCKETS[i].iter().enumerate() {
+			count += bucket.load(Ordering::Relaxed);
+
+			let bound = BUCKETS
+				.get(j)
+				.map_or_else(|| "+Inf".into(), |bound| bound.to_string());
+			let _ = writeln!(
+				text,
+				"runtime_overrides_phase_duration_seconds_bucket{{phase=\"{}\",le=\"{}\"}} {}",
+				phase, bound, count
+			);
+		}
+
+		let _ = writeln!(
+			text,
+			"runtime_overrides_phase_duration_seconds_sum{{phase=\"{}\"}} {}\n\
+			 runtime_overrides_phase_duration_seconds_count{{phase=\"{}\"}} {}",
+			phase,
+			PHASE_SUMS[i].load(Ordering::Relaxed) as f64 / 1000.,
+			phase,
+			count
+		);
+	}
+
+	text
+}
+
+/// The `/metrics` response.
+pub fn response() -> Response<Cursor<Vec<u8>>> {
+	Response::from_string(render()).with_header(
+		Header::from_bytes("Content-Type", "text/plain; version=0.0.4").expect("valid header"),
+	)
+}
+
+/// Serve `/metrics` on `address` in the background.
+pub fn serve(address: &str) -> AnyResult<()> {
+	let server = Server::http(address)
+		.map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", address, e))?;
+
+	info!("Serving the metrics on http://{}/metrics", address);
+
+	thread::spawn(move || {
+		for request in server.incoming_requests() {
+			let response = if request.url() == "/metrics" {
+				response().boxed()
+			} else {
+				Response::from_string("not found")
+					.with_status_code(404)
+					.boxed()
+			};
+
+			if let Err(e) = request.respond(response) {
+				warn!("Failed to respond: {}", e);
+			}
+		}
+	});
+
+	Ok(())
+}
@@ -0,0 +1,180 @@
+//! Renaming the artifacts named after their target, `<CHAIN>-<TARGET>-tracing-runtime.*`, after
+//! their spec version instead, as `--name-by-spec-version` names them.
+
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{checksums, index, prune, registry::Runtime, signing, AnyResult};
+
+/// Rename the artifacts of `runtimes` not named after their spec version yet, recomputing their
+/// digests, then regenerate the index and checksums, or only tell what would be done if
+/// `dry_run`.
+///
+/// An artifact whose spec version is already held by a different wasm is left as it is.
+pub fn run(output_dir: &Path, runtimes: &[Runtime], dry_run: bool) -> AnyResult<()> {
+	let mut migrated = 0;
+	let mut conflicts = 0;
+
+	for runtime in runtimes {
+		let chain_dir = output_dir.join(runtime.name());
+
+		for name in crate::verify::names(&chain_dir.join("digests"), ".json") {
+			let wasm = chain_dir
+				.join("wasms")
+				.join(format!("{}.compact.compressed.wasm", name));
+
+			if !wasm.is_file() {
+				warn!("Skipping {}/{}, its wasm is missing", runtime.name(), name);
+
+				continue;
+			}
+
+			let runtime_info =
+				serde_json::to_value(Subwasm::new(&Source::File(wasm.clone())).runtime_info())?;
+			let spec_version = runtime_info["core_version"]
+				.as_str()
+				.and_then(crate::artifact::spec_version)
+				.with_context(|| format!("the spec version of `{}` is unknown", wasm.display()))?;
+			let new_name = format!("{}-{}-tracing-runtime", runtime.name(), spec_version);
+
+			if name == new_name {
+				continue;
+			}
+
+			let new_digest = chain_dir.join("digests").join(format!("{}.json", new_name));
+
+			if let Some(existing) = crate::artifact::read_digest(&new_digest) {
+				if existing["blake2_256"] == runtime_info["blake2_256"] {
+					info!(
+						"{}/{} is the same wasm as {}, removing it",
+						runtime.name(),
+						name,
+						new_name
+					);
+
+					if !dry_run {
+						for path in prune::files(&chain_dir, &name) {
+							fs::remove_file(&path).with_context(|| {
+								format!("failed to remove `{}`", path.display())
+							})?;
+						}
+					}
+				} else {
+					warn!(
+						"Skipping {}/{}, {} is already held by a different wasm",
+						runtime.name(),
+						name,
+						new_name
+					);
+
+					conflicts += 1;
+				}
+
+				continue;
+			}
+
+			info!("Renaming {}/{} to {}", runtime.name(), name, new_name);
+
+			if dry_run {
+				migrated += 1;
+
+				continue;
+			}
+
+			rename(&chain_dir, &name, &new_name)?;
+			redigest(&new_digest, runtime_info)
+				.with_context(|| format!("failed to update `{}`", new_digest.display()))?;
+			migrated += 1;
+		}
+	}
+
+	if migrated != 0 && !dry_run {
+		index::write(output_dir)?;
+		checksums::write(output_dir)?;
+	}
+
+	info!("Migrated {} artifact(s)", migrated);
+
+	if conflicts != 0 {
+		anyhow::bail!(
+			"{} artifact(s) have the spec version of another wasm, remove or rename them by hand",
+			conflicts
+		);
+	}
+
+	Ok(())
+}
+
+/// Rename every file of the artifact `name`, after checking none of their new names is taken.
+fn rename(chain_dir: &Path, name: &str, new_name: &str) -> AnyResult<()> {
+	let renames = prune::files(chain_dir, name)
+		.into_iter()
+		.filter_map(|path| {
+			let suffix = path.file_name()?.to_str()?.strip_prefix(name)?.to_owned();
+			let new_path = path.with_file_name(format!("{}{}", new_name, suffix));
+
+			Some((path, new_path))
+		})
+		.collect::<Vec<(PathBuf, PathBuf)>>();
+
+	if let Some((_, taken)) = renames
+		.iter()
+		.find(|(_, new_path)| new_path.is_symlink() || new_path.exists())
+	{
+		anyhow::bail!("`{}` already exists", taken.display());
+	}
+
+	for (path, new_path) in renames {
+		// Moves the links of the content addressed wasms, which are relative to their directory.
+		fs::rename(&path, &new_path).with_context(|| {
+			format!(
+				"failed to rename `{}` to `{}`",
+				path.display(),
+				new_path.display()
+			)
+		})?;
+	}
+
+	Ok(())
+}
+
+/// Refresh the fields subwasm computes of the digest at `path`, keeping the ones the build added.
+///
+/// A changed digest loses its signature, which has to be made again.
+fn redigest(path: &Path, runtime_info: serde_json::Value) -> AnyResult<()> {
+	let mut digest = crate::artifact::read_digest(path).unwrap_or_default();
+	let previous = digest.clone();
+
+	if let (Some(digest), Some(runtime_info)) = (digest.as_object_mut(), runtime_info.as_object()) {
+		for (key, value) in runtime_info {
+			digest.insert(key.clone(), value.clone());
+		}
+	}
+	if digest == previous {
+		return Ok(());
+	}
+
+	let tmp = crate::temporary_path(path);
+
+	fs::write(&tmp, serde_json::to_vec(&digest)?)?;
+	fs::rename(&tmp, path)?;
+
+	let signature = signing::signature_path(path);
+
+	if signature.exists() {
+		fs::remove_file(&signature)?;
+		warn!(
+			"`{}` changed, its signature was removed, sign it again",
+			path.display()
+		);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,177 @@
+//! Telling people a build finished: a Slack, Discord or Matrix message, a JSON post to any other
+//! URL, or a command of their own.
+
+use std::{
+	path::Path,
+	process::Command,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use log::warn;
+
+use crate::{runner::CommandFailed, settings::Settings, AnyResult, Artifact};
+
+/// What happened to a build.
+pub enum Event<'a> {
+	Built(&'a Artifact),
+	Failed {
+		label: &'a str,
+		error: &'a anyhow::Error,
+		log: Option<&'a Path>,
+	},
+}
+impl Event<'_> {
+	fn to_json(&self) -> serde_json::Value {
+		match self {
+			Self::Built(artifact) => {
+				let mut json = serde_json::to_value(artifact).unwrap_or_default();
+
+				json["event"] = "built".into();
+
+				json
+			}
+			Self::Failed { label, error, log } => serde_json::json!({
+				"event": "failed",
+				"build": label,
+				"error": format!("{:#}", error),
+				"log": log.map(crate::path_arg),
+			}),
+		}
+	}
+
+	fn message(&self) -> String {
+		match self {
+			Self::Built(artifact) => format!(
+				"Built {}@{}: {}, spec_version {}, blake2_256 {}, log {}",
+				artifact.runtime,
+				artifact.target,
+				artifact
+					.wasm
+					.file_name()
+					.unwrap_or_default()
+					.to_string_lossy(),
+				artifact
+					.spec_version
+					.map_or_else(|| "unknown".into(), |spec_version| spec_version.to_string()),
+				artifact.hashes.blake2_256,
+				artifact
+					.log
+					.as_deref()
+					.map_or_else(|| "none".into(), crate::path_arg)
+			),
+			Self::Failed { label, error, log } => format!(
+				"Failed to build {}: {:#}, log {}",
+				label,
+				error,
+				log.map_or_else(|| "none".into(), crate::path_arg)
+			),
+		}
+	}
+}
+
+/// Fire every `--notify` hook, a failing hook is only warned about.
+pub fn notify(event: Event, settings: &Settings) {
+	if settings.dry_run {
+		return;
+	}
+
+	for url in &settings.notify {
+		if let Err(e) = post(url, &event) {
+			warn!("Failed to notify {}: {:#}", url, e);
+		}
+	}
+	if let Some(command) = &settings.notify_command {
+		if let Err(e) = run(command, &event) {
+			warn!("Failed to run `{}`: {:#}", command, e);
+		}
+	}
+}
+
+/// Post the message in the format of the service behind `url`, the JSON event if it's none of
+/// them.
+fn post(url: &str, event: &Event) -> AnyResult<()> {
+	let message = event.message();
+	let result = if url.contains("hooks.slack.com") {
+		ureq::post(url).send_json(serde_json::json!({ "text": message }))
+	} else if url.contains("discord.com/api/webhooks")
+		|| url.contains("discordapp.com/api/webhooks")
+	{
+		ureq::post(url).send_json(serde_json::json!({ "content": message }))
+	} else if url.contains("/_matrix/") {
+		// `.../rooms/<ROOM>/send/m.room.message?access_token=...`, each message with a transaction id.
+		let (path, query) = url.split_once('?').unwrap_or((url, ""));
+		let transaction = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_nanos();
+
+		ureq::put(&format!(
+			"{}/{}?{}",
+			path.trim_end_matches('/'),
+			transaction,
+			query
+		))
+		.send_json(serde_json::json!({ "msgtype": "m.text", "body": message }))
+	} else {
+		ureq::post(url).send_json(event.to_json())
+	};
+
+	result.map(drop).map_err(anyhow::Error::from)
+}
+
+/// Run `command` through the shell, the event in its environment.
+fn run(command: &str, event: &Event) -> AnyResult<()> {
+	let mut child = Command::new("sh");
+
+	child
+		.args(["-c", command])
+		.env("RO_NOTIFICATION", event.to_json().to_string())
+		.env("RO_MESSAGE", event.message());
+
+	match event {
+		Event::Built(artifact) => {
+			child
+				.env("RO_EVENT", "built")
+				.env("RO_RUNTIME", &artifact.runtime)
+				.env("RO_TARGET", &artifact.target)
+				.env("RO_COMMIT", &artifact.commit)
+				.env("RO_WASM", &artifact.wasm)
+				.env("RO_DIGEST", &artifact.digest)
+				.env("RO_BLAKE2_256", &artifact.hashes.blake2_256)
+				.env(
+					"RO_SPEC_VERSION",
+					artifact
+						.spec_version
+						.map(|spec_version| spec_version.to_string())
+						.unwrap_or_default(),
+				);
+
+			if let Some(log) = &artifact.log {
+				child.env("RO_LOG", log);
+			}
+		}
+		Event::Failed { label, error, log } => {
+			child
+				.env("RO_EVENT", "failed")
+				.env("RO_BUILD", label)
+				.env("RO_ERROR", format!("{:#}", error));
+
+			if let Some(log) = log {
+				child.env("RO_LOG", log);
+			}
+		}
+	}
+
+	let status = child.status().context("failed to spawn `sh`")?;
+
+	if !status.success() {
+		return Err(CommandFailed {
+			command: command.into(),
+			status,
+		}
+		.into());
+	}
+
+	Ok(())
+}
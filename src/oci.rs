@@ -0,0 +1,264 @@
+//! Exporting the overrides of some chains as an OCI image, a single layer holding them laid out
+//! for `--wasm-runtime-overrides`, for the nodes deployed on Kubernetes to mount.
+
+use std::{
+	env,
+	fs::{self, File},
+	path::Path,
+	process,
+};
+
+use anyhow::Context;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tar::{EntryType, Header};
+
+use crate::{install::Overrides, registry::Runtime, AnyResult, Artifact};
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.image.config.v1+json";
+/// The wasms are compressed already.
+const LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar";
+
+/// What the image is made of.
+pub struct Image<'a> {
+	/// e.g. `ghcr.io/darwinia-network/runtime-overrides:latest`.
+	pub reference: &'a str,
+	/// The override directory in the image, e.g. `/overrides`.
+	pub path: &'a str,
+	/// e.g. `amd64`, the wasms run anywhere but the image has to name a platform.
+	pub architecture: &'a str,
+}
+
+/// Write the image of the overrides of `runtimes` to `output`, an OCI archive which `docker load`,
+/// `podman load` and `skopeo copy oci-archive:<OUTPUT>` read.
+///
+/// Returns how many wasms it holds.
+pub fn export(
+	output_dir: &Path,
+	runtimes: &[Runtime],
+	image: &Image,
+	output: &Path,
+) -> AnyResult<usize> {
+	if !image.path.starts_with('/') || image.path.trim_matches('/').is_empty() {
+		anyhow::bail!(
+			"the path in the image, `{}`, isn't an absolute directory below the root",
+			image.path
+		);
+	}
+
+	let staging_dir = env::temp_dir().join(format!("runtime-overrides-oci-{}", process::id()));
+	let result = stage(output_dir, runtimes, &staging_dir).and_then(|count| {
+		let tmp = crate::temporary_path(output);
+
+		write(&staging_dir, image, &tmp)
+			.and_then(|_| Ok(fs::rename(&tmp, output)?))
+			.map_err(|e| {
+				let _ = fs::remove_file(&tmp);
+
+				e
+			})
+			.with_context(|| format!("failed to write `{}`", output.display()))?;
+
+		Ok(count)
+	});
+	let _ = fs::remove_dir_all(&staging_dir);
+
+	result
+}
+
+/// Install the artifacts of `runtimes` to `staging_dir`, the latest build of each spec version
+/// replacing the others.
+fn stage(output_dir: &Path, runtimes: &[Runtime], staging_dir: &Path) -> AnyResult<usize> {
+	let _ = fs::remove_dir_all(staging_dir);
+	let mut overrides = Overrides::load(staging_dir)?;
+	let mut installed = 0;
+
+	for runtime in runtimes {
+		let chain_dir = output_dir.join(runtime.name());
+		let mut digests = crate::dir_entries(&chain_dir.join("digests"))
+			.into_iter()
+			.filter_map(|digest| {
+				let name = digest
+					.file_name()?
+					.to_str()?
+					.strip_suffix(".json")?
+					.to_owned();
+				let runtime_info = crate::artifact::read_digest(&digest)?;
+
+				(!name.starts_with('.')).then(|| (name, digest, runtime_info))
+			})
+			.collect::<Vec<_>>();
+
+		digests.sort_by_cached_key(|(_, _, runtime_info)| {
+			runtime_info[crate::artifact::PROVENANCE]["built_at"]
+				.as_str()
+				.map(String::from)
+		});
+
+		for (name, digest, runtime_info) in digests {
+			let wasm = chain_dir
+				.join("wasms")
+				.join(format!("{}.compact.compressed.wasm", name));
+
+			if !wasm.is_file() {
+				continue;
+			}
+
+			let target = runtime_info[crate::artifact::PROVENANCE]["target"]
+				.as_str()
+				.unwrap_or_default()
+				.to_owned();
+			let artifact = Artifact::new(runtime, &target, wasm, digest, runtime_info)?;
+
+			overrides.install(&artifact, true)?;
+			installed += 1;
+		}
+	}
+
+	if installed == 0 {
+		anyhow::bail!(
+			"there are no artifacts to export in `{}`",
+			output_dir.display()
+		);
+	}
+
+	Ok(crate::dir_entries(staging_dir)
+		.iter()
+		.filter(|path| {
+			path.extension()
+				.map_or(false, |extension| extension == "wasm")
+		})
+		.count())
+}
+
+/// Write the OCI image layout, and the `manifest.json` of `docker save` for the older engines.
+fn write(staging_dir: &Path, image: &Image, to: &Path) -> AnyResult<()> {
+	let layer = layer(staging_dir, image.path.trim_matches('/'))?;
+	let layer_digest = sha256(&layer);
+	let created = chrono::Utc::now().to_rfc3339();
+	let config = serde_json::to_vec(&json!({
+		"created": created,
+		"architecture": image.architecture,
+		"os": "linux",
+		"config": {
+			"Labels": {
+				"org.opencontainers.image.created": created,
+				"org.opencontainers.image.title": "runtime-overrides",
+			},
+		},
+		"rootfs": { "type": "layers", "diff_ids": [layer_digest] },
+		"history": [{ "created": created, "created_by": "runtime-overrides export-image" }],
+	}))?;
+	let config_digest = sha256(&config);
+	let manifest = serde_json::to_vec(&json!({
+		"schemaVersion": 2,
+		"mediaType": MANIFEST_MEDIA_TYPE,
+		"config": descriptor(CONFIG_MEDIA_TYPE, &config_digest, config.len()),
+		"layers": [descriptor(LAYER_MEDIA_TYPE, &layer_digest, layer.len())],
+	}))?;
+	let manifest_digest = sha256(&manifest);
+	let mut manifest_descriptor = descriptor(MANIFEST_MEDIA_TYPE, &manifest_digest, manifest.len());
+
+	manifest_descriptor["annotations"] = json!({
+		"io.containerd.image.name": image.reference,
+		"org.opencontainers.image.ref.name": tag(image.reference),
+	});
+
+	let index = serde_json::to_vec(&json!({
+		"schemaVersion": 2,
+		"mediaType": "application/vnd.oci.image.index.v1+json",
+		"manifests": [manifest_descriptor],
+	}))?;
+	let docker_manifest = serde_json::to_vec(&json!([{
+		"Config": blob(&config_digest),
+		"RepoTags": [image.reference],
+		"Layers": [blob(&layer_digest)],
+	}]))?;
+	let mut archive = tar::Builder::new(File::create(to)?);
+
+	for (path, content) in [
+		(
+			"oci-layout".into(),
+			br#"{"imageLayoutVersion":"1.0.0"}"#.to_vec(),
+		),
+		("index.json".into(), index),
+		("manifest.json".into(), docker_manifest),
+		(blob(&layer_digest), layer),
+		(blob(&config_digest), config),
+		(blob(&manifest_digest), manifest),
+	] {
+		append(&mut archive, &path, &content)?;
+	}
+
+	archive.into_inner()?.sync_all()?;
+
+	Ok(())
+}
+
+/// The tar of the files of `staging_dir` in `dir`, reproducible: their metadata are fixed.
+fn layer(staging_dir: &Path, dir: &str) -> AnyResult<Vec<u8>> {
+	let mut layer = tar::Builder::new(Vec::new());
+	let mut files = crate::dir_entries(staging_dir)
+		.into_iter()
+		.filter(|path| path.is_file())
+		.collect::<Vec<_>>();
+
+	files.sort();
+
+	// Every parent of the directory, root aside.
+	for (i, _) in dir.match_indices('/').chain([(dir.len(), "")]) {
+		let mut header = header(EntryType::Directory, 0o755, 0);
+
+		layer.append_data(&mut header, format!("{}/", &dir[..i]), &[][..])?;
+	}
+	for file in files {
+		let content = fs::read(&file)?;
+		let name = file.file_name().unwrap_or_default().to_string_lossy();
+		let mut header = header(EntryType::Regular, 0o644, content.len());
+
+		layer.append_data(&mut header, format!("{}/{}", dir, name), &content[..])?;
+	}
+
+	Ok(layer.into_inner()?)
+}
+
+fn append(archive: &mut tar::Builder<File>, path: &str, content: &[u8]) -> AnyResult<()> {
+	let mut header = header(EntryType::Regular, 0o644, content.len());
+
+	Ok(archive.append_data(&mut header, path, content)?)
+}
+
+fn header(entry_type: EntryType, mode: u32, size: usize) -> Header {
+	let mut header = Header::new_ustar();
+
+	header.set_entry_type(entry_type);
+	header.set_mode(mode);
+	header.set_size(size as u64);
+	header.set_mtime(0);
+	header.set_uid(0);
+	header.set_gid(0);
+
+	header
+}
+
+fn descriptor(media_type: &str, digest: &str, size: usize) -> Value {
+	json!({ "mediaType": media_type, "digest": digest, "size": size })
+}
+
+fn sha256(content: &[u8]) -> String {
+	format!("sha256:{}", crate::to_hex(&Sha256::digest(content)))
+}
+
+/// `blobs/sha256/<HEX>` of `sha256:<HEX>`.
+fn blob(digest: &str) -> String {
+	format!("blobs/{}", digest.replacen(':', "/", 1))
+}
+
+/// `latest` of `ghcr.io/darwinia-network/runtime-overrides:latest`.
+fn tag(reference: &str) -> &str {
+	match reference.rsplit_once(':') {
+		Some((_, tag)) if !tag.contains('/') => tag,
+		_ => "latest",
+	}
+}
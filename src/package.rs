@@ -0,0 +1,144 @@
+//! Bundling the artifacts of some chains, their index and checksums included, into a single
+//! archive to attach to a release or to carry to the nodes without network access.
+
+use std::{
+	env,
+	fs::{self, File},
+	io,
+	path::{Path, PathBuf},
+	process,
+};
+
+use anyhow::Context;
+use clap::ArgEnum;
+use log::warn;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{checksums, index, registry::Runtime, AnyResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Format {
+	#[clap(name = "tar.zst")]
+	TarZst,
+	Zip,
+}
+impl Format {
+	fn extension(self) -> &'static str {
+		match self {
+			Self::TarZst => "tar.zst",
+			Self::Zip => "zip",
+		}
+	}
+}
+
+/// Write the artifacts of `runtimes` to `output`, under a `runtime-overrides-<version>/`
+/// directory laid out like the output directory.
+///
+/// Returns the path of the package, `runtime-overrides-<version>.<extension>` unless `output` is
+/// given.
+pub fn run(
+	output_dir: &Path,
+	runtimes: &[Runtime],
+	version: &str,
+	format: Format,
+	output: Option<PathBuf>,
+) -> AnyResult<PathBuf> {
+	let root = format!("runtime-overrides-{}", version);
+	let output = output.unwrap_or_else(|| format!("{}.{}", root, format.extension()).into());
+	let staging_dir = env::temp_dir().join(format!("runtime-overrides-package-{}", process::id()));
+	let result = stage(output_dir, runtimes, &staging_dir).and_then(|files| {
+		let tmp = crate::temporary_path(&output);
+
+		write(&staging_dir, &files, &root, format, &tmp)
+			.and_then(|_| Ok(fs::rename(&tmp, &output)?))
+			.map_err(|e| {
+				let _ = fs::remove_file(&tmp);
+
+				e
+			})
+			.with_context(|| format!("failed to write `{}`", output.display()))
+	});
+	let _ = fs::remove_dir_all(&staging_dir);
+
+	result.map(|_| output)
+}
+
+/// Copy the artifacts of `runtimes` to `staging_dir`, then index and checksum them there not to
+/// list the chains left out.
+///
+/// Returns the files to package, relative to `staging_dir`.
+fn stage(output_dir: &Path, runtimes: &[Runtime], staging_dir: &Path) -> AnyResult<Vec<String>> {
+	let _ = fs::remove_dir_all(staging_dir);
+	let mut files = Vec::new();
+
+	for runtime in runtimes {
+		let chain_dir = output_dir.join(runtime.name());
+		let chain_files = checksums::files(&chain_dir);
+
+		if chain_files.is_empty() {
+			warn!("{} has no artifacts to package", runtime.name());
+
+			continue;
+		}
+
+		for file in chain_files {
+			let to = staging_dir.join(runtime.name()).join(&file);
+
+			fs::create_dir_all(to.parent().unwrap_or(staging_dir))?;
+			// Follows the links of the content addressed wasms.
+			crate::copy_file(&chain_dir.join(&file), &to)?;
+			files.push(format!("{}/{}", runtime.name(), file));
+		}
+
+		files.push(format!("{}/SHA256SUMS", runtime.name()));
+		files.push(format!("{}/BLAKE2SUMS", runtime.name()));
+	}
+
+	if files.is_empty() {
+		anyhow::bail!(
+			"there are no artifacts to package in `{}`",
+			output_dir.display()
+		);
+	}
+
+	index::write(staging_dir)?;
+	checksums::write(staging_dir)?;
+	files.insert(0, index::FILE_NAME.into());
+
+	Ok(files)
+}
+
+fn write(
+	staging_dir: &Path,
+	files: &[String],
+	root: &str,
+	format: Format,
+	to: &Path,
+) -> AnyResult<()> {
+	let file = File::create(to)?;
+
+	match format {
+		Format::TarZst => {
+			let mut tar = tar::Builder::new(zstd::Encoder::new(file, 19)?);
+
+			for name in files {
+				tar.append_path_with_name(staging_dir.join(name), format!("{}/{}", root, name))?;
+			}
+
+			tar.into_inner()?.finish()?;
+		}
+		Format::Zip => {
+			let mut zip = ZipWriter::new(file);
+			let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+			for name in files {
+				zip.start_file(format!("{}/{}", root, name), options)?;
+				io::copy(&mut File::open(staging_dir.join(name))?, &mut zip)?;
+			}
+
+			zip.finish()?;
+		}
+	}
+
+	Ok(())
+}
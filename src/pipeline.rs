@@ -0,0 +1,1729 @@
+//! The builds, from the jobs an invocation is split into to the artifacts of each of their runtimes.
+
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	env,
+	fs::{self, File, OpenOptions},
+	io::{Read, Write},
+	path::{Path, PathBuf},
+	process, slice,
+	sync::{Arc, Mutex},
+	thread,
+	time::{Duration, Instant},
+	vec::IntoIter,
+};
+
+use anyhow::Context;
+use fs2::FileExt;
+
+use clap::ArgEnum;
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{
+	apis, archive,
+	artifact::{
+		check_duplicate, check_overwrite, previous_sizes, read_digest, sibling_path, spec_version,
+		write_artifact, write_metadata, Artifact, Timings, PROVENANCE, UNCOMPRESSED_EXTENSION,
+	},
+	checksums,
+	cli::BuildArgs,
+	config::{self, rpc_url, Config},
+	container, copy_file, create_dir_unchecked, dir_entries,
+	git::{
+		add_worktree, checkout, fetch_target, is_dirty, local_commit, revert_patches, submodules,
+		sync,
+	},
+	github, history, index, install, ipfs, metrics, notify, parse_duration, path_arg, profile_dir,
+	progress::{Phase, Progress, Tracker},
+	proposal, pull_request,
+	registry::{Layout, Registry, Runtime},
+	rpc,
+	runner::{with_logs, BuildLog, CommandRunner, TimedOut},
+	schema,
+	settings::{Builder, Settings},
+	signing::{self, Signer},
+	size, srtool, store, targets, temporary_path, to_hex, toolchain, try_runtime, wasm_opt,
+	AnyResult, BuildError,
+};
+
+/// What cargo says when `--locked` keeps it from updating the `Cargo.lock`.
+const LOCK_DRIFT: &str = "needs to be updated but --locked was passed";
+
+/// Runtimes sharing a repository and a target are built one after another from the same checkout.
+#[derive(Debug)]
+pub struct Job {
+	pub target: String,
+	pub layout: Layout,
+	pub runtimes: Vec<Runtime>,
+	/// One per runtime, none in dry-run mode.
+	pub logs: Vec<BuildLog>,
+}
+impl Job {
+	pub fn repository(&self) -> &str {
+		self.runtimes[0].repository(self.layout)
+	}
+
+	pub fn label(&self) -> String {
+		format!("{}@{}", self.repository(), self.target)
+	}
+
+	/// The local ref `target` is fetched into, so that branches, tags and commits are checked out
+	/// alike.
+	pub fn revision(&self) -> String {
+		format!("refs/runtime-overrides/{}", self.target)
+	}
+
+	pub fn clone_dir(&self, build_dir: &Path) -> PathBuf {
+		build_dir.join(self.repository())
+	}
+
+	pub fn worktree_dir(&self, build_dir: &Path) -> PathBuf {
+		build_dir
+			.join("worktrees")
+			.join(self.repository())
+			.join(self.target.replace('/', "-"))
+	}
+}
+
+/// The build and output directories, from the command line, the config or the defaults.
+pub fn dirs(
+	build_dir: Option<PathBuf>,
+	output_dir: Option<PathBuf>,
+	config: &Config,
+) -> AnyResult<(PathBuf, PathBuf)> {
+	let build_dir = env::current_dir()?.join(
+		build_dir
+			.or_else(|| config.build_dir.clone())
+			.unwrap_or_else(|| "build".into()),
+	);
+	let output_dir = output_dir
+		.or_else(|| config.output_dir.clone())
+		.unwrap_or_else(|| "overridden-runtimes".into());
+
+	Ok((build_dir, output_dir))
+}
+
+/// Fail with the first of `errors`, logging the others.
+pub fn first_error(errors: Vec<anyhow::Error>) -> AnyResult<()> {
+	let mut errors = errors.into_iter();
+
+	match errors.next() {
+		Some(first) => {
+			for e in errors {
+				error!("{:?}", e);
+			}
+
+			Err(first)
+		}
+		None => Ok(()),
+	}
+}
+
+/// Build the requested runtimes, the artifacts which could be and the errors of the others.
+pub fn build_artifacts(
+	args: BuildArgs,
+	config: Config,
+	registry: &Registry,
+	build_dir: PathBuf,
+	output_dir: PathBuf,
+	progress: Progress,
+	runner: Arc<dyn CommandRunner>,
+) -> AnyResult<(Vec<Artifact>, Vec<anyhow::Error>)> {
+	let BuildArgs {
+		runtime,
+		target,
+		concurrency,
+		retries,
+		lock_wait,
+		features,
+		no_default_features,
+		dry_run,
+		no_install,
+		offline,
+		full_clone,
+		sparse,
+		commit_in_name,
+		name_by_spec_version,
+		content_addressed,
+		metadata,
+		sign_key,
+		signer,
+		verify_reproducible,
+		try_runtime,
+		builder,
+		image,
+		jobs,
+		nice,
+		profile,
+		env,
+		cargo_arg,
+		patch,
+		allow_lock_update,
+		keep_uncompressed,
+		wasm_opt,
+		wasm_opt_arg,
+		sccache,
+		notify,
+		notify_command,
+		install_to,
+		publish_pr,
+		ipfs,
+		ipfs_pin_service,
+		ipfs_pin_token,
+		timeout,
+		build_log_dir,
+		no_clean,
+		check_chain,
+		strict_size,
+		rebuild,
+		force,
+		allow_duplicate,
+		output: _,
+		manifest_path,
+		package,
+		layout,
+		repo_url,
+		source_path,
+	} = args;
+	let runtimes = registry.resolve(&runtime)?;
+	let target = target
+		// The working tree isn't at any of the targets of the config.
+		.or(if source_path.is_some() {
+			Some(vec!["local".into()])
+		} else {
+			config.target
+		})
+		.unwrap_or_else(|| vec!["main".into()]);
+	let concurrency = concurrency.or(config.concurrency).unwrap_or(1);
+	let offline = offline || config.offline.unwrap_or_default();
+	let mut settings = Settings {
+		build_dir,
+		use_worktrees: false,
+		features: features.or(config.features),
+		no_default_features: no_default_features || config.no_default_features.unwrap_or_default(),
+		output_dir,
+		dry_run,
+		install: !(no_install || offline),
+		offline,
+		shallow: !(full_clone || config.full_clone.unwrap_or_default()),
+		sparse: sparse || config.sparse.unwrap_or_default(),
+		retries: retries.or(config.retries).unwrap_or(3),
+		commit_in_name: commit_in_name || config.commit_in_name.unwrap_or_default(),
+		content_addressed: content_addressed || config.content_addressed.unwrap_or_default(),
+		name_by_spec_version: name_by_spec_version
+			|| config.name_by_spec_version.unwrap_or_default(),
+		metadata: if metadata.is_empty() {
+			config.metadata.unwrap_or_default()
+		} else {
+			metadata
+		},
+		verify_reproducible: verify_reproducible || config.verify_reproducible.unwrap_or_default(),
+		try_runtime: try_runtime.or(config.try_runtime),
+		builder: builder.or(config.builder).unwrap_or(Builder::Cargo),
+		image: image.or(config.image),
+		check_chain: check_chain || config.check_chain.unwrap_or_default(),
+		strict_size: strict_size || config.strict_size.unwrap_or_default(),
+		force,
+		allow_duplicate,
+		repo_url,
+		source_path: source_path
+			.map(|path| {
+				path.canonicalize()
+					.with_context(|| format!("`{}` doesn't exist", path.display()))
+			})
+			.transpose()?,
+		manifest_path,
+		package,
+		runtimes: config.runtimes,
+		envs: config
+			.env
+			.into_iter()
+			.flatten()
+			.chain(env)
+			.collect::<BTreeMap<_, _>>()
+			.into_iter()
+			.collect(),
+		jobs: jobs.or(config.jobs),
+		nice: nice || config.nice.unwrap_or_default(),
+		profile: profile.or(config.profile),
+		no_clean: no_clean || config.no_clean.unwrap_or_default(),
+		timeout: match timeout {
+			Some(timeout) => Some(timeout),
+			None => config
+				.timeout
+				.as_deref()
+				.map(parse_duration)
+				.transpose()
+				.map_err(anyhow::Error::msg)
+				.context("invalid `timeout` in the config")?,
+		},
+		build_log_dir: build_log_dir.or(config.build_log_dir),
+		cargo_args: if cargo_arg.is_empty() {
+			config.cargo_args.unwrap_or_default()
+		} else {
+			cargo_arg
+		},
+		patches: if patch.is_empty() {
+			config.patches.unwrap_or_default()
+		} else {
+			patch
+		}
+		.into_iter()
+		.map(|path| {
+			let content =
+				fs::read(&path).with_context(|| format!("failed to read `{}`", path.display()))?;
+
+			Ok((path.canonicalize()?, to_hex(&Sha256::digest(&content))))
+		})
+		.collect::<AnyResult<_>>()?,
+		allow_lock_update: allow_lock_update
+			|| config.allow_lock_update.unwrap_or_default()
+			|| config.cargo_patch.is_some(),
+		cargo_patch: config.cargo_patch.unwrap_or_default(),
+		git: config.git,
+		keep_uncompressed: keep_uncompressed || config.keep_uncompressed.unwrap_or_default(),
+		wasm_opt: (wasm_opt || config.wasm_opt.unwrap_or_default()).then(|| {
+			if !wasm_opt_arg.is_empty() {
+				wasm_opt_arg
+			} else {
+				config.wasm_opt_args.unwrap_or_else(|| {
+					wasm_opt::DEFAULT_ARGS
+						.iter()
+						.map(|arg| arg.to_string())
+						.collect()
+				})
+			}
+		}),
+		notify: if notify.is_empty() {
+			config.notify.unwrap_or_default()
+		} else {
+			notify
+		},
+		notify_command: notify_command.or(config.notify_command),
+		ipfs: ipfs.or(config.ipfs),
+		ipfs_pin_service: match ipfs_pin_service.or(config.ipfs_pin_service) {
+			Some(service) => {
+				let token = ipfs_pin_token
+					.or_else(|| env::var(config.ipfs_pin_token_env.as_ref()?).ok())
+					.with_context(|| {
+						format!(
+							"no token for the pinning service `{}`, pass `--ipfs-pin-token` or \
+							 set `ipfs-pin-token-env`",
+							service
+						)
+					})?;
+
+				Some((service, token))
+			}
+			None => None,
+		},
+		progress,
+		runner,
+	};
+	// A working tree tells its layout by where its runtimes are.
+	let layout = layout.or_else(|| {
+		let source_path = settings.source_path.as_ref()?;
+
+		Layout::value_variants().iter().copied().find(|layout| {
+			runtimes.iter().all(|runtime| {
+				source_path
+					.join(settings.manifest_path(runtime, *layout))
+					.is_file()
+			})
+		})
+	});
+	let mut releases = HashMap::new();
+	let mut jobs = plan(&runtimes, &target, layout, |runtime, layout| {
+		let url = settings.repo_url(runtime, layout);
+
+		if let Some(tag) = releases.get(&url) {
+			return Ok(tag.clone());
+		}
+		if settings.offline {
+			anyhow::bail!("`latest-release` is looked up online, pass a tag with `--offline`");
+		}
+
+		let tag = github::latest_release(&url)?;
+
+		info!("The latest release of {} is {}", url, tag);
+		releases.insert(url, tag.clone());
+
+		Ok(tag)
+	})?;
+
+	if settings.repo_url.is_some()
+		&& jobs
+			.iter()
+			.map(Job::repository)
+			.collect::<HashSet<_>>()
+			.len() > 1
+	{
+		anyhow::bail!("`--repo-url` can only be used with runtimes from the same repository");
+	}
+	if settings.source_path.is_some() {
+		if jobs.len() > 1 {
+			anyhow::bail!("`--source-path` can only be used with a single repository and target");
+		}
+		if !settings.patches.is_empty() || !settings.cargo_patch.is_empty() {
+			anyhow::bail!("`--source-path` builds the working tree as it is, without any patch");
+		}
+	}
+	if (settings.manifest_path.is_some() || settings.package.is_some())
+		&& runtimes.iter().collect::<HashSet<_>>().len() > 1
+	{
+		anyhow::bail!("`--manifest-path` and `--package` can only be used with a single runtime");
+	}
+	if matches!(settings.builder, Builder::Docker | Builder::Podman) && settings.image.is_none() {
+		anyhow::bail!("the docker and podman builders need an `--image`");
+	}
+	if settings.timeout.is_some() && settings.builder == Builder::Srtool {
+		anyhow::bail!("`--timeout` can't be used with the srtool builder");
+	}
+	if sccache || config.sccache.unwrap_or_default() {
+		if settings.builder != Builder::Cargo {
+			anyhow::bail!("`--sccache` can only be used with the cargo builder");
+		}
+
+		let version = settings
+			.capture(Path::new("."), "sccache", &["--version"])
+			.context("sccache isn't available, install it with `cargo install sccache`")?;
+
+		info!("Caching the compilations with {}", version);
+		settings
+			.envs
+			.push(("RUSTC_WRAPPER".into(), "sccache".into()));
+	}
+	if settings.verify_reproducible && settings.builder != Builder::Cargo {
+		anyhow::bail!("`--verify-reproducible` can only be used with the cargo builder");
+	}
+	if settings.try_runtime.is_some() {
+		for runtime in &runtimes {
+			if !settings
+				.features_of(runtime)
+				.0
+				.split(',')
+				.any(|feature| feature == "try-runtime")
+			{
+				anyhow::bail!(
+					"`--try-runtime` needs the `try-runtime` feature, e.g. `--features evm-tracing,try-runtime`"
+				);
+			}
+			if rpc_url(&settings.runtimes, runtime).is_none() {
+				anyhow::bail!(
+					"no RPC endpoint is known for {} to run `try-runtime` against, set its `rpc`",
+					runtime.name()
+				);
+			}
+		}
+	}
+
+	// Held until the end of the invocation.
+	let _lock = if settings.dry_run {
+		None
+	} else {
+		Some(lock_build_dir(
+			&settings.build_dir,
+			lock_wait.or(config.lock_wait).unwrap_or_default(),
+		)?)
+	};
+	// Fail fast, before spending any time on cloning and building.
+	let mut refs = HashMap::new();
+	let mut artifacts = Vec::new();
+
+	for job in &mut jobs {
+		let url = settings.repo_url(&job.runtimes[0], job.layout);
+		let commit = if let Some(source_path) = &settings.source_path {
+			// Uncommitted changes are always built.
+			if is_dirty(source_path, &settings)? {
+				continue;
+			}
+
+			settings.capture(source_path, "git", &["rev-parse", "HEAD"])?
+		} else if settings.offline {
+			let clone_dir = job.clone_dir(&settings.build_dir);
+
+			local_commit(&clone_dir, &job.target, &settings).with_context(|| {
+				format!(
+					"`{}` isn't in the clone of {}, fetch it before building offline",
+					job.target,
+					job.repository()
+				)
+			})?
+		} else {
+			if !refs.contains_key(&url) {
+				let remote_refs = settings
+					.retry(&url, || {
+						targets::remote_refs(
+							&url,
+							&config::git_options(&settings.git, &url),
+							settings.runner.as_ref(),
+						)
+					})
+					.with_context(|| BuildError::CloneFailed { url: url.clone() })?;
+
+				refs.insert(url.clone(), remote_refs);
+			}
+
+			targets::check(&job.target, &refs[&url], &url)?;
+
+			// Abbreviated commits are only known once fetched, they are always built.
+			match targets::resolve(&job.target, &refs[&url]) {
+				Some(commit) => commit.to_owned(),
+				None => continue,
+			}
+		};
+		let mut runtimes = Vec::new();
+
+		for runtime in job.runtimes.drain(..) {
+			check_destination(&runtime, &job.target, &commit, &settings)?;
+
+			if !rebuild {
+				if let Some(artifact) = cached(&runtime, &job.target, &commit, &settings)? {
+					artifacts.push(artifact);
+
+					continue;
+				}
+			}
+
+			runtimes.push(runtime);
+		}
+
+		job.runtimes = runtimes;
+	}
+
+	jobs.retain(|job| !job.runtimes.is_empty());
+
+	if !settings.dry_run {
+		for job in &mut jobs {
+			job.logs = job
+				.runtimes
+				.iter()
+				.map(|runtime| {
+					BuildLog::create(settings.log_dir(runtime).join(format!(
+						"{}-{}.log",
+						runtime.name(),
+						job.target.replace('/', "-")
+					)))
+				})
+				.collect::<AnyResult<_>>()?;
+		}
+	}
+
+	// A single build keeps using the clone itself, concurrent ones need a worktree each.
+	settings.use_worktrees = concurrency > 1 && jobs.len() > 1;
+
+	let mut fetched = HashSet::new();
+	// A spinner per job, from its clone to its last runtime.
+	let mut jobs = jobs
+		.into_iter()
+		.map(|job| {
+			let tracker = settings.progress.start(&job.label());
+
+			(job, tracker)
+		})
+		.collect::<Vec<_>>();
+
+	for (job, tracker) in &mut jobs {
+		// A working tree is built as it is.
+		if settings.source_path.is_some() {
+			break;
+		}
+
+		let job = &*job;
+		let clone_dir = job.clone_dir(&settings.build_dir);
+
+		tracker.phase(Phase::Clone);
+		with_logs(&job.logs, || {
+			if fetched.insert(job.repository()) {
+				sync(job, &clone_dir, &settings)?;
+			}
+
+			fetch_target(job, &clone_dir, &settings)
+		})
+		.with_context(|| BuildError::CloneFailed {
+			url: settings.repo_url(&job.runtimes[0], job.layout),
+		})?;
+
+		// Rustup isn't safe to run concurrently, install the toolchains before spawning the workers.
+		if settings.use_worktrees {
+			tracker.phase(Phase::Checkout);
+			with_logs(&job.logs, || {
+				add_worktree(job, &settings)?;
+
+				if settings.install && settings.builder == Builder::Cargo {
+					toolchain::ensure(&job.worktree_dir(&settings.build_dir), &settings)?;
+				}
+
+				AnyResult::Ok(())
+			})
+			.with_context(|| BuildError::CheckoutFailed { label: job.label() })?;
+		}
+
+		tracker.queue();
+	}
+
+	let workers = concurrency.clamp(1, jobs.len().max(1));
+	let queue = Arc::new(Mutex::new(jobs.into_iter()));
+	let settings = Arc::new(settings);
+	let handles = (0..workers)
+		.map(|_| {
+			let queue = queue.clone();
+			let settings = settings.clone();
+
+			thread::spawn(move || work(&queue, &settings))
+		})
+		.collect::<Vec<_>>();
+	let mut errors = Vec::new();
+
+	for handle in handles {
+		match handle.join() {
+			Ok((mut a, mut e)) => {
+				artifacts.append(&mut a);
+				errors.append(&mut e);
+			}
+			Err(_) => errors.push(anyhow::anyhow!("a build worker panicked")),
+		}
+	}
+
+	if let Some(key) = sign_key.or(config.sign_key) {
+		let signer = signer.or(config.signer).unwrap_or(Signer::Minisign);
+
+		for artifact in &artifacts {
+			for file in [&artifact.wasm, &artifact.digest] {
+				if settings.dry_run {
+					info!("Would sign {}", file.display());
+
+					continue;
+				}
+
+				match signing::sign(signer, &key, file) {
+					Ok(signature) => info!("Signed {}", signature.display()),
+					Err(e) => errors.push(e),
+				}
+			}
+		}
+	}
+	if let Some(dir) = install_to.or(config.install_to) {
+		if settings.dry_run {
+			for artifact in &artifacts {
+				info!(
+					"Would install {} into {}",
+					artifact.wasm.display(),
+					dir.display()
+				);
+			}
+		} else {
+			match install::Overrides::load(&dir) {
+				Ok(mut overrides) => {
+					for artifact in &artifacts {
+						match overrides.install(artifact, settings.force) {
+							Ok(path) => info!("Installed {}", path.display()),
+							Err(e) => errors.push(e.context(format!(
+								"failed to install `{}`",
+								artifact.wasm.display()
+							))),
+						}
+					}
+				}
+				Err(e) => errors.push(e.context(format!(
+					"failed to read the overrides of `{}`",
+					dir.display()
+				))),
+			}
+		}
+	}
+	if let (Some(keep), false) = (config.archive_keep, settings.dry_run) {
+		let archive_dir = archive::dir(&settings.output_dir, config.archive_dir);
+
+		if let Err(e) = archive::run(&settings.output_dir, &runtimes, keep, &archive_dir) {
+			errors.push(e.context("failed to archive the old artifacts"));
+		}
+	}
+	if !settings.dry_run && settings.output_dir.is_dir() {
+		let _output_lock = lock_output_dir(&settings.output_dir)?;
+
+		match index::write(&settings.output_dir) {
+			Ok(path) => info!("Updated the index: {}", path.display()),
+			Err(e) => errors.push(e.context("failed to update the index")),
+		}
+		if let Err(e) = checksums::write(&settings.output_dir) {
+			errors.push(e.context("failed to update the checksums"));
+		}
+	}
+
+	let built = artifacts
+		.iter()
+		.filter(|artifact| !artifact.cached)
+		.collect::<Vec<_>>();
+
+	if (publish_pr || config.publish_pr.unwrap_or_default())
+		&& !settings.dry_run
+		&& !built.is_empty()
+	{
+		match pull_request::open(&built, config.github_repository.as_deref(), &settings) {
+			Ok(url) => info!("Opened {}", url),
+			Err(e) => errors.push(e.context("failed to open the pull request")),
+		}
+	}
+
+	Ok((artifacts, errors))
+}
+
+/// Group the requested runtimes by repository and target, skipping duplicates.
+///
+/// `latest_release` looks up the tag `latest-release` currently stands for.
+fn plan<F>(
+	runtimes: &[Runtime],
+	targets: &[String],
+	layout: Option<Layout>,
+	mut latest_release: F,
+) -> AnyResult<Vec<Job>>
+where
+	F: FnMut(&Runtime, Layout) -> AnyResult<String>,
+{
+	let mut jobs = Vec::<Job>::new();
+
+	for target in targets {
+		for runtime in runtimes {
+			let target = if target == github::LATEST_RELEASE {
+				// The newest releases are Darwinia 2.0 ones.
+				latest_release(runtime, layout.unwrap_or(Layout::Monorepo))?
+			} else {
+				target.clone()
+			};
+			let layout = layout.unwrap_or_else(|| Layout::detect(&target));
+
+			match jobs
+				.iter_mut()
+				.find(|j| j.target == target && j.repository() == runtime.repository(layout))
+			{
+				Some(job) => {
+					if !job.runtimes.contains(runtime) {
+						job.runtimes.push(runtime.clone());
+					}
+				}
+				None => jobs.push(Job {
+					target,
+					layout,
+					runtimes: vec![runtime.clone()],
+					logs: Vec::new(),
+				}),
+			}
+		}
+	}
+
+	Ok(jobs)
+}
+
+/// Lock the build directory, so that concurrent invocations don't corrupt the clones.
+///
+/// The lock is released once the returned file is dropped.
+fn lock_build_dir(build_dir: &Path, wait: u64) -> AnyResult<File> {
+	fs::create_dir_all(build_dir)?;
+
+	let path = build_dir.join(".lock");
+	let mut file = OpenOptions::new()
+		.read(true)
+		.write(true)
+		.create(true)
+		.open(&path)
+		.with_context(|| format!("failed to open `{}`", path.display()))?;
+	let start = Instant::now();
+	let mut waiting = false;
+
+	loop {
+		match file.try_lock_exclusive() {
+			Ok(()) => break,
+			Err(e) if e.kind() != fs2::lock_contended_error().kind() => {
+				return Err(e).with_context(|| format!("failed to lock `{}`", path.display()))
+			}
+			Err(_) => {}
+		}
+
+		if start.elapsed() >= Duration::from_secs(wait) {
+			let mut holder = String::new();
+			let _ = file.read_to_string(&mut holder);
+
+			anyhow::bail!(
+				"`{}` is used by another invocation (pid {}), wait for it to finish or pass `--lock-wait`",
+				build_dir.display(),
+				holder.trim()
+			);
+		}
+		if !waiting {
+			info!(
+				"Waiting for another invocation to release `{}`",
+				build_dir.display()
+			);
+
+			waiting = true;
+		}
+
+		thread::sleep(Duration::from_secs(1));
+	}
+
+	file.set_len(0)?;
+	file.write_all(process::id().to_string().as_bytes())?;
+
+	Ok(file)
+}
+
+/// Lock the output directory, so that concurrent builds don't drop each other's artifacts from the
+/// index and the checksums, waiting for as long as it takes.
+///
+/// The lock is released once the returned file is dropped.
+fn lock_output_dir(output_dir: &Path) -> AnyResult<File> {
+	fs::create_dir_all(output_dir)?;
+
+	let path = output_dir.join(".lock");
+	let file = OpenOptions::new()
+		.write(true)
+		.create(true)
+		.open(&path)
+		.with_context(|| format!("failed to open `{}`", path.display()))?;
+
+	file.lock_exclusive()
+		.with_context(|| format!("failed to lock `{}`", path.display()))?;
+
+	Ok(file)
+}
+
+/// Keep taking jobs from the shared queue until it's drained.
+fn work(
+	queue: &Mutex<IntoIter<(Job, Tracker)>>,
+	settings: &Settings,
+) -> (Vec<Artifact>, Vec<anyhow::Error>) {
+	let mut artifacts = Vec::new();
+	let mut errors = Vec::new();
+
+	loop {
+		let job = match queue.lock() {
+			Ok(mut queue) => queue.next(),
+			Err(_) => None,
+		};
+		let (job, mut tracker) = match job {
+			Some(next) => next,
+			None => break,
+		};
+
+		let built = artifacts.len();
+		let start = Instant::now();
+
+		metrics::started();
+
+		let result = build_job(&job, &mut tracker, settings, &mut artifacts);
+
+		metrics::finished(result.is_ok());
+
+		for artifact in &artifacts[built..] {
+			notify::notify(notify::Event::Built(artifact), settings);
+
+			if let Some(runtime) = job.runtimes.iter().find(|r| r.name() == artifact.runtime) {
+				record_history(
+					history::Record::built(artifact, settings.features_of(runtime).0),
+					settings,
+				);
+			}
+		}
+		if let Err(e) = result {
+			let e = e.context(format!("failed to build `{}`", job.label()));
+
+			// The job stops at the first runtime which fails.
+			if let Some(runtime) = job.runtimes.get(artifacts.len() - built) {
+				record_history(
+					history::Record::failed(
+						runtime,
+						&job.target,
+						settings.features_of(runtime).0,
+						start.elapsed(),
+						&e,
+					),
+					settings,
+				);
+			}
+
+			// The log of the runtime which failed, the job stops at the first failure.
+			notify::notify(
+				notify::Event::Failed {
+					label: &job.label(),
+					error: &e,
+					log: job
+						.logs
+						.get(artifacts.len() - built)
+						.map(|log| log.path.as_path()),
+				},
+				settings,
+			);
+			errors.push(e);
+		}
+	}
+
+	(artifacts, errors)
+}
+
+/// A history which can't be written doesn't fail the build.
+fn record_history(record: history::Record, settings: &Settings) {
+	if settings.dry_run {
+		return;
+	}
+	if let Err(e) = history::append(&settings.build_dir, &record) {
+		warn!("{:#}", e);
+	}
+}
+
+fn build_job(
+	job: &Job,
+	tracker: &mut Tracker,
+	settings: &Settings,
+	artifacts: &mut Vec<Artifact>,
+) -> AnyResult<()> {
+	let source_dir = if let Some(source_path) = &settings.source_path {
+		if settings.install && settings.builder == Builder::Cargo {
+			with_logs(&job.logs, || toolchain::ensure(source_path, settings))
+				.with_context(|| BuildError::CheckoutFailed { label: job.label() })?;
+		}
+
+		source_path.clone()
+	} else if settings.use_worktrees {
+		job.worktree_dir(&settings.build_dir)
+	} else {
+		let clone_dir = job.clone_dir(&settings.build_dir);
+
+		info!("Checking out {}", job.label());
+		tracker.phase(Phase::Checkout);
+		with_logs(&job.logs, || {
+			checkout(&clone_dir, job, settings)?;
+
+			if settings.install && settings.builder == Builder::Cargo {
+				toolchain::ensure(&clone_dir, settings)?;
+			}
+
+			AnyResult::Ok(())
+		})
+		.with_context(|| BuildError::CheckoutFailed { label: job.label() })?;
+
+		clone_dir
+	};
+
+	let result = job
+		.runtimes
+		.iter()
+		.enumerate()
+		.try_for_each(|(i, runtime)| {
+			let log = job.logs.get(i);
+			let artifact = with_logs(log.map(slice::from_ref).unwrap_or_default(), || {
+				build(
+					runtime,
+					&job.target,
+					job.layout,
+					&source_dir,
+					log,
+					tracker,
+					settings,
+				)
+			});
+
+			match (artifact, log) {
+				(Ok(artifact), _) => {
+					artifacts.extend(artifact);
+
+					Ok(())
+				}
+				(Err(e), Some(log)) => {
+					let e = e.context(format!("see the log `{}`", log.path.display()));
+
+					if log.contains(LOCK_DRIFT) {
+						Err(e.context(format!(
+							"the `Cargo.lock` of {} is out of date, pass `--allow-lock-update` to let cargo update it",
+							job.label()
+						)))
+					} else {
+						Err(e)
+					}
+				}
+				(Err(e), None) => Err(e),
+			}
+		});
+
+	revert_patches(&source_dir, &job.label(), settings);
+
+	result
+}
+
+/// Build the runtime from `source_dir`, there's nothing to return in dry-run mode.
+fn build(
+	runtime: &Runtime,
+	target: &str,
+	layout: Layout,
+	source_dir: &Path,
+	log: Option<&BuildLog>,
+	tracker: &mut Tracker,
+	settings: &Settings,
+) -> AnyResult<Option<Artifact>> {
+	let runtime_manifest = settings.manifest_path(runtime, layout);
+	let runtime_package = settings.package(runtime);
+	let runtime_lowercase_name = runtime.name().to_owned();
+	let label = format!("{}@{}", runtime_lowercase_name, target);
+	let start = Instant::now();
+	let mut timings = Timings::default();
+	let toolchain = toolchain::detect(source_dir)?.and_then(|toolchain| toolchain.channel);
+	// A branch keeps moving, the commit is what the artifact is really built from.
+	let commit = if settings.dry_run {
+		"<commit>".into()
+	} else {
+		settings.capture(source_dir, "git", &["rev-parse", "HEAD"])?
+	};
+	// Checked before cargo gets to touch the `Cargo.lock`.
+	let dirty = settings.source_path.is_some() && is_dirty(source_dir, settings)?;
+
+	tracker.relabel(&label);
+
+	if let Some(toolchain) = &toolchain {
+		info!("Using the {} toolchain pinned by {}", toolchain, label);
+	}
+
+	info!("Building {} at {}", label, commit);
+
+	let (features, no_default_features) = settings.features_of(runtime);
+	let jobs = settings.jobs.map(|jobs| jobs.to_string());
+	let mut build_opts = Vec::new();
+
+	if !settings.allow_lock_update {
+		build_opts.push("--locked");
+	}
+	if settings.offline {
+		build_opts.push("--offline");
+	}
+	if let Some(jobs) = &jobs {
+		build_opts.extend(["--jobs", jobs.as_str()]);
+	}
+
+	if !features.is_empty() {
+		build_opts.extend(["--features", features.as_str()]);
+	}
+	if no_default_features {
+		build_opts.push("--no-default-features");
+	}
+
+	build_opts.extend(settings.cargo_args.iter().map(String::as_str));
+
+	let profile = settings.profile.as_deref().unwrap_or("release");
+	// `--profile` only got stable with Rust 1.57, older tags are built with `--release`.
+	let profile_args = if profile == "release" {
+		vec!["--release"]
+	} else {
+		vec!["--profile", profile]
+	};
+	let mut build_args = vec!["b"];
+
+	build_args.extend(&profile_args);
+	build_args.extend(["--manifest-path", runtime_manifest.as_str()]);
+	build_args.extend(&build_opts);
+
+	let wasm_file = format!(
+		"{}/wbuild/{}/{}.compact.compressed.wasm",
+		profile_dir(profile),
+		runtime_package,
+		runtime_package.replace('-', "_"),
+	);
+	let mut clean_args = vec!["clean"];
+
+	clean_args.extend(&profile_args);
+	clean_args.extend([
+		"--manifest-path",
+		runtime_manifest.as_str(),
+		"-p",
+		runtime_package.as_str(),
+	]);
+
+	let target_dir = match settings.builder {
+		Builder::Cargo | Builder::Srtool => source_dir.join("target"),
+		Builder::Docker | Builder::Podman => source_dir.join(container::TARGET_DIR),
+	};
+	// `cargo clean` is only needed when the runtime is built from other sources or features than the
+	// last time, what the marker left in the target directory tells.
+	let marker_path = target_dir.join(format!(".runtime-overrides-{}.json", runtime_package));
+	let marker = serde_json::json!({
+		"commit": commit,
+		"features": features,
+		"no_default_features": no_default_features,
+		"profile": profile,
+	});
+	let clean = !settings.no_clean
+		&& fs::read(&marker_path)
+			.ok()
+			.and_then(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+			!= Some(marker.clone());
+	// A killed build may leave the target directory half written, clean it the next time.
+	let forget_marker = |e: anyhow::Error| {
+		if e.is::<TimedOut>() {
+			let _ = fs::remove_file(&marker_path);
+		}
+
+		e
+	};
+	let build_failed = || BuildError::BuildFailed {
+		label: label.clone(),
+	};
+	let (built_wasm_path, srtool_digest) = match settings.builder {
+		Builder::Cargo => {
+			if clean {
+				tracker.phase(Phase::Clean);
+
+				let (program, args) = settings.cargo(toolchain.as_deref(), &clean_args);
+
+				settings
+					.run(source_dir, &label, program, &args)
+					.with_context(build_failed)?;
+
+				timings.clean = start.elapsed().as_secs_f64();
+			}
+
+			let (program, args) = settings.cargo(toolchain.as_deref(), &build_args);
+
+			tracker.phase(Phase::Build);
+			settings
+				.build(source_dir, &label, program, &args)
+				.map_err(forget_marker)
+				.with_context(build_failed)?;
+
+			(target_dir.join(&wasm_file), None)
+		}
+		Builder::Docker | Builder::Podman => {
+			let image = settings.image_of_builder().unwrap_or_default();
+			let cargo = |args: &[&str]| {
+				let args = args
+					.iter()
+					.copied()
+					.chain(["--target-dir", container::TARGET_DIR])
+					.collect::<Vec<_>>();
+
+				container::command(
+					settings.builder,
+					image,
+					source_dir,
+					"cargo",
+					&args,
+					&settings.envs,
+					settings.offline,
+				)
+			};
+
+			if clean {
+				tracker.phase(Phase::Clean);
+
+				let (program, args) = cargo(&clean_args)?;
+
+				settings
+					.run(
+						source_dir,
+						&label,
+						program,
+						&args.iter().map(String::as_str).collect::<Vec<_>>(),
+					)
+					.with_context(build_failed)?;
+
+				timings.clean = start.elapsed().as_secs_f64();
+			}
+
+			let (program, args) = cargo(&build_args)?;
+
+			tracker.phase(Phase::Build);
+			settings
+				.build(
+					source_dir,
+					&label,
+					program,
+					&args.iter().map(String::as_str).collect::<Vec<_>>(),
+				)
+				.map_err(forget_marker)
+				.with_context(build_failed)?;
+
+			(target_dir.join(&wasm_file), None)
+		}
+		Builder::Srtool => {
+			tracker.phase(Phase::Build);
+			srtool::build(
+				source_dir,
+				&label,
+				&runtime_manifest,
+				&runtime_package,
+				&build_opts,
+				settings,
+			)
+			.with_context(build_failed)?
+		}
+	};
+
+	if !settings.dry_run && !built_wasm_path.is_file() {
+		return Err(BuildError::WasmMissing {
+			path: built_wasm_path,
+		}
+		.into());
+	}
+
+	if !settings.dry_run && settings.builder != Builder::Srtool {
+		fs::write(&marker_path, marker.to_string())
+			.with_context(|| format!("failed to write `{}`", marker_path.display()))?;
+	}
+
+	timings.build = start.elapsed().as_secs_f64() - timings.clean;
+
+	if settings.verify_reproducible {
+		check_reproducible(
+			source_dir,
+			&label,
+			toolchain.as_deref(),
+			&build_args,
+			&built_wasm_path,
+			&wasm_file,
+			settings,
+		)?;
+	}
+
+	if settings.dry_run {
+		let (wasm_path, digest_path) = settings.artifact_paths(runtime, target, &commit, None);
+
+		info!("Would generate WASM:   {}", wasm_path.display());
+		info!("Would generate digest: {}", digest_path.display());
+
+		return Ok(None);
+	}
+
+	let compact_wasm_path = built_wasm_path.with_file_name(format!(
+		"{}.compact.wasm",
+		runtime_package.replace('-', "_")
+	));
+
+	tracker.phase(Phase::Package);
+
+	let digest_failed = || BuildError::DigestFailed {
+		label: label.clone(),
+	};
+
+	if let Some(args) = &settings.wasm_opt {
+		wasm_opt::run(
+			&compact_wasm_path,
+			&built_wasm_path,
+			args,
+			source_dir,
+			&label,
+			settings,
+		)?;
+	}
+
+	if features.split(',').any(|feature| feature == "evm-tracing") {
+		apis::check_tracing(&compact_wasm_path).with_context(digest_failed)?;
+	}
+	if let (Some(check), Some(uri)) = (settings.try_runtime, rpc_url(&settings.runtimes, runtime)) {
+		try_runtime::run(check, &built_wasm_path, uri, source_dir, &label, settings)?;
+	}
+
+	let wasm = Subwasm::new(&Source::File(built_wasm_path.clone()));
+	let mut runtime_info = serde_json::to_value(wasm.runtime_info()).with_context(digest_failed)?;
+	let metadata = serde_json::to_value(wasm.runtime_metadata_prefixed())?;
+
+	check_size(runtime, &label, &built_wasm_path, &metadata, settings)?;
+
+	let (wasm_path, digest_path) = settings.artifact_paths(
+		runtime,
+		target,
+		&commit,
+		runtime_info["core_version"].as_str().and_then(spec_version),
+	);
+
+	for path in [&wasm_path, &digest_path] {
+		create_dir_unchecked(path.parent().unwrap_or_else(|| Path::new(".")))?;
+	}
+
+	// Named after the artifact once it is known, it's only renamed once the artifact is in place so
+	// that a failure until then points to where the log still is.
+	let log_path = log.map(|_| {
+		settings.log_dir(runtime).join(format!(
+			"{}.log",
+			digest_path
+				.file_stem()
+				.unwrap_or_default()
+				.to_string_lossy()
+		))
+	});
+
+	tracker.phase(Phase::Digest);
+
+	let set_code_hash = proposal::set_code_hash(&metadata, &fs::read(&built_wasm_path)?)
+		.with_context(digest_failed)?;
+	let compressed_size = fs::metadata(&built_wasm_path)?.len();
+	let uncompressed_size = fs::metadata(&compact_wasm_path)?.len();
+	let compression_ratio = uncompressed_size as f64 / compressed_size.max(1) as f64;
+	let size_delta = previous_sizes(&digest_path).map(|(from, compressed, uncompressed)| {
+		serde_json::json!({
+			"from": from,
+			"compressed": compressed_size as i64 - compressed as i64,
+			"uncompressed": uncompressed_size as i64 - uncompressed as i64,
+		})
+	});
+
+	if let Some(runtime_info) = runtime_info.as_object_mut() {
+		runtime_info.insert("schema_version".into(), schema::VERSION.into());
+		runtime_info.insert("set_code_hash".into(), set_code_hash.clone().into());
+		// The build cache key.
+		runtime_info.insert("commit".into(), commit.into());
+		runtime_info.insert("features".into(), features.into());
+		runtime_info.insert("no_default_features".into(), no_default_features.into());
+
+		if !settings.patches.is_empty() {
+			runtime_info.insert("patches".into(), settings.patches_json());
+		}
+		if !settings.cargo_patch.is_empty() {
+			runtime_info.insert("cargo_patch".into(), settings.cargo_patch_json());
+		}
+
+		if settings.source_path.is_some() {
+			runtime_info.insert("dirty".into(), dirty.into());
+		}
+
+		let submodules = submodules(source_dir, settings)?;
+
+		if !submodules.is_null() {
+			runtime_info.insert("submodules".into(), submodules);
+		}
+
+		runtime_info.insert(
+			PROVENANCE.into(),
+			serde_json::json!({
+				"repository": settings.repo_url(runtime, layout),
+				"source_path": settings.source_path,
+				"target": target,
+				"toolchain": toolchain,
+				"builder": settings.builder,
+				"image": settings.image_of_builder(),
+				"profile": profile,
+				"env": settings.envs.iter().cloned().collect::<BTreeMap<_, _>>(),
+				"cargo_args": settings.cargo_args,
+				"locked": !settings.allow_lock_update,
+				"wasm_opt": settings.wasm_opt,
+				"rustc": match &srtool_digest {
+					Some(srtool_digest) => srtool_digest["rustc"].clone(),
+					None => rustc_version(source_dir, toolchain.as_deref(), settings)?.into(),
+				},
+				"built_at": chrono::Utc::now().to_rfc3339(),
+				"tool_version": env!("CARGO_PKG_VERSION"),
+				"compressed_size": compressed_size,
+				"uncompressed_size": uncompressed_size,
+				"compression_ratio": (compression_ratio * 100.).round() / 100.,
+				"size_delta": size_delta,
+				"cid": null,
+				"reproducible": settings.verify_reproducible.then_some(true),
+				"log": log_path.as_deref().map(path_arg),
+			}),
+		);
+	}
+
+	// Held until the artifact is in place, the other workers of `serve` share the output directory.
+	let output_lock = lock_output_dir(&settings.output_dir)?;
+
+	check_overwrite(&wasm_path, &digest_path, &runtime_info, settings.force)?;
+
+	if !settings.allow_duplicate {
+		check_duplicate(&digest_path, &runtime_info)?;
+	}
+
+	for format in &settings.metadata {
+		let path = write_metadata(&wasm, *format, &digest_path).with_context(digest_failed)?;
+
+		info!("Generated metadata: {}", path.display());
+	}
+	if let Some(srtool_digest) = &srtool_digest {
+		let path = sibling_path(&digest_path, "srtool", "json");
+		let tmp = temporary_path(&path);
+
+		create_dir_unchecked(path.parent().unwrap_or_else(|| Path::new(".")))?;
+		serde_json::to_writer_pretty(File::create(&tmp)?, srtool_digest)?;
+		fs::rename(&tmp, &path).with_context(|| format!("failed to write `{}`", path.display()))?;
+		info!("Generated srtool digest: {}", path.display());
+	}
+	if settings.keep_uncompressed {
+		let path = sibling_path(&digest_path, "wasms", UNCOMPRESSED_EXTENSION);
+
+		copy_file(&compact_wasm_path, &path).with_context(digest_failed)?;
+		info!("Generated uncompressed WASM: {}", path.display());
+	}
+	// Published once nothing refuses the artifact anymore.
+	let cid = settings
+		.ipfs
+		.as_deref()
+		.map(|api| {
+			let cid = ipfs::add(api, &built_wasm_path)?;
+
+			if let Some((service, token)) = &settings.ipfs_pin_service {
+				ipfs::pin_remotely(service, token, &cid, &label)?;
+			}
+
+			AnyResult::Ok(cid)
+		})
+		.transpose()
+		.with_context(digest_failed)?;
+
+	if let Some(cid) = &cid {
+		runtime_info[PROVENANCE]["cid"] = cid.as_str().into();
+	}
+
+	write_artifact(
+		&built_wasm_path,
+		&wasm_path,
+		&digest_path,
+		&runtime_info,
+		settings.content_addressed,
+	)
+	.with_context(digest_failed)?;
+	drop(output_lock);
+
+	if let (Some(log), Some(log_path)) = (log, &log_path) {
+		fs::rename(&log.path, log_path)
+			.with_context(|| format!("failed to write `{}`", log_path.display()))?;
+	}
+
+	info!("Generated WASM:   {}", wasm_path.display());
+	info!("Generated digest: {}", digest_path.display());
+	info!("setCode hash:     {}", set_code_hash);
+	info!(
+		"Size:             {} compressed, {} uncompressed, {:.2}x",
+		compressed_size, uncompressed_size, compression_ratio
+	);
+
+	if let Some(size_delta) = &size_delta {
+		info!(
+			"Since {}: {:+} compressed, {:+} uncompressed",
+			size_delta["from"].as_str().unwrap_or_default(),
+			size_delta["compressed"].as_i64().unwrap_or_default(),
+			size_delta["uncompressed"].as_i64().unwrap_or_default()
+		);
+	}
+	if let Some(cid) = &cid {
+		info!("IPFS CID:         {}", cid);
+	}
+	if let Some(log_path) = &log_path {
+		info!("Build log:        {}", log_path.display());
+	}
+	if settings.check_chain {
+		check_chain(runtime, &runtime_info, settings);
+	}
+
+	timings.total = start.elapsed().as_secs_f64();
+
+	let mut artifact = Artifact::new(runtime, target, wasm_path, digest_path, runtime_info)?;
+
+	artifact.timings = timings;
+
+	Ok(Some(artifact))
+}
+
+/// The `rustc --version` the runtimes are built with, on the host or in the container.
+fn rustc_version(
+	source_dir: &Path,
+	toolchain: Option<&str>,
+	settings: &Settings,
+) -> AnyResult<String> {
+	match (settings.builder, settings.image_of_builder()) {
+		(Builder::Docker | Builder::Podman, Some(image)) => {
+			let (program, args) = container::command(
+				settings.builder,
+				image,
+				source_dir,
+				"rustc",
+				&["--version"],
+				&settings.envs,
+				settings.offline,
+			)?;
+
+			settings.capture(
+				source_dir,
+				program,
+				&args.iter().map(String::as_str).collect::<Vec<_>>(),
+			)
+		}
+		_ => toolchain::rustc_version(source_dir, toolchain, settings),
+	}
+}
+
+/// Warn, or fail if `--strict-size`, when the compressed wasm is larger than its chain accepts.
+fn check_size(
+	runtime: &Runtime,
+	label: &str,
+	wasm_path: &Path,
+	metadata: &serde_json::Value,
+	settings: &Settings,
+) -> AnyResult<()> {
+	let limit = match settings
+		.runtime_config(runtime)
+		.and_then(|r| r.max_code_size)
+		.or_else(|| size::max_code_size(metadata))
+	{
+		Some(limit) => limit,
+		None => {
+			warn!(
+				"The max code size of {} is unknown, set its `max-code-size`",
+				runtime.name()
+			);
+
+			return Ok(());
+		}
+	};
+	let size = fs::metadata(wasm_path)?.len();
+
+	if size <= u64::from(limit) {
+		return Ok(());
+	}
+
+	let message = format!(
+		"{} is {} bytes compressed, over the {} bytes its chain accepts",
+		label, size, limit
+	);
+
+	if settings.strict_size {
+		anyhow::bail!(message);
+	}
+
+	warn!("!!! {} !!!", message);
+
+	Ok(())
+}
+
+/// Build again from scratch, in another target directory, and fail unless the wasms are identical.
+fn check_reproducible(
+	source_dir: &Path,
+	label: &str,
+	toolchain: Option<&str>,
+	build_args: &[&str],
+	built_wasm_path: &Path,
+	wasm_file: &str,
+	settings: &Settings,
+) -> AnyResult<()> {
+	let target_dir = source_dir.join("target-reproducible");
+	let target_dir_arg = path_arg(&target_dir);
+	let mut args = build_args.to_vec();
+
+	args.extend(["--target-dir", &target_dir_arg]);
+
+	if target_dir.exists() && !settings.dry_run {
+		fs::remove_dir_all(&target_dir)
+			.with_context(|| format!("failed to remove `{}`", target_dir.display()))?;
+	}
+
+	info!("Building {} again to check it is reproducible", label);
+
+	let (program, args) = settings.cargo(toolchain, &args);
+
+	if let Err(e) = settings.build(source_dir, label, program, &args) {
+		if e.is::<TimedOut>() {
+			let _ = fs::remove_dir_all(&target_dir);
+		}
+
+		return Err(e);
+	}
+	if settings.dry_run {
+		return Ok(());
+	}
+
+	let rebuilt_wasm_path = target_dir.join(wasm_file);
+
+	if fs::read(built_wasm_path)? != fs::read(&rebuilt_wasm_path)? {
+		anyhow::bail!(
+			"{} isn't reproducible, `{}` differs from `{}`, both are kept to be compared",
+			label,
+			rebuilt_wasm_path.display(),
+			built_wasm_path.display()
+		);
+	}
+
+	info!("{} is reproducible", label);
+
+	fs::remove_dir_all(&target_dir)
+		.with_context(|| format!("failed to remove `{}`", target_dir.display()))
+}
+
+/// Warn when the build isn't the runtime its chain is running, e.g. a tag older than the last
+/// upgrade.
+fn check_chain(runtime: &Runtime, runtime_info: &serde_json::Value, settings: &Settings) {
+	let rpc = match rpc_url(&settings.runtimes, runtime) {
+		Some(rpc) => rpc,
+		None => {
+			warn!(
+				"No RPC endpoint is known for {}, it can't be checked against its chain",
+				runtime.name()
+			);
+
+			return;
+		}
+	};
+	let on_chain = match rpc::Client::new(rpc).runtime_version(None) {
+		Ok(version) => version,
+		Err(e) => {
+			warn!(
+				"Could not check {} against its chain: {:?}",
+				runtime.name(),
+				e
+			);
+
+			return;
+		}
+	};
+	let built = runtime_info["core_version"]
+		.as_str()
+		.and_then(|core_version| core_version.split_whitespace().next())
+		.and_then(|version| version.rsplit_once('-'));
+
+	match built {
+		Some((spec_name, spec_version))
+			if spec_name == on_chain.spec_name
+				&& spec_version == on_chain.spec_version.to_string() =>
+		{
+			info!(
+				"{} matches the runtime of its chain, {}-{}",
+				runtime.name(),
+				spec_name,
+				spec_version
+			)
+		}
+		Some((spec_name, spec_version)) => warn!(
+			"{} was built as {}-{} but its chain is running {}-{}, is the target right?",
+			runtime.name(),
+			spec_name,
+			spec_version,
+			on_chain.spec_name,
+			on_chain.spec_version
+		),
+		None => warn!("The spec version of {} is unknown", runtime.name()),
+	}
+}
+
+/// Whether the digest is the one of a build of `runtime` from `commit` with the current features
+/// and patches.
+fn is_build_of(
+	runtime_info: &serde_json::Value,
+	runtime: &Runtime,
+	commit: &str,
+	settings: &Settings,
+) -> bool {
+	let (features, no_default_features) = settings.features_of(runtime);
+
+	runtime_info["commit"] == commit
+		&& runtime_info["features"] == features
+		&& runtime_info["no_default_features"] == no_default_features
+		&& runtime_info["patches"] == settings.patches_json()
+		&& runtime_info["cargo_patch"] == settings.cargo_patch_json()
+}
+
+/// Fail before building if the artifact about to be written would replace a different one.
+fn check_destination(
+	runtime: &Runtime,
+	target: &str,
+	commit: &str,
+	settings: &Settings,
+) -> AnyResult<()> {
+	// Named after the spec version, the destination is only known once built.
+	if settings.name_by_spec_version {
+		return Ok(());
+	}
+
+	let (wasm_path, digest_path) = settings.artifact_paths(runtime, target, commit, None);
+	let same = read_digest(&digest_path).map_or(false, |runtime_info| {
+		is_build_of(&runtime_info, runtime, commit, settings)
+	});
+
+	if !settings.force && !same && (wasm_path.exists() || digest_path.exists()) {
+		anyhow::bail!(
+			"`{}` was built from another commit or with other features, pass `--force` to overwrite it",
+			wasm_path.display()
+		);
+	}
+
+	Ok(())
+}
+
+/// The artifact of `runtime` previously built from `commit` with the same features, if any.
+///
+/// One built for another target, e.g. a tag of the same commit, is copied over.
+fn cached(
+	runtime: &Runtime,
+	target: &str,
+	commit: &str,
+	settings: &Settings,
+) -> AnyResult<Option<Artifact>> {
+	let (wasm_path, digest_path) = settings.artifact_paths(runtime, target, commit, None);
+	let wasms_dir = wasm_path.parent().unwrap_or_else(|| Path::new("."));
+	let digests_dir = digest_path.parent().unwrap_or_else(|| Path::new("."));
+	let others = dir_entries(digests_dir)
+		.into_iter()
+		.filter(|path| path != &digest_path);
+
+	for candidate in [digest_path.clone()].into_iter().chain(others) {
+		let runtime_info = match read_digest(&candidate) {
+			Some(runtime_info) => runtime_info,
+			None => continue,
+		};
+
+		// Built from uncommitted changes, which may differ from the current ones.
+		if !is_build_of(&runtime_info, runtime, commit, settings) || runtime_info["dirty"] == true {
+			continue;
+		}
+
+		let candidate_wasm = wasms_dir.join(format!(
+			"{}.compact.compressed.wasm",
+			candidate.file_stem().unwrap_or_default().to_string_lossy()
+		));
+
+		if !candidate_wasm.is_file() {
+			continue;
+		}
+
+		let (wasm_path, digest_path) = settings.artifact_paths(
+			runtime,
+			target,
+			commit,
+			runtime_info["core_version"].as_str().and_then(spec_version),
+		);
+
+		if candidate != digest_path {
+			if settings.dry_run {
+				info!(
+					"Would copy {} from {}",
+					wasm_path.display(),
+					candidate_wasm.display()
+				);
+			} else {
+				// Same order as `write_artifact`.
+				match runtime_info["blake2_256"].as_str() {
+					Some(blake2_256) if settings.content_addressed => {
+						store::write(&candidate_wasm, &wasm_path, blake2_256, true)?
+					}
+					_ => copy_file(&candidate_wasm, &wasm_path)?,
+				}
+
+				let uncompressed = sibling_path(&candidate, "wasms", UNCOMPRESSED_EXTENSION);
+
+				if settings.keep_uncompressed && uncompressed.is_file() {
+					copy_file(
+						&uncompressed,
+						&sibling_path(&digest_path, "wasms", UNCOMPRESSED_EXTENSION),
+					)?;
+				}
+				copy_file(&candidate, &digest_path)?;
+			}
+		}
+
+		info!("Reusing {} built from {}", wasm_path.display(), commit);
+
+		let mut artifact = Artifact::new(runtime, target, wasm_path, digest_path, runtime_info)?;
+
+		artifact.cached = true;
+
+		return Ok(Some(artifact));
+	}
+
+	Ok(None)
+}
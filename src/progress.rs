@@ -0,0 +1,168 @@
+//! What every build is busy with: a spinner per job on a terminal, a line per phase otherwise.
+
+use std::{
+	fmt::{self, Display, Formatter},
+	time::{Duration, Instant},
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::info;
+
+use crate::metrics;
+
+/// The steps of a build, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+	Clone,
+	Checkout,
+	Clean,
+	Build,
+	Package,
+	Digest,
+}
+impl Display for Phase {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Self::Clone => "clone",
+			Self::Checkout => "checkout",
+			Self::Clean => "clean",
+			Self::Build => "build",
+			Self::Package => "package",
+			Self::Digest => "digest",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Progress {
+	/// None unless stderr is a terminal.
+	bars: Option<MultiProgress>,
+}
+impl Progress {
+	/// Draw the spinners unless `quiet`, or stderr isn't a terminal.
+	pub fn new(quiet: bool) -> Self {
+		let bars = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+
+		Self {
+			bars: (!quiet && !bars.is_hidden()).then_some(bars),
+		}
+	}
+
+	/// Where the terminal logs go, printed above the spinners instead of through them.
+	pub fn output(&self) -> fern::Output {
+		let bars = self.bars.clone();
+
+		fern::Output::call(move |record| match &bars {
+			Some(bars) => {
+				let _ = bars.println(record.args().to_string());
+			}
+			None => eprintln!("{}", record.args()),
+		})
+	}
+
+	/// Start tracking what `label`, e.g. `darwinia@main`, is busy with.
+	pub fn start(&self, label: &str) -> Tracker {
+		let bar = self.bars.as_ref().map(|bars| {
+			let bar = bars.add(ProgressBar::new_spinner());
+
+			bar.set_style(
+				ProgressStyle::with_template("{spinner} {prefix:.bold} {wide_msg} {elapsed}")
+					.unwrap_or_else(|_| ProgressStyle::default_spinner()),
+			);
+			bar.set_prefix(label.to_owned());
+			bar.enable_steady_tick(Duration::from_millis(100));
+
+			bar
+		});
+		let now = Instant::now();
+
+		Tracker {
+			label: label.to_owned(),
+			bar,
+			phase: None,
+			start: now,
+			phase_start: now,
+		}
+	}
+}
+
+/// The progress of a single job, finished once dropped.
+pub struct Tracker {
+	label: String,
+	bar: Option<ProgressBar>,
+	phase: Option<Phase>,
+	start: Instant,
+	phase_start: Instant,
+}
+impl Tracker {
+	/// Tell what is busy from now on, e.g. `crab@main` out of the runtimes of the job.
+	pub fn relabel(&mut self, label: &str) {
+		self.label = label.to_owned();
+
+		if let Some(bar) = &self.bar {
+			bar.set_prefix(label.to_owned());
+		}
+	}
+
+	/// Enter `phase`, telling how long the previous one took.
+	pub fn phase(&mut self, phase: Phase) {
+		let previous = self.end_phase();
+
+		self.phase = Some(phase);
+
+		match &self.bar {
+			Some(bar) => bar.set_message(format!("{}{}", phase, previous.unwrap_or_default())),
+			None => info!(
+				"[{}] {} ({} elapsed{})",
+				self.label,
+				phase,
+				format_duration(self.start.elapsed()),
+				previous.unwrap_or_default()
+			),
+		}
+	}
+
+	/// Wait for a worker, the time spent queued belonging to no phase.
+	pub fn queue(&mut self) {
+		let previous = self.end_phase();
+
+		if let Some(bar) = &self.bar {
+			bar.set_message(format!("queued{}", previous.unwrap_or_default()));
+		}
+	}
+
+	/// How long the current phase took, if any.
+	fn end_phase(&mut self) -> Option<String> {
+		let previous = self.phase.take().map(|previous| {
+			let elapsed = self.phase_start.elapsed();
+
+			metrics::observe(previous, elapsed);
+
+			format!(", {} took {}", previous, format_duration(elapsed))
+		});
+
+		self.phase_start = Instant::now();
+
+		previous
+	}
+}
+impl Drop for Tracker {
+	fn drop(&mut self) {
+		if let Some(phase) = self.phase {
+			metrics::observe(phase, self.phase_start.elapsed());
+		}
+		if let Some(bar) = &self.bar {
+			bar.finish_and_clear();
+		}
+	}
+}
+
+fn format_duration(duration: Duration) -> String {
+	let seconds = duration.as_secs();
+
+	if seconds < 60 {
+		format!("{}s", seconds)
+	} else {
+		format!("{}m{:02}s", seconds / 60, seconds % 60)
+	}
+}
@@ -0,0 +1,92 @@
+//! The calls governance proposes to upgrade a chain to a runtime.
+
+use anyhow::Context;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use clap::ArgEnum;
+use codec::Encode;
+use serde_json::Value;
+
+use crate::AnyResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Call {
+	/// `system.setCode(code)`, the upgrade of a relay or solo chain.
+	SetCode,
+	/// `parachainSystem.authorizeUpgrade(blake2_256(code))`, the first step of a parachain upgrade.
+	AuthorizeUpgrade,
+}
+
+/// The SCALE encoded `call` upgrading to `code`, its indexes looked up in the V14 `metadata`.
+pub fn encode(call: Call, metadata: &Value, code: &[u8]) -> AnyResult<Vec<u8>> {
+	match call {
+		Call::SetCode => {
+			let (pallet, call, _) = call_index(metadata, "System", "set_code")?;
+			let mut encoded = vec![pallet, call];
+
+			encoded.extend(code.to_vec().encode());
+
+			Ok(encoded)
+		}
+		Call::AuthorizeUpgrade => {
+			let (pallet, call, fields) =
+				call_index(metadata, "ParachainSystem", "authorize_upgrade")?;
+			let mut encoded = vec![pallet, call];
+
+			encoded.extend(blake2_256(code));
+
+			// The newer cumulus releases added `check_version`.
+			if fields > 1 {
+				encoded.push(true as u8);
+			}
+
+			Ok(encoded)
+		}
+	}
+}
+
+/// The blake2-256 hash of the `system.setCode(code)` preimage, `0x` prefixed.
+pub fn set_code_hash(metadata: &Value, code: &[u8]) -> AnyResult<String> {
+	Ok(format!(
+		"0x{}",
+		crate::to_hex(&blake2_256(&encode(Call::SetCode, metadata, code)?))
+	))
+}
+
+fn blake2_256(bytes: &[u8]) -> Vec<u8> {
+	Blake2b::<U32>::digest(bytes).to_vec()
+}
+
+/// The pallet and call indexes of `pallet.call` in the JSON of a V14 metadata, and its number of
+/// fields.
+///
+/// They shift from a runtime to the other, e.g. frame-system dropped `fill_block` at some point.
+fn call_index(metadata: &Value, pallet: &str, call: &str) -> AnyResult<(u8, u8, usize)> {
+	let metadata = &metadata[1]["V14"];
+	let pallet_metadata = metadata["pallets"]
+		.as_array()
+		.context("only the V14 metadata is supported")?
+		.iter()
+		.find(|p| p["name"] == pallet)
+		.with_context(|| format!("the runtime has no {} pallet", pallet))?;
+	let calls_type = &pallet_metadata["calls"]["ty"];
+	let variants = metadata["types"]["types"]
+		.as_array()
+		.into_iter()
+		.flatten()
+		.find(|ty| &ty["id"] == calls_type)
+		.and_then(|ty| ty["type"]["def"]["variant"]["variants"].as_array())
+		.with_context(|| format!("the {} pallet has no calls", pallet))?;
+	let index = |value: &Value| value.as_u64().and_then(|index| u8::try_from(index).ok());
+
+	variants
+		.iter()
+		.find(|variant| variant["name"] == call)
+		.and_then(|variant| {
+			Some((
+				index(&pallet_metadata["index"])?,
+				index(&variant["index"])?,
+				variant["fields"].as_array().map_or(0, Vec::len),
+			))
+		})
+		.with_context(|| format!("the {} pallet has no `{}` call", pallet, call))
+}
@@ -0,0 +1,155 @@
+//! Removal of the artifacts the live chains can't load anymore.
+//!
+//! A node only overrides the runtime whose spec version it is executing, so an artifact older than
+//! the current runtime is dead weight unless the node has to replay the historic blocks.
+
+use std::{
+	collections::HashSet,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+
+use crate::{config::Config, registry::Runtime, rpc::Client, signing, store, AnyResult};
+
+/// Flag, or delete if `delete`, the artifacts of `runtimes` older than their chain's runtime.
+///
+/// With `historic`, those of a spec version the chain ran at some point are kept too.
+pub fn run(
+	output_dir: &Path,
+	runtimes: &[Runtime],
+	config: &Config,
+	historic: bool,
+	delete: bool,
+) -> AnyResult<()> {
+	let mut prunable = 0;
+
+	for runtime in runtimes {
+		let digests_dir = output_dir.join(runtime.name()).join("digests");
+
+		if !digests_dir.is_dir() {
+			continue;
+		}
+
+		let rpc = match crate::config::rpc_url(&config.runtimes, runtime) {
+			Some(rpc) => rpc,
+			None => {
+				warn!(
+					"No RPC endpoint is known for {}, skipping it",
+					runtime.name()
+				);
+
+				continue;
+			}
+		};
+		let client = Client::new(rpc);
+		let current = client
+			.spec_version(None)
+			.with_context(|| format!("failed to query the spec version of {}", runtime.name()))?;
+		let enacted = if historic {
+			client
+				.spec_versions()
+				.with_context(|| {
+					format!(
+						"failed to query the historic spec versions of {}, is `{}` an archive node?",
+						runtime.name(),
+						rpc
+					)
+				})?
+				.into_iter()
+				.collect()
+		} else {
+			HashSet::new()
+		};
+
+		info!("{} is running spec version {}", runtime.name(), current);
+
+		for digest in crate::dir_entries(&digests_dir) {
+			let name = match digest.file_name().unwrap_or_default().to_str() {
+				Some(name) if !name.starts_with('.') => name,
+				_ => continue,
+			};
+			let name = match name.strip_suffix(".json") {
+				Some(name) => name,
+				None => continue,
+			};
+			let spec_version = match crate::artifact::read_digest(&digest).and_then(|info| {
+				info["core_version"]
+					.as_str()
+					.and_then(crate::artifact::spec_version)
+			}) {
+				Some(spec_version) => spec_version,
+				None => {
+					warn!("`{}` has no spec version, skipping it", digest.display());
+
+					continue;
+				}
+			};
+
+			if spec_version >= current || enacted.contains(&spec_version) {
+				println!("keep  {}/{} ({})", runtime.name(), name, spec_version);
+
+				continue;
+			}
+
+			prunable += 1;
+
+			println!("prune {}/{} ({})", runtime.name(), name, spec_version);
+
+			if delete {
+				for path in files(&output_dir.join(runtime.name()), name) {
+					fs::remove_file(&path)
+						.with_context(|| format!("failed to remove `{}`", path.display()))?;
+				}
+			}
+		}
+
+		if delete {
+			for object in store::collect_garbage(&output_dir.join(runtime.name()).join("wasms"))? {
+				info!("Removed `{}`, no artifact points to it", object.display());
+			}
+		}
+	}
+
+	if delete {
+		if prunable != 0 {
+			crate::index::write(output_dir)?;
+			crate::checksums::write(output_dir)?;
+		}
+	} else if prunable != 0 {
+		info!(
+			"{} artifact(s) can be pruned, pass `--delete` to remove them",
+			prunable
+		);
+	}
+
+	Ok(())
+}
+
+/// The existing files of the artifact `name` of `chain_dir`: its wasm, its digest, their
+/// signatures, its uncompressed wasm, its metadata and its srtool digest.
+pub fn files(chain_dir: &Path, name: &str) -> Vec<PathBuf> {
+	let wasm = chain_dir
+		.join("wasms")
+		.join(format!("{}.compact.compressed.wasm", name));
+	let digest = chain_dir.join("digests").join(format!("{}.json", name));
+	let uncompressed = chain_dir
+		.join("wasms")
+		.join(format!("{}.compact.wasm", name));
+	// The metadata and the srtool digest are named after the digest too.
+	let siblings = crate::dir_entries(chain_dir)
+		.into_iter()
+		.filter(|dir| !dir.ends_with("wasms") && !dir.ends_with("digests"))
+		.flat_map(|dir| crate::dir_entries(&dir))
+		.filter(|path| path.file_stem().and_then(|stem| stem.to_str()) == Some(name));
+
+	[wasm, digest]
+		.into_iter()
+		.flat_map(|file| [signing::signature_path(&file), file])
+		.chain([uncompressed])
+		.chain(siblings)
+		.filter(|path| path.is_symlink() || path.exists())
+		.collect()
+}
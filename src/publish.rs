@@ -0,0 +1,188 @@
+//! Publishing the artifacts of a build out of the git history, to a GitHub release or a bucket.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::info;
+
+use crate::{
+	cli::{Destination, PublishArgs, PublishedBuild},
+	config::Config,
+	github,
+	registry::Registry,
+	s3, signing, AnyResult,
+};
+
+/// The artifacts of a build of a runtime at a target, found in the output directory.
+pub struct Build {
+	pub runtime: String,
+	pub target: String,
+	pub artifacts: Vec<Artifact>,
+	/// The checksums of the chain directory.
+	pub checksums: Vec<PathBuf>,
+}
+
+pub struct Artifact {
+	pub runtime_info: serde_json::Value,
+	pub wasm: PathBuf,
+	/// The wasm, the digest, their signatures and the uncompressed wasm.
+	pub files: Vec<PathBuf>,
+}
+
+impl Build {
+	/// Find the artifacts `published` stands for, whatever they are named after.
+	pub fn find(
+		output_dir: &Path,
+		registry: &Registry,
+		published: &PublishedBuild,
+	) -> AnyResult<Self> {
+		let runtime = crate::cli::single_runtime(registry, published.runtime.clone())?;
+		let chain_dir = output_dir.join(runtime.name());
+		let mut artifacts = Vec::new();
+
+		for digest in crate::dir_entries(&chain_dir.join("digests")) {
+			let runtime_info = match crate::artifact::read_digest(&digest) {
+				Some(runtime_info)
+					if runtime_info[crate::artifact::PROVENANCE]["target"]
+						== published.target.as_str() =>
+				{
+					runtime_info
+				}
+				_ => continue,
+			};
+			let name = digest.file_stem().unwrap_or_default().to_string_lossy();
+			let wasm = chain_dir
+				.join("wasms")
+				.join(format!("{}.compact.compressed.wasm", name));
+			let uncompressed = chain_dir
+				.join("wasms")
+				.join(format!("{}.compact.wasm", name));
+			let files = [wasm.clone(), digest]
+				.into_iter()
+				.flat_map(|file| [signing::signature_path(&file), file])
+				.chain([uncompressed])
+				.filter(|file| file.is_file())
+				.collect();
+
+			artifacts.push(Artifact {
+				runtime_info,
+				wasm,
+				files,
+			});
+		}
+
+		if artifacts.is_empty() {
+			anyhow::bail!(
+				"{} wasn't built at `{}` in `{}`",
+				runtime.name(),
+				published.target,
+				output_dir.display()
+			);
+		}
+
+		Ok(Self {
+			runtime: runtime.name().into(),
+			target: published.target.clone(),
+			artifacts,
+			checksums: ["SHA256SUMS", "BLAKE2SUMS"]
+				.into_iter()
+				.map(|name| chain_dir.join(name))
+				.filter(|file| file.is_file())
+				.collect(),
+		})
+	}
+
+	/// Every file of the build, the checksums last.
+	pub fn files(&self) -> Vec<PathBuf> {
+		self.artifacts
+			.iter()
+			.flat_map(|artifact| artifact.files.iter().cloned())
+			.chain(self.checksums.iter().cloned())
+			.collect()
+	}
+
+	/// The release notes: the spec version, commit and hashes of each artifact.
+	pub fn notes(&self) -> String {
+		let mut notes = format!(
+			"Tracing runtime of {} built from `{}`.\n",
+			self.runtime, self.target
+		);
+
+		for artifact in &self.artifacts {
+			let field = |name: &str| {
+				artifact.runtime_info[name]
+					.as_str()
+					.unwrap_or("unknown")
+					.to_owned()
+			};
+
+			notes.push_str(&format!(
+				"\n- `{}`\n  - commit: `{}`\n  - blake2_256: `{}`\n  - setCode hash: `{}`\n",
+				field("core_version"),
+				field("commit"),
+				field("blake2_256"),
+				field("set_code_hash"),
+			));
+		}
+
+		notes
+	}
+}
+
+pub fn run(
+	args: PublishArgs,
+	config: &Config,
+	registry: &Registry,
+	output_dir: &Path,
+) -> AnyResult<()> {
+	match args.destination {
+		Destination::Github(args) => {
+			let build = Build::find(output_dir, registry, &args.published)?;
+			let repository = match args.repository.or_else(|| config.github_repository.clone()) {
+				Some(repository) => repository,
+				None => {
+					let url = crate::runner::capture(
+						Path::new("."),
+						"git",
+						&["remote", "get-url", "origin"],
+					)
+					.context("no `--repository` given and no `origin` remote to default to")?;
+
+					github::repository(&url)
+						.with_context(|| format!("the `origin` remote `{}` isn't on GitHub", url))?
+						.to_owned()
+				}
+			};
+			let tag = args
+				.tag
+				.unwrap_or_else(|| format!("{}-{}", build.runtime, build.target));
+			let files = build.files();
+			let url =
+				github::publish_release(&repository, &tag, &build.notes(), &files, &args.token)?;
+
+			info!("Published {} file(s) to {}", files.len(), url);
+
+			Ok(())
+		}
+		Destination::S3(args) => {
+			let build = Build::find(output_dir, registry, &args.published)?;
+			let bucket = s3::Bucket {
+				endpoint: args
+					.endpoint
+					.unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", args.region)),
+				region: args.region,
+				name: args.bucket,
+				access_key_id: args.access_key_id,
+				secret_access_key: args.secret_access_key,
+			};
+			let uploaded = s3::publish(&bucket, &args.prefix, &build)?;
+
+			info!(
+				"Published {} file(s) to {}/{}/{}",
+				uploaded, bucket.endpoint, bucket.name, args.prefix
+			);
+
+			Ok(())
+		}
+	}
+}
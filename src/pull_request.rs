@@ -0,0 +1,136 @@
+//! `--publish-pr`: committing the new artifacts onto a branch of the repository holding the output
+//! directory and opening a pull request of it, instead of copying, committing and pushing by hand.
+
+use std::{env, fs};
+
+use anyhow::Context;
+
+use crate::{github, index, settings::Settings, signing, AnyResult, Artifact};
+
+/// Commit the files of `artifacts` and the index onto a branch, push it and open a pull request of
+/// it into the current branch, returning its URL.
+///
+/// The branch is named after the artifacts, publishing them again resets it.
+///
+/// The repository is `repository` if given, the `origin` remote otherwise.
+pub fn open(
+	artifacts: &[&Artifact],
+	repository: Option<&str>,
+	settings: &Settings,
+) -> AnyResult<String> {
+	let token = env::var("GITHUB_TOKEN").context("`--publish-pr` needs a `GITHUB_TOKEN`")?;
+	let output_dir = settings.output_dir.as_path();
+	let git = |args: &[&str]| settings.capture(output_dir, "git", args);
+	let repository = match repository {
+		Some(repository) => repository.to_owned(),
+		None => {
+			let url = git(&["remote", "get-url", "origin"])?;
+
+			github::repository(&url)
+				.with_context(|| format!("the `origin` remote `{}` isn't on GitHub", url))?
+				.to_owned()
+		}
+	};
+	let labels = artifacts
+		.iter()
+		.map(|artifact| format!("{}@{}", artifact.runtime, artifact.target))
+		.collect::<Vec<_>>();
+	let branch = format!(
+		"runtime-overrides/{}",
+		labels.join("-").replace(
+			|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-',
+			"-"
+		)
+	);
+	let base = git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+
+	if base == "HEAD" {
+		anyhow::bail!(
+			"`{}` is a detached checkout, `--publish-pr` needs the branch to open the pull request into",
+			output_dir.display()
+		);
+	}
+
+	let mut files = vec![output_dir.join(index::FILE_NAME)];
+
+	for artifact in artifacts {
+		let chain_dir = output_dir.join(&artifact.runtime);
+
+		// The object of `--content-addressed` the wasm links to.
+		if let Ok(object) = fs::read_link(&artifact.wasm) {
+			if let Some(wasms_dir) = artifact.wasm.parent() {
+				files.push(wasms_dir.join(object));
+			}
+		}
+
+		files.extend(
+			[&artifact.wasm, &artifact.digest]
+				.into_iter()
+				.flat_map(|file| [file.clone(), signing::signature_path(file)])
+				.chain(["SHA256SUMS", "BLAKE2SUMS"].map(|name| chain_dir.join(name))),
+		);
+	}
+	files.retain(|file| file.is_file());
+	files.sort();
+	files.dedup();
+
+	// Git runs in the output directory, the paths are relative to it.
+	let files = files
+		.iter()
+		.map(|file| crate::path_arg(file.strip_prefix(output_dir).unwrap_or(file)))
+		.collect::<Vec<_>>();
+	let title = format!("Add the tracing runtimes of {}", labels.join(", "));
+
+	git(&["checkout", "-B", &branch])?;
+
+	let committed = (|| {
+		git(&["add", "--"]
+			.into_iter()
+			.chain(files.iter().map(String::as_str))
+			.collect::<Vec<_>>())?;
+		git(&["commit", "-m", &title])?;
+		git(&["push", "--force", "--set-upstream", "origin", &branch])
+	})();
+
+	// Back to where the user was, the artifacts stay in the working tree either way.
+	git(&["checkout", &base])?;
+	committed.with_context(|| format!("failed to push the artifacts to {}", branch))?;
+
+	github::open_pull_request(
+		&repository,
+		&branch,
+		&base,
+		&title,
+		&description(artifacts),
+		&token,
+	)
+}
+
+/// A table of the chain, target, commit, spec version and hashes of each artifact.
+fn description(artifacts: &[&Artifact]) -> String {
+	let mut description = String::from(
+		"| Chain | Target | Commit | spec_version | blake2_256 | setCode hash |\n|---|---|---|---|---|---|\n",
+	);
+
+	for artifact in artifacts {
+		description.push_str(&format!(
+			"| {} | `{}` | `{}` | {} | `{}` | `{}` |\n",
+			artifact.runtime,
+			artifact.target,
+			artifact.commit,
+			artifact
+				.spec_version
+				.map_or_else(|| "unknown".into(), |spec_version| spec_version.to_string()),
+			artifact.hashes.blake2_256,
+			artifact
+				.hashes
+				.set_code_hash
+				.as_deref()
+				.unwrap_or("unknown"),
+		));
+	}
+
+	description.push_str("\nOpened by `runtime-overrides --publish-pr`.\n");
+
+	description
+}
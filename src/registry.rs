@@ -0,0 +1,175 @@
+//! The runtimes this tool knows how to build, described by `runtimes.toml`.
+
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::ArgEnum;
+use serde::Deserialize;
+
+use crate::AnyResult;
+
+/// The registry looked up in the current directory, the built-in copy is used if it is missing.
+pub const FILE_NAME: &str = "runtimes.toml";
+
+const BUILT_IN: &str = include_str!("../runtimes.toml");
+
+/// How the runtimes are spread across the Darwinia repositories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum)]
+pub enum Layout {
+	/// Pangoro and Pangolin live in `darwinia-common`.
+	Legacy,
+	/// Darwinia 2.0, every solo chain runtime lives in the `darwinia` monorepo.
+	Monorepo,
+}
+impl Layout {
+	/// Darwinia 2.0 releases start from `v6.0.0`, anything else is assumed to be legacy.
+	pub fn detect(target: &str) -> Self {
+		let major = target
+			.strip_prefix('v')
+			.and_then(|version| version.split('.').next())
+			.and_then(|major| major.parse::<u32>().ok());
+
+		match major {
+			Some(major) if major >= 6 => Self::Monorepo,
+			_ => Self::Legacy,
+		}
+	}
+}
+
+/// Where a runtime crate lives.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Location {
+	repository: String,
+	url: Option<String>,
+	path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Runtime {
+	#[serde(skip)]
+	name: String,
+	repository: String,
+	url: Option<String>,
+	path: String,
+	package: Option<String>,
+	features: Option<Vec<String>>,
+	monorepo: Option<Location>,
+	rpc: Option<String>,
+}
+impl Runtime {
+	/// The lowercase name used on the command line, e.g. `crab-parachain`.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn github(&self, layout: Layout) -> String {
+		match self.location(layout) {
+			(_, Some(url), _) => url.into(),
+			(repository, None, _) => format!("https://github.com/darwinia-network/{}", repository),
+		}
+	}
+
+	pub fn repository(&self, layout: Layout) -> &str {
+		self.location(layout).0
+	}
+
+	pub fn path(&self, layout: Layout) -> &str {
+		self.location(layout).2
+	}
+
+	pub fn package(&self) -> String {
+		self.package
+			.clone()
+			.unwrap_or_else(|| format!("{}-runtime", self.name))
+	}
+
+	pub fn features(&self) -> Option<&[String]> {
+		self.features.as_deref()
+	}
+
+	/// RPC endpoint of the live chain.
+	pub fn rpc(&self) -> Option<&str> {
+		self.rpc.as_deref()
+	}
+
+	fn location(&self, layout: Layout) -> (&str, Option<&str>, &str) {
+		match (layout, &self.monorepo) {
+			(Layout::Monorepo, Some(l)) => {
+				(l.repository.as_str(), l.url.as_deref(), l.path.as_str())
+			}
+			_ => (
+				self.repository.as_str(),
+				self.url.as_deref(),
+				self.path.as_str(),
+			),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Registry(BTreeMap<String, Runtime>);
+impl Registry {
+	/// Load the registry at `path` if given, otherwise `runtimes.toml` or the built-in one.
+	pub fn load(path: Option<&Path>) -> AnyResult<Self> {
+		let path = path
+			.map(Path::to_path_buf)
+			.or_else(|| Some(PathBuf::from(FILE_NAME)).filter(|path| path.is_file()));
+
+		match path {
+			Some(path) => Self::parse(
+				&fs::read_to_string(&path)
+					.with_context(|| format!("failed to read `{}`", path.display()))?,
+			)
+			.with_context(|| format!("failed to parse `{}`", path.display())),
+			None => Self::parse(BUILT_IN).context("failed to parse the built-in registry"),
+		}
+	}
+
+	fn parse(content: &str) -> AnyResult<Self> {
+		let runtimes = toml::from_str::<BTreeMap<String, Runtime>>(content)?
+			.into_iter()
+			.map(|(name, mut runtime)| {
+				let name = name.to_ascii_lowercase();
+
+				runtime.name = name.clone();
+
+				(name, runtime)
+			})
+			.collect();
+
+		Ok(Self(runtimes))
+	}
+
+	pub fn runtimes(&self) -> impl Iterator<Item = &Runtime> {
+		self.0.values()
+	}
+
+	/// Look the runtimes up by name (non case sensitive), `all` selects every one of them.
+	pub fn resolve(&self, names: &[String]) -> AnyResult<Vec<Runtime>> {
+		let mut runtimes = Vec::new();
+
+		for name in names {
+			let name = name.to_ascii_lowercase();
+
+			if name == "all" {
+				runtimes.extend(self.0.values().cloned());
+			} else if let Some(runtime) = self.0.get(&name) {
+				runtimes.push(runtime.clone());
+			} else {
+				anyhow::bail!(
+					"unknown runtime `{}`, expected `all` or one of: {}",
+					name,
+					self.0.keys().cloned().collect::<Vec<_>>().join(", ")
+				);
+			}
+		}
+
+		Ok(runtimes)
+	}
+}
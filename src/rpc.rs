@@ -0,0 +1,165 @@
+//! A minimal JSON-RPC client of the Substrate nodes.
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::AnyResult;
+
+/// The storage key of the runtime code, `:code` hex encoded.
+const CODE_KEY: &str = "0x3a636f6465";
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+	result: Option<T>,
+	error: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeVersion {
+	pub spec_name: String,
+	pub spec_version: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+	number: String,
+}
+
+#[derive(Debug)]
+pub struct Client {
+	url: String,
+}
+impl Client {
+	/// WebSocket endpoints are queried over HTTP, Substrate nodes serve both on the same port.
+	pub fn new(url: &str) -> Self {
+		let url = match url.split_once("://") {
+			Some(("ws", rest)) => format!("http://{}", rest),
+			Some(("wss", rest)) => format!("https://{}", rest),
+			_ => url.into(),
+		};
+
+		Self { url }
+	}
+
+	pub fn call<T>(&self, method: &str, params: Value) -> AnyResult<T>
+	where
+		T: DeserializeOwned,
+	{
+		let response = ureq::post(&self.url)
+			.send_json(json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"method": method,
+				"params": params,
+			}))
+			.with_context(|| format!("failed to call `{}` on {}", method, self.url))?
+			.into_json::<Response<T>>()
+			.with_context(|| {
+				format!("failed to parse the `{}` response of {}", method, self.url)
+			})?;
+
+		match response {
+			Response {
+				result: Some(result),
+				..
+			} => Ok(result),
+			Response { error, .. } => anyhow::bail!(
+				"`{}` failed on {}: {}",
+				method,
+				self.url,
+				error.unwrap_or(Value::Null)
+			),
+		}
+	}
+
+	/// The hash of the block `number`, none if it isn't there yet.
+	pub fn block_hash(&self, number: u64) -> AnyResult<Option<String>> {
+		self.call("chain_getBlockHash", json!([number]))
+	}
+
+	/// The number of the best block.
+	pub fn best_number(&self) -> AnyResult<u64> {
+		let header = self.call::<Header>("chain_getHeader", json!([]))?;
+
+		u64::from_str_radix(header.number.trim_start_matches("0x"), 16)
+			.with_context(|| format!("invalid block number `{}`", header.number))
+	}
+
+	/// The version of the runtime at the block `hash`, the best one by default.
+	pub fn runtime_version(&self, hash: Option<&str>) -> AnyResult<RuntimeVersion> {
+		self.call("state_getRuntimeVersion", json!([hash]))
+	}
+
+	pub fn spec_version(&self, hash: Option<&str>) -> AnyResult<u32> {
+		Ok(self.runtime_version(hash)?.spec_version)
+	}
+
+	/// The runtime code in storage at the block `hash`, the best one by default.
+	pub fn code(&self, hash: Option<&str>) -> AnyResult<Vec<u8>> {
+		let code = self
+			.call::<Option<String>>("state_getStorage", json!([CODE_KEY, hash]))?
+			.context("the `:code` storage item is empty")?;
+
+		decode_hex(&code).with_context(|| format!("invalid `:code` returned by {}", self.url))
+	}
+
+	/// Every spec version the chain ever ran, found by bisecting the blocks between the upgrades.
+	///
+	/// The states of the old blocks are needed, which only archive nodes keep.
+	pub fn spec_versions(&self) -> AnyResult<Vec<u32>> {
+		let best = self.best_number()?;
+		let spec_version_at = |number| -> AnyResult<u32> {
+			let hash = self
+				.block_hash(number)?
+				.with_context(|| format!("block {} is missing", number))?;
+
+			self.spec_version(Some(&hash))
+		};
+		let latest = spec_version_at(best)?;
+		let mut versions = Vec::new();
+		let mut start = 0;
+
+		loop {
+			let version = spec_version_at(start)?;
+
+			versions.push(version);
+
+			if version >= latest {
+				return Ok(versions);
+			}
+
+			// The first block past `start` running a newer runtime.
+			let (mut low, mut high) = (start, best);
+
+			while high - low > 1 {
+				let middle = low + (high - low) / 2;
+
+				if spec_version_at(middle)? > version {
+					high = middle;
+				} else {
+					low = middle;
+				}
+			}
+
+			start = high;
+		}
+	}
+}
+
+pub fn decode_hex(hex: &str) -> AnyResult<Vec<u8>> {
+	let hex = hex.trim_start_matches("0x");
+
+	if hex.len() % 2 != 0 {
+		anyhow::bail!("odd number of hex digits");
+	}
+
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| {
+			u8::from_str_radix(&hex[i..i + 2], 16)
+				.with_context(|| format!("invalid hex byte `{}`", &hex[i..i + 2]))
+		})
+		.collect()
+}
@@ -0,0 +1,336 @@
+//! How the builds run git, cargo and the other programs, replaceable to test the pipeline without
+//! them.
+
+use std::{
+	cell::RefCell,
+	collections::VecDeque,
+	error::Error,
+	fmt::{self, Debug, Display, Formatter},
+	fs::{self, File},
+	io::{self, BufRead, BufReader, Read, Write},
+	os::unix::process::CommandExt,
+	path::{Path, PathBuf},
+	process::{Child, Command, ExitStatus, Stdio},
+	sync::{Arc, Mutex},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use log::{debug, error, info, warn};
+
+use crate::{create_dir_unchecked, AnyResult};
+
+/// Runs the programs a build is made of.
+pub trait CommandRunner: Debug + Send + Sync {
+	/// Run `program` in `dir` to completion, failing if it doesn't succeed or if it's still running
+	/// after `timeout`.
+	///
+	/// `label`, e.g. `crab@main`, tells which build its output belongs to.
+	fn run(
+		&self,
+		dir: &Path,
+		label: &str,
+		program: &str,
+		args: &[&str],
+		envs: &[(String, String)],
+		timeout: Option<Duration>,
+	) -> AnyResult<()>;
+
+	/// Run `program` in `dir` to completion and return its trimmed stdout.
+	fn capture(&self, dir: &Path, program: &str, args: &[&str]) -> AnyResult<String>;
+}
+
+/// Spawns the programs for real.
+#[derive(Debug, Default)]
+pub struct SystemRunner;
+impl CommandRunner for SystemRunner {
+	fn run(
+		&self,
+		dir: &Path,
+		label: &str,
+		program: &str,
+		args: &[&str],
+		envs: &[(String, String)],
+		timeout: Option<Duration>,
+	) -> AnyResult<()> {
+		run(dir, label, program, args, envs, timeout)
+	}
+
+	fn capture(&self, dir: &Path, program: &str, args: &[&str]) -> AnyResult<String> {
+		capture(dir, program, args)
+	}
+}
+
+/// How many of the last lines of its stderr a failed command shows.
+const STDERR_TAIL: usize = 20;
+
+/// A spawned program exited unsuccessfully.
+#[derive(Debug)]
+pub struct CommandFailed {
+	pub command: String,
+	pub status: ExitStatus,
+}
+impl CommandFailed {
+	/// Reuse the program's exit code, falling back to `1` if it was killed by a signal.
+	pub fn exit_code(&self) -> i32 {
+		self.status.code().filter(|code| *code != 0).unwrap_or(1)
+	}
+}
+impl Display for CommandFailed {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "`{}` failed with {}", self.command, self.status)
+	}
+}
+impl Error for CommandFailed {}
+
+/// A spawned program was killed for running longer than `--timeout`.
+#[derive(Debug)]
+pub struct TimedOut {
+	command: String,
+	timeout: Duration,
+}
+impl TimedOut {
+	/// The exit code of `timeout(1)`.
+	pub const EXIT_CODE: i32 = 124;
+}
+impl Display for TimedOut {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"`{}` timed out after {}s",
+			self.command,
+			self.timeout.as_secs()
+		)
+	}
+}
+impl Error for TimedOut {}
+
+/// Run `program` to completion and return its trimmed stdout.
+pub fn capture(dir: &Path, program: &str, args: &[&str]) -> AnyResult<String> {
+	let command = format!("{} {}", program, args.join(" "));
+	let output = Command::new(program)
+		.args(args)
+		.current_dir(dir)
+		.stderr(Stdio::inherit())
+		.output()
+		.with_context(|| format!("failed to spawn `{}`", command))?;
+
+	if !output.status.success() {
+		return Err(CommandFailed {
+			command,
+			status: output.status,
+		}
+		.into());
+	}
+
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Run `program` to completion, logging its output as it arrives, and kill it after `timeout`.
+fn run(
+	dir: &Path,
+	label: &str,
+	program: &str,
+	args: &[&str],
+	envs: &[(String, String)],
+	timeout: Option<Duration>,
+) -> AnyResult<()> {
+	let command = format!("{} {}", program, args.join(" "));
+	let mut cmd = Command::new(program);
+
+	cmd.args(args)
+		.envs(envs.iter().map(|(key, value)| (key, value)))
+		.current_dir(dir)
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped());
+
+	// Its own process group, so that the rustc and build script processes get killed with it.
+	if timeout.is_some() {
+		unsafe {
+			cmd.pre_exec(|| {
+				if libc::setpgid(0, 0) == 0 {
+					Ok(())
+				} else {
+					Err(io::Error::last_os_error())
+				}
+			});
+		}
+	}
+
+	let mut child = cmd
+		.spawn()
+		.with_context(|| format!("failed to spawn `{}`", command))?;
+	let prefix = format!("[{} {}]", program, label);
+	let logs = BUILD_LOGS.with(|logs| logs.borrow().clone());
+
+	debug!("{} running `{}` in `{}`", prefix, command, dir.display());
+
+	for log in &logs {
+		log.write_line(&format!("$ (cd {} && {})", dir.display(), command));
+	}
+
+	let stdout = forward(child.stdout.take(), prefix.clone(), logs.clone(), 0);
+	let stderr = forward(child.stderr.take(), prefix, logs, STDERR_TAIL);
+	let status = match timeout {
+		Some(timeout) => match wait_timeout(&mut child, timeout)? {
+			Some(status) => status,
+			None => {
+				warn!(
+					"[{}] `{}` is still running after {}s, killing it",
+					label,
+					command,
+					timeout.as_secs()
+				);
+				kill_group(&mut child)?;
+
+				let _ = stdout.join();
+				let _ = stderr.join();
+
+				return Err(TimedOut { command, timeout }.into());
+			}
+		},
+		None => child.wait()?,
+	};
+
+	// Drain the remaining output before reporting the result.
+	let _ = stdout.join();
+	let tail = stderr.join().unwrap_or_default();
+
+	if !status.success() {
+		// Its output isn't shown with `--quiet`, or may have scrolled away, tell what went wrong anyway.
+		if !tail.is_empty() {
+			error!(
+				"[{}] `{}` failed, its stderr ended with:\n{}",
+				label,
+				command,
+				Vec::from(tail).join("\n")
+			);
+		}
+
+		return Err(CommandFailed { command, status }.into());
+	}
+
+	Ok(())
+}
+
+fn wait_timeout(child: &mut Child, timeout: Duration) -> AnyResult<Option<ExitStatus>> {
+	let start = Instant::now();
+
+	loop {
+		if let Some(status) = child.try_wait()? {
+			return Ok(Some(status));
+		}
+		if start.elapsed() >= timeout {
+			return Ok(None);
+		}
+
+		thread::sleep(Duration::from_millis(100));
+	}
+}
+
+/// `SIGTERM` the process group of `child`, `SIGKILL` it if it's still there a few seconds later.
+fn kill_group(child: &mut Child) -> AnyResult<()> {
+	let group = -(child.id() as libc::pid_t);
+
+	unsafe {
+		libc::kill(group, libc::SIGTERM);
+	}
+
+	if wait_timeout(child, Duration::from_secs(10))?.is_none() {
+		unsafe {
+			libc::kill(group, libc::SIGKILL);
+		}
+
+		child.wait()?;
+	}
+
+	Ok(())
+}
+
+/// Log every line of `reader` as soon as it arrives, tagged with `prefix`, and copy it to `logs`,
+/// returning the last `tail` of them.
+fn forward<R>(
+	reader: Option<R>,
+	prefix: String,
+	logs: Vec<BuildLog>,
+	tail: usize,
+) -> JoinHandle<VecDeque<String>>
+where
+	R: 'static + Read + Send,
+{
+	thread::spawn(move || {
+		let mut last = VecDeque::with_capacity(tail);
+		let reader = match reader {
+			Some(reader) => reader,
+			None => return last,
+		};
+
+		for line in BufReader::new(reader).lines().flatten() {
+			for log in &logs {
+				log.write_line(&line);
+			}
+
+			info!("{} {}", prefix, line);
+
+			if tail > 0 {
+				if last.len() == tail {
+					last.pop_front();
+				}
+
+				last.push_back(line);
+			}
+		}
+
+		last
+	})
+}
+
+thread_local! {
+	/// The logs the output of the commands run by this thread is copied to.
+	static BUILD_LOGS: RefCell<Vec<BuildLog>> = RefCell::new(Vec::new());
+}
+
+/// The complete output of the commands run for an artifact.
+#[derive(Clone, Debug)]
+pub struct BuildLog {
+	pub path: PathBuf,
+	file: Arc<Mutex<File>>,
+}
+impl BuildLog {
+	pub fn create(path: PathBuf) -> AnyResult<Self> {
+		create_dir_unchecked(path.parent().unwrap_or_else(|| Path::new(".")))?;
+
+		let file = File::create(&path)
+			.with_context(|| format!("failed to create `{}`", path.display()))?;
+
+		Ok(Self {
+			path,
+			file: Arc::new(Mutex::new(file)),
+		})
+	}
+
+	/// A log failing to be written isn't worth failing the build.
+	fn write_line(&self, line: &str) {
+		if let Ok(mut file) = self.file.lock() {
+			let _ = writeln!(file, "{}", line);
+		}
+	}
+
+	pub fn contains(&self, text: &str) -> bool {
+		fs::read_to_string(&self.path).map_or(false, |content| content.contains(text))
+	}
+}
+
+/// Copy the output of the commands `f` runs to `logs`.
+pub fn with_logs<T, F>(logs: &[BuildLog], f: F) -> T
+where
+	F: FnOnce() -> T,
+{
+	let previous = BUILD_LOGS.with(|current| current.replace(logs.to_vec()));
+	let result = f();
+
+	BUILD_LOGS.with(|current| current.replace(previous));
+
+	result
+}
@@ -0,0 +1,30 @@
+//! The JSON Schema of the digests, `schemas/digest-v<VERSION>.json`, the structure the downstream
+//! tooling can rely on.
+
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+/// Recorded as the `schema_version` of each digest.
+pub const VERSION: u64 = 1;
+
+const SCHEMA: &str = include_str!("../schemas/digest-v1.json");
+
+/// Check `digest` against the schema, one line per violation.
+pub fn validate(digest: &Value) -> Result<(), String> {
+	if let Some(version) = digest["schema_version"].as_u64().filter(|v| *v > VERSION) {
+		return Err(format!(
+			"schema version {} is newer than this tool's, {}",
+			version, VERSION
+		));
+	}
+
+	let schema = serde_json::from_str(SCHEMA).map_err(|e| format!("invalid schema: {}", e))?;
+	let schema = JSONSchema::compile(&schema).map_err(|e| format!("invalid schema: {}", e))?;
+
+	schema.validate(digest).map_err(|errors| {
+		errors
+			.map(|e| format!("{}: {}", e.instance_path, e))
+			.collect::<Vec<_>>()
+			.join("\n")
+	})
+}
@@ -0,0 +1,596 @@
+//! A server building the runtimes it's asked for: with `--webhook`, the tags GitHub reports pushed
+//! or released, with `--api`, the builds posted to `/builds`.
+
+use std::{
+	fs::{self, File},
+	io::{self, Read},
+	path::{Component, Path, PathBuf},
+	sync::{Arc, Condvar, Mutex},
+	thread,
+};
+
+use anyhow::Context;
+
+use clap::ArgEnum;
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tiny_http::{Header, Method, Request, Response, ResponseBox, Server};
+
+use crate::{
+	cli::ServeArgs,
+	config::Config,
+	metrics,
+	progress::Progress,
+	registry::{Layout, Registry},
+	AnyResult, Artifact, SystemRunner,
+};
+
+/// What a build is at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum State {
+	Queued,
+	Running,
+	Succeeded,
+	Failed,
+}
+
+/// A build asked for, as `GET /builds/<ID>` reports it.
+#[derive(Debug, Serialize, Deserialize)]
+struct Build {
+	id: usize,
+	runtime: String,
+	target: String,
+	state: State,
+	artifacts: Vec<serde_json::Value>,
+	errors: Vec<String>,
+}
+
+/// The builds asked for, saved to `path` on every change to survive restarts.
+struct Queue {
+	path: PathBuf,
+	/// The build of id `n` at `n - 1`.
+	builds: Mutex<Vec<Build>>,
+	queued: Condvar,
+}
+impl Queue {
+	/// Load the builds of the previous runs, queueing the interrupted ones again.
+	fn load(path: PathBuf) -> AnyResult<Self> {
+		let mut builds = match fs::read(&path) {
+			Ok(content) => serde_json::from_slice::<Vec<Build>>(&content)
+				.with_context(|| format!("failed to parse `{}`", path.display()))?,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+			Err(e) => {
+				return Err(e).with_context(|| format!("failed to read `{}`", path.display()))
+			}
+		};
+
+		for build in &mut builds {
+			if build.state == State::Running {
+				build.state = State::Queued;
+			}
+		}
+
+		let queued = builds
+			.iter()
+			.filter(|build| build.state == State::Queued)
+			.count();
+
+		if queued != 0 {
+			info!("Resuming {} queued build(s)", queued);
+		}
+
+		Ok(Self {
+			path,
+			builds: Mutex::new(builds),
+			queued: Condvar::new(),
+		})
+	}
+
+	/// Queue `runtime` at `target` unless it's already queued or running, returning the id of the
+	/// build and whether it's a new one.
+	fn push(&self, runtime: &str, target: &str) -> (usize, bool) {
+		let mut builds = self.builds.lock().unwrap();
+
+		if let Some(build) = builds.iter().find(|build| {
+			matches!(build.state, State::Queued | State::Running)
+				&& build.runtime == runtime
+				&& build.target == target
+		}) {
+			return (build.id, false);
+		}
+
+		let id = builds.len() + 1;
+
+		builds.push(Build {
+			id,
+			runtime: runtime.into(),
+			target: target.into(),
+			state: State::Queued,
+			artifacts: Vec::new(),
+			errors: Vec::new(),
+		});
+
+		self.save(&builds);
+		self.queued.notify_one();
+
+		(id, true)
+	}
+
+	/// Wait for a queued build, the oldest one, and mark it running.
+	fn next(&self) -> (usize, String, String) {
+		let mut builds = self.builds.lock().unwrap();
+
+		loop {
+			if let Some(build) = builds.iter_mut().find(|build| build.state == State::Queued) {
+				build.state = State::Running;
+
+				let next = (build.id, build.runtime.clone(), build.target.clone());
+
+				self.save(&builds);
+
+				return next;
+			}
+
+			builds = self.queued.wait(builds).unwrap();
+		}
+	}
+
+	fn finish(&self, id: usize, artifacts: Vec<Artifact>, errors: Vec<anyhow::Error>) {
+		let mut builds = self.builds.lock().unwrap();
+		let build = &mut builds[id - 1];
+
+		build.state = if errors.is_empty() {
+			State::Succeeded
+		} else {
+			State::Failed
+		};
+		build.artifacts = artifacts
+			.iter()
+			.filter_map(|artifact| serde_json::to_value(artifact).ok())
+			.collect();
+		build.errors = errors.iter().map(|e| format!("{:#}", e)).collect();
+
+		self.save(&builds);
+	}
+
+	fn save(&self, builds: &[Build]) {
+		let temporary_path = crate::temporary_path(&self.path);
+		let result = serde_json::to_vec_pretty(builds)
+			.map_err(anyhow::Error::from)
+			.and_then(|content| Ok(fs::write(&temporary_path, content)?))
+			.and_then(|()| Ok(fs::rename(&temporary_path, &self.path)?));
+
+		if let Err(e) = result {
+			warn!(
+				"Failed to save the queue to `{}`: {:#}",
+				self.path.display(),
+				e
+			);
+		}
+	}
+}
+
+/// Serve on `--listen` until killed, `--workers` builds running at the same time.
+pub fn run(
+	serve: ServeArgs,
+	config: Config,
+	registry: &Registry,
+	build_dir: PathBuf,
+	output_dir: PathBuf,
+	progress: Progress,
+) -> AnyResult<()> {
+	let args = serve.build;
+
+	if args.target.is_some() {
+		anyhow::bail!("`serve` builds the targets it's asked for, it takes no `--target`");
+	}
+	if !serve.webhook && !serve.api {
+		anyhow::bail!("nothing to serve, pass `--webhook`, `--api` or both");
+	}
+
+	let runtimes = registry.resolve(&args.runtime)?;
+	let webhook = serve
+		.webhook_secret
+		.filter(|_| serve.webhook)
+		.map(|secret| Webhook {
+			secret,
+			routes: runtimes
+				.iter()
+				.flat_map(|runtime| {
+					let repo_url = args.repo_url.clone().or_else(|| {
+						config
+							.runtimes
+							.get(runtime.name())
+							.and_then(|r| r.repo_url.clone())
+					});
+
+					Layout::value_variants().iter().map(move |layout| {
+						(
+							runtime.name().to_owned(),
+							*layout,
+							repo_url.clone().unwrap_or_else(|| runtime.github(*layout)),
+						)
+					})
+				})
+				.collect(),
+		});
+	let api = serve.api.then(|| Api {
+		token: serve.api_token,
+		runtimes: runtimes
+			.iter()
+			.map(|runtime| runtime.name().into())
+			.collect(),
+		output_dir: output_dir.clone(),
+	});
+
+	fs::create_dir_all(&build_dir)
+		.with_context(|| format!("failed to create `{}`", build_dir.display()))?;
+
+	let queue = Arc::new(Queue::load(build_dir.join("queue.json"))?);
+	let registry = Arc::new(registry.clone());
+
+	// Each worker needs a build directory of its own, they are locked while building.
+	for worker in 1..=serve.workers.max(1) {
+		let queue = queue.clone();
+		let registry = registry.clone();
+		let args = args.clone();
+		let config = config.clone();
+		let build_dir = if serve.workers > 1 {
+			build_dir.join(format!("worker-{}", worker))
+		} else {
+			build_dir.clone()
+		};
+		let output_dir = output_dir.clone();
+		let progress = progress.clone();
+
+		thread::spawn(move || loop {
+			let (id, runtime, target) = queue.next();
+
+			info!("Building {} at {} (#{})", runtime, target, id);
+
+			let mut args = args.clone();
+
+			args.runtime = vec![runtime];
+			args.target = Some(vec![target]);
+
+			let (artifacts, errors) = match crate::pipeline::build_artifacts(
+				args,
+				config.clone(),
+				&registry,
+				build_dir.clone(),
+				output_dir.clone(),
+				progress.clone(),
+				Arc::new(SystemRunner),
+			) {
+				Ok(result) => result,
+				Err(e) => (Vec::new(), vec![e]),
+			};
+
+			errors.iter().for_each(|e| error!("{:?}", e));
+
+			queue.finish(id, artifacts, errors);
+		});
+	}
+
+	let server = Server::http(&serve.listen)
+		.map_err(|e| anyhow::anyhow!("failed to listen on {}: {}", serve.listen, e))?;
+
+	info!("Listening on http://{}", serve.listen);
+
+	for mut request in server.incoming_requests() {
+		let method = request.method().clone();
+		let path = request
+			.url()
+			.split('?')
+			.next()
+			.unwrap_or_default()
+			.to_owned();
+		let response = match (method, path.as_str(), &webhook, &api) {
+			(Method::Post, "/webhook", Some(webhook), _) => {
+				text(webhook.handle(&mut request, &queue))
+			}
+			(Method::Post, "/builds", _, Some(api)) => api.create(&mut request, &queue),
+			(Method::Get, "/metrics", _, _) => metrics::response().boxed(),
+			(Method::Get, path, _, Some(api)) if path.starts_with("/builds/") => {
+				api.status(&request, &path["/builds/".len()..], &queue)
+			}
+			(Method::Get, path, _, Some(api)) if path.starts_with("/artifacts/") => {
+				api.artifact(&request, &path["/artifacts/".len()..])
+			}
+			_ => text((404, "not found".into())),
+		};
+
+		if let Err(e) = request.respond(response) {
+			warn!("Failed to respond: {}", e);
+		}
+	}
+
+	Ok(())
+}
+
+/// The GitHub webhook endpoint.
+struct Webhook {
+	secret: String,
+	/// The runtimes to build, each with the repository holding it in each layout.
+	routes: Vec<(String, Layout, String)>,
+}
+impl Webhook {
+	/// Queue the builds of the tag `request` reports, returning the status and the body of the
+	/// response.
+	fn handle(&self, request: &mut Request, queue: &Queue) -> (u16, String) {
+		let mut body = Vec::new();
+
+		if let Err(e) = request.as_reader().read_to_end(&mut body) {
+			return (400, format!("failed to read the payload: {}", e));
+		}
+
+		if !header(request, "X-Hub-Signature-256")
+			.map_or(false, |signature| self.verify(&body, signature))
+		{
+			return (401, "invalid signature".into());
+		}
+
+		let event = header(request, "X-GitHub-Event").unwrap_or_default();
+		let payload = match serde_json::from_slice::<serde_json::Value>(&body) {
+			Ok(payload) => payload,
+			Err(e) => return (400, format!("invalid payload: {}", e)),
+		};
+		let tag = match tag_of(event, &payload) {
+			Some(tag) => tag,
+			None => return (200, format!("ignored `{}` event", event)),
+		};
+		let repository = payload["repository"]["html_url"]
+			.as_str()
+			.unwrap_or_default();
+		let mut runtimes = self
+			.routes
+			.iter()
+			.filter(|(_, layout, url)| {
+				*layout == Layout::detect(tag) && same_repository(url, repository)
+			})
+			.map(|(runtime, _, _)| runtime.as_str())
+			.collect::<Vec<_>>();
+
+		runtimes.dedup();
+
+		if runtimes.is_empty() {
+			return (
+				200,
+				format!("no runtime is built from {} at {}", repository, tag),
+			);
+		}
+
+		let ids = runtimes
+			.iter()
+			.map(|runtime| format!("#{}", queue.push(runtime, tag).0))
+			.collect::<Vec<_>>();
+
+		(
+			202,
+			format!(
+				"queued {} at {} ({})",
+				runtimes.join(", "),
+				tag,
+				ids.join(", ")
+			),
+		)
+	}
+
+	/// Whether `signature`, the `X-Hub-Signature-256` header, is the HMAC of `body` with the
+	/// secret.
+	fn verify(&self, body: &[u8], signature: &str) -> bool {
+		let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+			.expect("HMAC takes keys of any length");
+
+		mac.update(body);
+
+		let expected = format!("sha256={}", crate::to_hex(&mac.finalize().into_bytes()));
+
+		constant_time_eq(&expected, signature)
+	}
+}
+
+/// Whether `a` is `b`, compared in constant time not to tell how much of a forged secret is right.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+	a.len() == b.len()
+		&& a.bytes()
+			.zip(b.bytes())
+			.fold(0, |difference, (a, b)| difference | (a ^ b))
+			== 0
+}
+
+/// The body of `POST /builds`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewBuild {
+	runtime: String,
+	target: String,
+}
+
+/// The REST endpoints.
+struct Api {
+	/// Required as a bearer token by every endpoint, if any.
+	token: Option<String>,
+	/// The runtimes which can be built.
+	runtimes: Vec<String>,
+	output_dir: PathBuf,
+}
+impl Api {
+	/// `POST /builds`, queue the build of `{"runtime": "crab", "target": "v0.12.3"}`.
+	fn create(&self, request: &mut Request, queue: &Queue) -> ResponseBox {
+		if !self.authorized(request) {
+			return text((401, "invalid token".into()));
+		}
+
+		let build = match serde_json::from_reader::<_, NewBuild>(request.as_reader()) {
+			Ok(build) => build,
+			Err(e) => return text((400, format!("invalid build: {}", e))),
+		};
+		let runtime = build.runtime.to_ascii_lowercase();
+
+		if !self.runtimes.contains(&runtime) {
+			return text((
+				400,
+				format!(
+					"unknown runtime `{}`, expected one of: {}",
+					runtime,
+					self.runtimes.join(", ")
+				),
+			));
+		}
+
+		let (id, new) = queue.push(&runtime, &build.target);
+
+		// An identical build already queued or running is reused.
+		json(
+			if new { 202 } else { 200 },
+			&serde_json::json!({ "id": id }),
+		)
+	}
+
+	/// `GET /builds/<ID>`, the state of a build, its artifacts and errors once done.
+	fn status(&self, request: &Request, id: &str, queue: &Queue) -> ResponseBox {
+		if !self.authorized(request) {
+			return text((401, "invalid token".into()));
+		}
+
+		let builds = queue.builds.lock().unwrap();
+
+		match id
+			.parse::<usize>()
+			.ok()
+			.and_then(|id| builds.get(id.checked_sub(1)?))
+		{
+			Some(build) => json(200, build),
+			None => text((404, format!("no build `{}`", id))),
+		}
+	}
+
+	/// `GET /artifacts/<PATH>`, a file of the output directory, e.g. `crab/wasms/...` or
+	/// `index.json`.
+	fn artifact(&self, request: &Request, path: &str) -> ResponseBox {
+		if !self.authorized(request) {
+			return text((401, "invalid token".into()));
+		}
+
+		let path = Path::new(path);
+
+		if !path
+			.components()
+			.all(|component| matches!(component, Component::Normal(_)))
+		{
+			return text((400, "invalid path".into()));
+		}
+
+		let content_type = if path
+			.extension()
+			.map_or(false, |extension| extension == "json")
+		{
+			"application/json"
+		} else {
+			"application/octet-stream"
+		};
+
+		match File::open(self.output_dir.join(path)) {
+			Ok(file) if file.metadata().map_or(false, |metadata| metadata.is_file()) => {
+				Response::from_file(file)
+					.with_header(content_type_header(content_type))
+					.boxed()
+			}
+			_ => text((404, format!("no artifact `{}`", path.display()))),
+		}
+	}
+
+	/// Whether `request` has the bearer token, if one is required.
+	fn authorized(&self, request: &Request) -> bool {
+		self.token.as_ref().map_or(true, |token| {
+			header(request, "Authorization").map_or(false, |authorization| {
+				constant_time_eq(&format!("Bearer {}", token), authorization)
+			})
+		})
+	}
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+	request
+		.headers()
+		.iter()
+		.find(|header| header.field.equiv(name))
+		.map(|header| header.value.as_str())
+}
+
+fn content_type_header(content_type: &str) -> Header {
+	Header::from_bytes("Content-Type", content_type).expect("valid header")
+}
+
+fn text((status, body): (u16, String)) -> ResponseBox {
+	Response::from_string(body)
+		.with_status_code(status)
+		.with_header(content_type_header("text/plain; charset=utf-8"))
+		.boxed()
+}
+
+fn json<T>(status: u16, body: &T) -> ResponseBox
+where
+	T: ?Sized + Serialize,
+{
+	Response::from_string(serde_json::to_string_pretty(body).unwrap_or_default())
+		.with_status_code(status)
+		.with_header(content_type_header("application/json"))
+		.boxed()
+}
+
+/// The tag a `push` or `release` event reports created or published.
+fn tag_of<'a>(event: &str, payload: &'a serde_json::Value) -> Option<&'a str> {
+	match event {
+		"push" if payload["deleted"] != true => payload["ref"].as_str()?.strip_prefix("refs/tags/"),
+		"release" if payload["action"] == "published" => payload["release"]["tag_name"].as_str(),
+		_ => None,
+	}
+}
+
+fn same_repository(a: &str, b: &str) -> bool {
+	let normalize = |url: &str| {
+		url.trim_end_matches('/')
+			.trim_end_matches(".git")
+			.to_ascii_lowercase()
+	};
+
+	normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn compares_in_constant_time() {
+		for (a, b, equal) in [
+			("", "", true),
+			("Bearer 1234", "Bearer 1234", true),
+			("Bearer 1234", "Bearer 1235", false),
+			("Bearer 1234", "Bearer 123", false),
+			("Bearer 123", "Bearer 1234", false),
+		] {
+			assert_eq!(constant_time_eq(a, b), equal, "`{}` and `{}`", a, b);
+		}
+	}
+
+	#[test]
+	fn verifies_the_webhook_signatures() {
+		// The example of the GitHub documentation.
+		let webhook = Webhook {
+			secret: "It's a Secret to Everybody".into(),
+			routes: Vec::new(),
+		};
+		let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+		assert!(webhook.verify(b"Hello, World!", signature));
+		assert!(!webhook.verify(b"Hello, World?", signature));
+		assert!(!webhook.verify(b"Hello, World!", &signature[..signature.len() - 1]));
+		assert!(!webhook.verify(b"Hello, World!", "sha1=01234"));
+	}
+}
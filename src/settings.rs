@@ -0,0 +1,317 @@
+//! The settings every build of an invocation shares, from the command line and the config.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::{Path, PathBuf},
+	sync::Arc,
+	thread,
+	time::Duration,
+};
+
+use clap::ArgEnum;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	artifact::MetadataFormat,
+	config::{self, GitConfig, RuntimeConfig},
+	progress::Progress,
+	registry::{Layout, Runtime},
+	runner::CommandRunner,
+	srtool, toolchain, try_runtime, AnyResult,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Builder {
+	/// The host's cargo, with the toolchain the workspace pins.
+	Cargo,
+	/// The srtool image, which also writes its digests to `<CHAIN>/srtool/`.
+	Srtool,
+	/// Cargo in the `--image` container, run by docker.
+	Docker,
+	/// Cargo in the `--image` container, run by podman.
+	Podman,
+}
+
+/// Settings shared by every build of this invocation.
+#[derive(Debug)]
+pub struct Settings {
+	pub build_dir: PathBuf,
+	pub use_worktrees: bool,
+	pub features: Option<Vec<String>>,
+	pub no_default_features: bool,
+	pub output_dir: PathBuf,
+	pub dry_run: bool,
+	pub install: bool,
+	pub offline: bool,
+	pub shallow: bool,
+	pub sparse: bool,
+	pub retries: u32,
+	pub commit_in_name: bool,
+	pub name_by_spec_version: bool,
+	pub content_addressed: bool,
+	pub metadata: Vec<MetadataFormat>,
+	pub verify_reproducible: bool,
+	pub try_runtime: Option<try_runtime::Check>,
+	pub builder: Builder,
+	pub image: Option<String>,
+	pub check_chain: bool,
+	pub strict_size: bool,
+	pub force: bool,
+	pub allow_duplicate: bool,
+	pub repo_url: Option<String>,
+	/// Absolute.
+	pub source_path: Option<PathBuf>,
+	pub manifest_path: Option<String>,
+	pub package: Option<String>,
+	pub runtimes: HashMap<String, RuntimeConfig>,
+	pub jobs: Option<u32>,
+	pub nice: bool,
+	pub profile: Option<String>,
+	pub no_clean: bool,
+	pub timeout: Option<Duration>,
+	pub build_log_dir: Option<PathBuf>,
+	pub cargo_args: Vec<String>,
+	/// Absolute, to be applied from any checkout, with their SHA-256.
+	pub patches: Vec<(PathBuf, String)>,
+	pub cargo_patch: BTreeMap<String, toml::value::Table>,
+	pub git: HashMap<String, GitConfig>,
+	/// Build without `--locked`.
+	pub allow_lock_update: bool,
+	pub keep_uncompressed: bool,
+	/// The arguments of `wasm-opt`, none not to run it.
+	pub wasm_opt: Option<Vec<String>>,
+	pub notify: Vec<String>,
+	pub notify_command: Option<String>,
+	pub ipfs: Option<String>,
+	/// The URL and the token of the remote pinning service.
+	pub ipfs_pin_service: Option<(String, String)>,
+	pub progress: Progress,
+	pub runner: Arc<dyn CommandRunner>,
+	/// Set for every command, e.g. `RUSTC_WRAPPER`.
+	pub envs: Vec<(String, String)>,
+}
+impl Settings {
+	pub fn runtime_config(&self, runtime: &Runtime) -> Option<&RuntimeConfig> {
+		self.runtimes.get(runtime.name())
+	}
+
+	/// Where to clone `runtime` from, honoring the command line and its config overrides.
+	pub fn repo_url(&self, runtime: &Runtime, layout: Layout) -> String {
+		self.repo_url
+			.clone()
+			.or_else(|| {
+				self.runtime_config(runtime)
+					.and_then(|r| r.repo_url.clone())
+			})
+			.unwrap_or_else(|| runtime.github(layout))
+	}
+
+	pub fn manifest_path(&self, runtime: &Runtime, layout: Layout) -> String {
+		self.manifest_path
+			.clone()
+			.or_else(|| {
+				self.runtime_config(runtime)
+					.and_then(|r| r.manifest_path.clone())
+			})
+			.unwrap_or_else(|| format!("{}/Cargo.toml", runtime.path(layout)))
+	}
+
+	pub fn package(&self, runtime: &Runtime) -> String {
+		self.package
+			.clone()
+			.or_else(|| self.runtime_config(runtime).and_then(|r| r.package.clone()))
+			.unwrap_or_else(|| runtime.package())
+	}
+
+	/// Where the wasm and the digest of `runtime` built from `target` at `commit` are written.
+	///
+	/// The spec version is only known once built, a placeholder stands for it until then.
+	pub fn artifact_paths(
+		&self,
+		runtime: &Runtime,
+		target: &str,
+		commit: &str,
+		spec_version: Option<u32>,
+	) -> (PathBuf, PathBuf) {
+		let name_prefix = if self.name_by_spec_version {
+			format!(
+				"{}-{}-tracing-runtime",
+				runtime.name(),
+				spec_version.map_or_else(|| "<spec_version>".into(), |v| v.to_string())
+			)
+		} else if self.commit_in_name {
+			format!(
+				"{}-{}-{}-tracing-runtime",
+				runtime.name(),
+				target,
+				commit.get(..7).unwrap_or(commit)
+			)
+		} else {
+			format!("{}-{}-tracing-runtime", runtime.name(), target)
+		};
+		let chain_dir = self.output_dir.join(runtime.name());
+
+		(
+			chain_dir
+				.join("wasms")
+				.join(format!("{}.compact.compressed.wasm", name_prefix)),
+			chain_dir
+				.join("digests")
+				.join(format!("{}.json", name_prefix)),
+		)
+	}
+
+	/// The directory the build logs of `runtime` are written to.
+	pub fn log_dir(&self, runtime: &Runtime) -> PathBuf {
+		match &self.build_log_dir {
+			Some(dir) => dir.join(runtime.name()),
+			None => self.output_dir.join(runtime.name()).join("logs"),
+		}
+	}
+
+	/// The container image the runtimes are built in, none with cargo.
+	pub fn image_of_builder(&self) -> Option<&str> {
+		match self.builder {
+			Builder::Cargo => None,
+			Builder::Srtool => Some(self.image.as_deref().unwrap_or(srtool::DEFAULT_IMAGE)),
+			Builder::Docker | Builder::Podman => self.image.as_deref(),
+		}
+	}
+
+	/// Invoke `cargo` with the pinned toolchain, at the lowest priority with `--nice`.
+	pub fn cargo<'a>(
+		&self,
+		toolchain: Option<&'a str>,
+		args: &[&'a str],
+	) -> (&'static str, Vec<&'a str>) {
+		let (program, args) = toolchain::cargo(toolchain, args);
+
+		if self.nice {
+			(
+				"nice",
+				["-n", "19", program].into_iter().chain(args).collect(),
+			)
+		} else {
+			(program, args)
+		}
+	}
+
+	/// The patches as recorded in the digests, null if there are none.
+	pub fn patches_json(&self) -> serde_json::Value {
+		if self.patches.is_empty() {
+			return serde_json::Value::Null;
+		}
+
+		self.patches
+			.iter()
+			.map(|(path, sha256)| {
+				serde_json::json!({
+					"file": path.file_name().unwrap_or_default().to_string_lossy(),
+					"sha256": sha256,
+				})
+			})
+			.collect()
+	}
+
+	/// The `[cargo-patch]` sections as recorded in the digests, null if there are none.
+	pub fn cargo_patch_json(&self) -> serde_json::Value {
+		if self.cargo_patch.is_empty() {
+			return serde_json::Value::Null;
+		}
+
+		serde_json::to_value(&self.cargo_patch).unwrap_or_default()
+	}
+
+	/// The cargo features of `runtime`, honoring its config overrides and registry defaults.
+	pub fn features_of(&self, runtime: &Runtime) -> (String, bool) {
+		let overrides = self.runtime_config(runtime);
+		let features = overrides
+			.and_then(|r| r.features.as_deref())
+			.or(self.features.as_deref())
+			.or_else(|| runtime.features())
+			.map(|features| features.join(","))
+			.unwrap_or_else(|| "evm-tracing".into());
+		let no_default_features = overrides
+			.and_then(|r| r.no_default_features)
+			.unwrap_or(self.no_default_features);
+
+		(features, no_default_features)
+	}
+
+	pub fn run(&self, dir: &Path, label: &str, program: &str, args: &[&str]) -> AnyResult<()> {
+		if self.dry_run {
+			info!(
+				"[dry-run] (cd {} && {}{} {})",
+				dir.display(),
+				self.envs
+					.iter()
+					.map(|(key, value)| format!("{}={} ", key, value))
+					.collect::<String>(),
+				program,
+				args.join(" ")
+			);
+
+			Ok(())
+		} else {
+			self.runner.run(dir, label, program, args, &self.envs, None)
+		}
+	}
+
+	/// `run` a build step, killing it once `self.timeout` is over.
+	pub fn build(&self, dir: &Path, label: &str, program: &str, args: &[&str]) -> AnyResult<()> {
+		if self.dry_run {
+			self.run(dir, label, program, args)
+		} else {
+			self.runner
+				.run(dir, label, program, args, &self.envs, self.timeout)
+		}
+	}
+
+	/// Run git talking to the remote at `url`, with the options of the config to reach it.
+	pub fn remote_git(&self, dir: &Path, label: &str, url: &str, args: &[&str]) -> AnyResult<()> {
+		let options = config::git_options(&self.git, url);
+		let args = options
+			.iter()
+			.map(String::as_str)
+			.chain(args.iter().copied())
+			.collect::<Vec<_>>();
+
+		self.run(dir, label, "git", &args)
+	}
+
+	/// Run `program` to completion and return its trimmed stdout, even in dry-run mode.
+	pub fn capture(&self, dir: &Path, program: &str, args: &[&str]) -> AnyResult<String> {
+		self.runner.capture(dir, program, args)
+	}
+
+	/// Retry a network operation up to `self.retries` times, with an exponential backoff.
+	pub fn retry<T, F>(&self, label: &str, mut f: F) -> AnyResult<T>
+	where
+		F: FnMut() -> AnyResult<T>,
+	{
+		let mut delay = Duration::from_secs(2);
+		let mut attempt = 1;
+
+		loop {
+			match f() {
+				Err(e) if attempt <= self.retries => {
+					warn!(
+						"[{}] attempt {}/{} failed, retrying in {}s: {:#}",
+						label,
+						attempt,
+						self.retries + 1,
+						delay.as_secs(),
+						e
+					);
+					thread::sleep(delay);
+
+					delay *= 2;
+					attempt += 1;
+				}
+				result => return result,
+			}
+		}
+	}
+}
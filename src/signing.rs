@@ -0,0 +1,130 @@
+//! Detached `.sig` signatures of the artifacts, made and checked by minisign or GPG.
+
+use std::{
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::Context;
+use clap::ArgEnum;
+use serde::Deserialize;
+
+use crate::{runner::CommandFailed, AnyResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Signer {
+	Minisign,
+	Gpg,
+}
+
+pub fn signature_path(file: &Path) -> PathBuf {
+	let mut name = file.file_name().unwrap_or_default().to_os_string();
+
+	name.push(".sig");
+
+	file.with_file_name(name)
+}
+
+/// Sign `file` with the minisign secret key at `key`, or the GPG key `key`.
+pub fn sign(signer: Signer, key: &str, file: &Path) -> AnyResult<PathBuf> {
+	let signature = signature_path(file);
+	let (file_arg, signature_arg) = (crate::path_arg(file), crate::path_arg(&signature));
+
+	match signer {
+		Signer::Minisign => invoke(
+			"minisign",
+			&["-S", "-s", key, "-m", &file_arg, "-x", &signature_arg],
+		),
+		Signer::Gpg => invoke(
+			"gpg",
+			&[
+				"--batch",
+				"--yes",
+				"--local-user",
+				key,
+				"--output",
+				&signature_arg,
+				"--detach-sign",
+				&file_arg,
+			],
+		),
+	}
+	.with_context(|| format!("failed to sign `{}`", file.display()))?;
+
+	Ok(signature)
+}
+
+/// Check the signature of `file` against the minisign public key at `key`, or the GPG keyring.
+pub fn verify(signer: Signer, key: Option<&str>, file: &Path) -> AnyResult<()> {
+	let signature = signature_path(file);
+
+	if !signature.is_file() {
+		anyhow::bail!("`{}` isn't signed", file.display());
+	}
+
+	let (file_arg, signature_arg) = (crate::path_arg(file), crate::path_arg(&signature));
+
+	match (signer, key) {
+		(Signer::Minisign, Some(key)) => invoke(
+			"minisign",
+			&["-V", "-q", "-p", key, "-m", &file_arg, "-x", &signature_arg],
+		),
+		(Signer::Minisign, None) => anyhow::bail!("minisign needs the public key, pass `--key`"),
+		(Signer::Gpg, _) => invoke("gpg", &["--batch", "--verify", &signature_arg, &file_arg]),
+	}
+	.with_context(|| format!("`{}` has an invalid signature", file.display()))
+}
+
+/// Check the signatures of every wasm and digest of `output_dir`.
+pub fn verify_all(output_dir: &Path, signer: Signer, key: Option<&str>) -> AnyResult<()> {
+	let mut checked = 0;
+	let mut problems = 0;
+
+	for chain_dir in crate::dir_entries(output_dir) {
+		let files = ["wasms", "digests"].iter().flat_map(|dir| {
+			crate::dir_entries(&chain_dir.join(dir))
+				.into_iter()
+				.filter(|path| {
+					let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+					!name.starts_with('.')
+						&& (name.ends_with(".compact.compressed.wasm") || name.ends_with(".json"))
+				})
+		});
+
+		for file in files {
+			checked += 1;
+
+			match verify(signer, key, &file) {
+				Ok(()) => println!("✔ {}", file.display()),
+				Err(e) => {
+					problems += 1;
+
+					println!("✘ {:#}", e);
+				}
+			}
+		}
+	}
+
+	if problems != 0 {
+		anyhow::bail!("{} of the {} signatures are invalid", problems, checked);
+	}
+
+	Ok(())
+}
+
+/// Run `program` in the foreground, it may need to prompt for a passphrase.
+fn invoke(program: &str, args: &[&str]) -> AnyResult<()> {
+	let command = format!("{} {}", program, args.join(" "));
+	let status = Command::new(program)
+		.args(args)
+		.status()
+		.with_context(|| format!("failed to spawn `{}`", command))?;
+
+	if !status.success() {
+		return Err(CommandFailed { command, status }.into());
+	}
+
+	Ok(())
+}
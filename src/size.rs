@@ -0,0 +1,27 @@
+//! The largest code a chain accepts, the compressed wasm being the payload of `system.setCode`.
+
+use serde_json::Value;
+
+/// The operational `max` of `System.BlockLength` in the JSON of a V14 metadata, which bounds the
+/// length of a `system.setCode` extrinsic.
+pub fn max_code_size(metadata: &Value) -> Option<u32> {
+	let constant = metadata[1]["V14"]["pallets"]
+		.as_array()?
+		.iter()
+		.find(|pallet| pallet["name"] == "System")?["constants"]
+		.as_array()?
+		.iter()
+		.find(|constant| constant["name"] == "BlockLength")?;
+	let value = match &constant["value"] {
+		Value::String(hex) => crate::rpc::decode_hex(hex).ok()?,
+		value => value
+			.as_array()?
+			.iter()
+			.map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+			.collect::<Option<Vec<_>>>()?,
+	};
+	// A `PerDispatchClass<u32>`: normal, operational and mandatory.
+	let operational = value.get(4..8)?;
+
+	Some(u32::from_le_bytes(operational.try_into().ok()?))
+}
@@ -0,0 +1,248 @@
+//! Tracing a transaction on a dev node running an override, before trusting it in production.
+
+use std::{
+	env,
+	fs::{self, File},
+	net::TcpListener,
+	path::Path,
+	process::{self, Child, Command},
+	thread,
+	time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use k256::ecdsa::{recoverable, signature::DigestSigner, SigningKey};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{cli::SmokeTestArgs, rpc, AnyResult};
+
+/// Gas of a plain transfer.
+const TRANSFER_GAS: u64 = 21_000;
+/// Baltathar, a dev account receiving the transfer.
+const RECIPIENT: &str = "3cd0a705a2dc65e5b1e1205896baa2be8a07c6e0";
+
+/// Kills the node once dropped.
+struct Node(Child);
+impl Drop for Node {
+	fn drop(&mut self) {
+		let _ = self.0.kill();
+		let _ = self.0.wait();
+	}
+}
+
+/// Start `--node` with `args.wasm` as its only override, send a transfer and trace it.
+pub fn run(args: SmokeTestArgs) -> AnyResult<()> {
+	let dir = env::temp_dir().join(format!("runtime-overrides-smoke-{}", process::id()));
+	let overrides = dir.join("overrides");
+	let log_path = dir.join("node.log");
+
+	fs::create_dir_all(&overrides)?;
+	crate::copy_file(
+		&args.wasm,
+		&overrides.join(args.wasm.file_name().unwrap_or_default()),
+	)?;
+
+	let result = smoke_test(&args, &overrides, &log_path);
+
+	match &result {
+		Ok(()) => {
+			let _ = fs::remove_dir_all(&dir);
+
+			println!("Passed, {} traces transactions", args.wasm.display());
+		}
+		Err(_) => println!("Failed, see the node log `{}`", log_path.display()),
+	}
+
+	result
+}
+
+fn smoke_test(args: &SmokeTestArgs, overrides: &Path, log_path: &Path) -> AnyResult<()> {
+	let deadline = Instant::now() + args.timeout;
+	let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+	let log = File::create(log_path)?;
+	let mut node = Node(
+		Command::new(&args.node)
+			.args(["--chain", &args.chain, "--alice", "--tmp"])
+			.args([
+				"--rpc-port",
+				&port.to_string(),
+				"--ethapi",
+				"debug,trace,txpool",
+			])
+			.arg("--wasm-runtime-overrides")
+			.arg(overrides)
+			.args(&args.node_arg)
+			.stdout(log.try_clone()?)
+			.stderr(log)
+			.spawn()
+			.with_context(|| format!("failed to spawn `{}`", args.node.display()))?,
+	);
+	let client = rpc::Client::new(&format!("http://127.0.0.1:{}", port));
+	let version = wait(deadline, "the node to serve RPC", || {
+		if let Some(status) = node.0.try_wait()? {
+			anyhow::bail!("the node exited with {}", status);
+		}
+
+		Ok(client.runtime_version(None).ok())
+	})?;
+
+	println!("✔ node started, RPC on port {}", port);
+
+	// Overrides are only used for the spec version the chain runs.
+	let runtime_info =
+		serde_json::to_value(Subwasm::new(&Source::File(args.wasm.clone())).runtime_info())?;
+	let expected = format!("{}-{}", version.spec_name, version.spec_version);
+
+	match runtime_info["core_version"].as_str() {
+		Some(core_version) if core_version.starts_with(&format!("{} ", expected)) => {
+			println!("✔ the chain runs {}, the version of the override", expected)
+		}
+		core_version => anyhow::bail!(
+			"the chain runs {} but the override is {}, the node wouldn't use it",
+			expected,
+			core_version.unwrap_or("unknown")
+		),
+	}
+
+	let key = SigningKey::from_bytes(
+		&rpc::decode_hex(&args.private_key).context("invalid `--private-key`")?,
+	)
+	.context("invalid `--private-key`")?;
+	let sender = address(&key);
+	let chain_id = quantity(&client.call::<String>("eth_chainId", json!([]))?)? as u64;
+	let nonce = quantity(&client.call::<String>(
+		"eth_getTransactionCount",
+		json!([format!("0x{}", crate::to_hex(&sender)), "pending"]),
+	)?)? as u64;
+	let gas_price = quantity(&client.call::<String>("eth_gasPrice", json!([]))?)?;
+	let transaction = sign_transfer(&key, chain_id, nonce, gas_price)?;
+	let hash = client.call::<String>(
+		"eth_sendRawTransaction",
+		json!([format!("0x{}", crate::to_hex(&transaction))]),
+	)?;
+
+	println!("✔ sent transaction {}", hash);
+
+	let receipt = wait(deadline, "the transaction to be included", || {
+		client.call::<Option<Value>>("eth_getTransactionReceipt", json!([hash]))
+	})?;
+
+	println!(
+		"✔ included in block {}",
+		receipt["blockNumber"].as_str().unwrap_or("unknown")
+	);
+
+	let trace = client
+		.call::<Value>("debug_traceTransaction", json!([hash]))
+		.context("the transaction couldn't be traced")?;
+
+	match trace["structLogs"].as_array() {
+		Some(steps) => println!("✔ debug_traceTransaction returned {} step(s)", steps.len()),
+		None => anyhow::bail!("unexpected `debug_traceTransaction` result: {}", trace),
+	}
+
+	Ok(())
+}
+
+/// Poll `f` every second until it returns something, failing past the deadline.
+fn wait<T, F>(deadline: Instant, what: &str, mut f: F) -> AnyResult<T>
+where
+	F: FnMut() -> AnyResult<Option<T>>,
+{
+	loop {
+		if let Some(t) = f()? {
+			return Ok(t);
+		}
+		if Instant::now() >= deadline {
+			anyhow::bail!("timed out waiting for {}, pass a longer `--timeout`", what);
+		}
+
+		thread::sleep(Duration::from_secs(1));
+	}
+}
+
+/// A `0x` prefixed hex number of the Ethereum RPC.
+fn quantity(quantity: &str) -> AnyResult<u128> {
+	u128::from_str_radix(quantity.trim_start_matches("0x"), 16)
+		.with_context(|| format!("invalid quantity `{}`", quantity))
+}
+
+fn address(key: &SigningKey) -> Vec<u8> {
+	let public_key = key.verifying_key().to_encoded_point(false);
+
+	Keccak256::digest(&public_key.as_bytes()[1..])[12..].to_vec()
+}
+
+/// An EIP-155 legacy transaction sending 1 wei to [`RECIPIENT`].
+fn sign_transfer(
+	key: &SigningKey,
+	chain_id: u64,
+	nonce: u64,
+	gas_price: u128,
+) -> AnyResult<Vec<u8>> {
+	let to = rpc::decode_hex(RECIPIENT)?;
+	let fields = [
+		rlp_uint(nonce.into()),
+		rlp_uint(gas_price),
+		rlp_uint(TRANSFER_GAS.into()),
+		rlp_bytes(&to),
+		rlp_uint(1),
+		rlp_bytes(&[]),
+	];
+	let unsigned = rlp_list(
+		&[
+			&fields[..],
+			&[rlp_uint(chain_id.into()), rlp_uint(0), rlp_uint(0)],
+		]
+		.concat(),
+	);
+	let signature: recoverable::Signature = key.sign_digest(Keccak256::new_with_prefix(&unsigned));
+	let signature = signature.as_ref();
+	let v = u128::from(signature[64]) + u128::from(chain_id) * 2 + 35;
+
+	Ok(rlp_list(
+		&[
+			&fields[..],
+			&[
+				rlp_uint(v),
+				rlp_bytes(strip_zeros(&signature[..32])),
+				rlp_bytes(strip_zeros(&signature[32..64])),
+			],
+		]
+		.concat(),
+	))
+}
+
+fn strip_zeros(bytes: &[u8]) -> &[u8] {
+	&bytes[bytes.iter().take_while(|byte| **byte == 0).count()..]
+}
+
+fn rlp_uint(n: u128) -> Vec<u8> {
+	rlp_bytes(strip_zeros(&n.to_be_bytes()))
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+	match bytes {
+		[byte] if *byte < 0x80 => vec![*byte],
+		_ => [rlp_length(bytes.len(), 0x80), bytes.to_vec()].concat(),
+	}
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+	let payload = items.concat();
+
+	[rlp_length(payload.len(), 0xc0), payload].concat()
+}
+
+fn rlp_length(length: usize, offset: u8) -> Vec<u8> {
+	if length < 56 {
+		vec![offset + length as u8]
+	} else {
+		let length = strip_zeros(&length.to_be_bytes()).to_vec();
+
+		[vec![offset + 55 + length.len() as u8], length].concat()
+	}
+}
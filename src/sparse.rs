@@ -0,0 +1,169 @@
+//! Sparse checkouts limited to the crates the runtimes' workspace is made of.
+//!
+//! Cargo loads every workspace member and resolves all of their path dependencies, so those are
+//! the directories kept, anything else (docs, scripts, assets...) is left out.
+
+use std::{
+	collections::{BTreeSet, HashSet},
+	path::{Component, Path},
+};
+
+use anyhow::Context;
+use toml::Value;
+
+use crate::{pipeline::Job, settings::Settings, AnyResult};
+
+/// Restrict the checkout of `dir` to the crates needed to build the runtimes of `job`.
+pub fn set(dir: &Path, job: &Job, settings: &Settings) -> AnyResult<()> {
+	let label = job.label();
+
+	if settings.dry_run {
+		return settings.run(
+			dir,
+			&label,
+			"git",
+			&["sparse-checkout", "set", "--cone", "<workspace crates>"],
+		);
+	}
+
+	let revision = job.revision();
+	let files = settings.capture(dir, "git", &["ls-tree", "-r", "--name-only", &revision])?;
+	let crates = files
+		.lines()
+		.filter_map(|file| {
+			if file == "Cargo.toml" {
+				Some("")
+			} else {
+				file.strip_suffix("/Cargo.toml")
+			}
+		})
+		.collect::<HashSet<_>>();
+	let mut pending = job
+		.runtimes
+		.iter()
+		.map(|runtime| {
+			let manifest = settings.manifest_path(runtime, job.layout);
+
+			normalize(
+				Path::new(&manifest)
+					.parent()
+					.unwrap_or_else(|| Path::new("")),
+			)
+		})
+		.collect::<Vec<_>>();
+	let mut seen = HashSet::new();
+	let mut dirs = BTreeSet::new();
+
+	if crates.contains("") {
+		let root = read_manifest(dir, &revision, "", settings)?;
+		let members = root
+			.get("workspace")
+			.and_then(|workspace| workspace.get("members"))
+			.and_then(Value::as_array)
+			.into_iter()
+			.flatten()
+			.filter_map(Value::as_str);
+
+		for member in members {
+			pending.extend(
+				crates
+					.iter()
+					.filter(|dir| matches(member.trim_end_matches('/'), dir))
+					.map(|dir| dir.to_string()),
+			);
+		}
+
+		collect_paths(&root, Path::new(""), &mut pending);
+	}
+
+	while let Some(crate_dir) = pending.pop() {
+		if crate_dir.is_empty()
+			|| !crates.contains(crate_dir.as_str())
+			|| !seen.insert(crate_dir.clone())
+		{
+			continue;
+		}
+
+		let manifest = read_manifest(dir, &revision, &crate_dir, settings)?;
+
+		collect_paths(&manifest, Path::new(&crate_dir), &mut pending);
+		dirs.insert(crate_dir);
+	}
+
+	if files.lines().any(|file| file.starts_with(".cargo/")) {
+		dirs.insert(".cargo".into());
+	}
+
+	let mut args = vec!["sparse-checkout", "set", "--cone"];
+
+	args.extend(dirs.iter().map(String::as_str));
+
+	settings.run(dir, &label, "git", &args)
+}
+
+fn read_manifest(
+	dir: &Path,
+	revision: &str,
+	crate_dir: &str,
+	settings: &Settings,
+) -> AnyResult<Value> {
+	let path = if crate_dir.is_empty() {
+		"Cargo.toml".into()
+	} else {
+		format!("{}/Cargo.toml", crate_dir)
+	};
+	let content = settings.capture(dir, "git", &["show", &format!("{}:{}", revision, path)])?;
+
+	toml::from_str(&content).with_context(|| format!("failed to parse `{}`", path))
+}
+
+/// Every `path = "..."` of the manifest, resolved against its directory.
+///
+/// Those of the `[lib]` and `[[bin]]` targets are picked up too, they simply aren't crates.
+fn collect_paths(value: &Value, crate_dir: &Path, paths: &mut Vec<String>) {
+	match value {
+		Value::Table(table) => {
+			for (key, value) in table {
+				match (key.as_str(), value) {
+					("path", Value::String(path)) => paths.push(normalize(&crate_dir.join(path))),
+					_ => collect_paths(value, crate_dir, paths),
+				}
+			}
+		}
+		Value::Array(values) => {
+			for value in values {
+				collect_paths(value, crate_dir, paths);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Resolve the `..` of a relative path lexically, the sparse checkout has nothing to canonicalize.
+fn normalize(path: &Path) -> String {
+	let mut parts = Vec::new();
+
+	for component in path.components() {
+		match component {
+			Component::ParentDir => {
+				parts.pop();
+			}
+			Component::Normal(part) => parts.push(part.to_string_lossy()),
+			_ => {}
+		}
+	}
+
+	parts.join("/")
+}
+
+/// Match a workspace member glob, e.g. `frame/*`, against a directory.
+fn matches(pattern: &str, dir: &str) -> bool {
+	let patterns = pattern.split('/').collect::<Vec<_>>();
+	let parts = dir.split('/').collect::<Vec<_>>();
+
+	patterns.len() == parts.len()
+		&& patterns
+			.iter()
+			.zip(parts)
+			.all(|(pattern, part)| crate::glob_match(pattern, part))
+}
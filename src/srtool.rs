@@ -0,0 +1,85 @@
+//! Builds inside the srtool image, the deterministic builder of the Polkadot runtimes.
+//!
+//! See https://github.com/paritytech/srtool, its `build --json` output is kept as srtool's digest.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::debug;
+
+use crate::{settings::Settings, AnyResult};
+
+pub const DEFAULT_IMAGE: &str = "paritytech/srtool";
+
+/// Build `package` from the workspace `source_dir` is the root of, returning the path of the wasm
+/// and srtool's digest, none in dry-run.
+pub fn build(
+	source_dir: &Path,
+	label: &str,
+	manifest_path: &str,
+	package: &str,
+	build_opts: &[&str],
+	settings: &Settings,
+) -> AnyResult<(PathBuf, Option<serde_json::Value>)> {
+	let profile = settings.profile.as_deref().unwrap_or("release");
+	let runtime_dir = Path::new(manifest_path)
+		.parent()
+		.map(crate::path_arg)
+		.unwrap_or_default();
+	let envs = [
+		format!("PACKAGE={}", package),
+		format!("RUNTIME_DIR={}", runtime_dir),
+		format!("BUILD_OPTS={}", build_opts.join(" ")),
+		format!("PROFILE={}", profile),
+	]
+	.into_iter()
+	.chain(
+		settings
+			.envs
+			.iter()
+			.map(|(key, value)| format!("{}={}", key, value)),
+	)
+	.collect::<Vec<_>>();
+	let volume = format!("{}:/build", crate::path_arg(source_dir));
+	let image = settings.image.as_deref().unwrap_or(DEFAULT_IMAGE);
+	let mut args = vec!["run", "--rm"];
+
+	if settings.offline {
+		args.push("--network=none");
+	}
+
+	for env in &envs {
+		args.extend(["-e", env.as_str()]);
+	}
+
+	args.extend(["-v", &volume, image, "build", "--app", "--json"]);
+
+	let default_wasm_path = source_dir.join(format!(
+		"target/srtool/{}/wbuild/{}/{}.compact.compressed.wasm",
+		crate::profile_dir(profile),
+		package,
+		package.replace('-', "_")
+	));
+
+	if settings.dry_run {
+		settings.run(source_dir, label, "docker", &args)?;
+
+		return Ok((default_wasm_path, None));
+	}
+
+	let output = settings.capture(source_dir, "docker", &args)?;
+
+	debug!("[docker {}] {}", label, output);
+
+	// The progress comes first, the digest is the last line.
+	let digest = output
+		.lines()
+		.last()
+		.and_then(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+		.context("srtool didn't print its digest")?;
+	let wasm_path = digest["runtimes"]["compressed"]["wasm"]
+		.as_str()
+		.map_or(default_wasm_path, |wasm| source_dir.join(wasm));
+
+	Ok((wasm_path, Some(digest)))
+}
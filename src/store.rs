@@ -0,0 +1,78 @@
+//! Content addressed wasms: each one stored once, as `wasms/by-hash/<BLAKE2_256>.wasm`, the
+//! artifact names being symlinks to it, since the targets of a chain often build the same runtime.
+
+use std::{
+	collections::HashSet,
+	fs,
+	os::unix,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::AnyResult;
+
+pub const DIR: &str = "by-hash";
+
+/// Store `wasm` under `blake2_256` next to `link`, unless the same wasm is already there, then make
+/// `link` point to it.
+///
+/// `wasm` is moved into the store, or copied if it's another artifact to `keep`.
+pub fn write(wasm: &Path, link: &Path, blake2_256: &str, keep: bool) -> AnyResult<()> {
+	let wasms_dir = link.parent().unwrap_or_else(|| Path::new("."));
+	let name = format!("{}.wasm", blake2_256.trim_start_matches("0x"));
+	let object = wasms_dir.join(DIR).join(&name);
+
+	if object.is_file() {
+		if !keep {
+			fs::remove_file(wasm)?;
+		}
+	} else {
+		fs::create_dir_all(wasms_dir.join(DIR))?;
+
+		if keep {
+			crate::copy_file(wasm, &object)?;
+		} else {
+			let tmp = crate::temporary_path(&object);
+
+			crate::move_file(wasm, &tmp)
+				.and_then(|()| Ok(fs::rename(&tmp, &object)?))
+				.with_context(|| format!("failed to write `{}`", object.display()))?;
+		}
+	}
+
+	// Relative, for the output directory to be moved around.
+	let target = Path::new(DIR).join(name);
+	let tmp = crate::temporary_path(link);
+	let _ = fs::remove_file(&tmp);
+
+	unix::fs::symlink(&target, &tmp)
+		.and_then(|()| fs::rename(&tmp, link))
+		.with_context(|| {
+			format!(
+				"failed to link `{}` to `{}`",
+				link.display(),
+				target.display()
+			)
+		})
+}
+
+/// Remove the stored wasms of `wasms_dir` no artifact points to anymore, returning them.
+pub fn collect_garbage(wasms_dir: &Path) -> AnyResult<Vec<PathBuf>> {
+	let linked = crate::dir_entries(wasms_dir)
+		.into_iter()
+		.filter_map(|path| fs::read_link(path).ok())
+		.map(|target| wasms_dir.join(target))
+		.collect::<HashSet<_>>();
+	let unlinked = crate::dir_entries(&wasms_dir.join(DIR))
+		.into_iter()
+		.filter(|object| !linked.contains(object))
+		.collect::<Vec<_>>();
+
+	for object in &unlinked {
+		fs::remove_file(object)
+			.with_context(|| format!("failed to remove `{}`", object.display()))?;
+	}
+
+	Ok(unlinked)
+}
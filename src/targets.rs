@@ -0,0 +1,240 @@
+//! The branches and tags the runtimes can be built from.
+
+use std::{
+	collections::HashMap,
+	fmt::{Display, Formatter, Result as FmtResult},
+	path::Path,
+};
+
+use crate::{
+	config::{self, GitConfig},
+	AnyResult, CommandRunner, SystemRunner,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefKind {
+	Branch,
+	Tag,
+}
+impl Display for RefKind {
+	fn fmt(&self, f: &mut Formatter) -> FmtResult {
+		match self {
+			Self::Branch => write!(f, "branch"),
+			Self::Tag => write!(f, "tag"),
+		}
+	}
+}
+
+/// A branch or a tag of a remote repository.
+#[derive(Debug)]
+pub struct Ref {
+	pub kind: RefKind,
+	pub name: String,
+	pub commit: String,
+}
+
+/// The branches and tags of the repository at `url`, version sorted, reached with the `-c` options
+/// `git`.
+pub fn remote_refs(url: &str, git: &[String], runner: &dyn CommandRunner) -> AnyResult<Vec<Ref>> {
+	let args = git
+		.iter()
+		.map(String::as_str)
+		.chain(["ls-remote", "--heads", "--tags", "--sort=v:refname", url])
+		.collect::<Vec<_>>();
+	let output = runner.capture(Path::new("."), "git", &args)?;
+	let mut refs = Vec::<Ref>::new();
+
+	for line in output.lines() {
+		let (commit, name) = match line.split_once('\t') {
+			Some(line) => line,
+			None => continue,
+		};
+		let (kind, name) = match (
+			name.strip_prefix("refs/heads/"),
+			name.strip_prefix("refs/tags/"),
+		) {
+			(Some(name), _) => (RefKind::Branch, name),
+			(_, Some(name)) => (RefKind::Tag, name),
+			_ => continue,
+		};
+
+		// Annotated tags are listed a second time, peeled to their commit, with a `^{}` suffix.
+		if let Some(name) = name.strip_suffix("^{}") {
+			if let Some(tag) = refs
+				.iter_mut()
+				.find(|r| r.kind == RefKind::Tag && r.name == name)
+			{
+				tag.commit = commit.into();
+			}
+		} else {
+			refs.push(Ref {
+				kind,
+				name: name.into(),
+				commit: commit.into(),
+			});
+		}
+	}
+
+	Ok(refs)
+}
+
+/// The commit `target` currently points to, tags first like git does.
+pub fn resolve<'a>(target: &'a str, refs: &'a [Ref]) -> Option<&'a str> {
+	if target.len() == 40 && is_commit(target) {
+		return Some(target);
+	}
+
+	[RefKind::Tag, RefKind::Branch].iter().find_map(|kind| {
+		refs.iter()
+			.find(|r| r.kind == *kind && r.name == target)
+			.map(|r| r.commit.as_str())
+	})
+}
+
+/// Print the branches and tags of every repository, those matching `filter` only if given.
+pub fn list(
+	urls: &[String],
+	filter: Option<&str>,
+	git: &HashMap<String, GitConfig>,
+) -> AnyResult<()> {
+	for url in urls {
+		if urls.len() > 1 {
+			println!("{}:", url);
+		}
+
+		for r in remote_refs(url, &config::git_options(git, url), &SystemRunner)? {
+			if filter.map_or(true, |filter| crate::glob_match(filter, &r.name)) {
+				println!("{:<6} {}", r.kind, r.name);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Make sure `target` is one of `refs`, suggesting the closest ones if it isn't.
+///
+/// Commits can't be looked up remotely, anything looking like one is trusted.
+pub fn check(target: &str, refs: &[Ref], url: &str) -> AnyResult<()> {
+	if is_commit(target) || refs.iter().any(|r| r.name == target) {
+		return Ok(());
+	}
+
+	let mut candidates = refs
+		.iter()
+		.map(|r| (distance(target, &r.name), r.name.as_str()))
+		.filter(|(distance, name)| *distance <= target.len().max(6) / 3 || name.contains(target))
+		.collect::<Vec<_>>();
+
+	candidates.sort();
+	candidates.dedup_by_key(|(_, name)| *name);
+
+	if candidates.is_empty() {
+		anyhow::bail!(
+			"`{}` is neither a branch nor a tag of {}, see `list-targets` for the valid ones",
+			target,
+			url
+		);
+	}
+
+	anyhow::bail!(
+		"`{}` is neither a branch nor a tag of {}, did you mean {}?",
+		target,
+		url,
+		candidates
+			.iter()
+			.take(5)
+			.map(|(_, name)| format!("`{}`", name))
+			.collect::<Vec<_>>()
+			.join(", ")
+	)
+}
+
+/// Whether `target` looks like a full or abbreviated commit hash.
+pub fn is_commit(target: &str) -> bool {
+	(7..=40).contains(&target.len()) && target.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The Levenshtein distance between two names.
+fn distance(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+	for (i, a) in a.chars().enumerate() {
+		let mut previous = row[0];
+
+		row[0] = i + 1;
+
+		for (j, b) in b.iter().enumerate() {
+			let substitution = previous + usize::from(a != *b);
+
+			previous = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+		}
+	}
+
+	row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn refs(names: &[&str]) -> Vec<Ref> {
+		names
+			.iter()
+			.map(|name| Ref {
+				kind: RefKind::Tag,
+				name: (*name).into(),
+				commit: String::new(),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn suggests_the_closest_targets() {
+		let refs = refs(&["main", "v0.12.2", "v0.12.3", "v0.12.3-1", "release/v0.12"]);
+
+		for (target, suggestions) in [
+			("main", None),
+			("0123abc", None),
+			("v0.12.4", Some("did you mean `v0.12.2`, `v0.12.3`?")),
+			("mian", Some("did you mean `main`?")),
+			(
+				"v0.12.3-",
+				Some("did you mean `v0.12.3`, `v0.12.3-1`, `v0.12.2`?"),
+			),
+			("v0.12", Some("`v0.12.3-1`, `release/v0.12`?")),
+			("develop", Some("see `list-targets` for the valid ones")),
+		] {
+			let result = check(
+				target,
+				&refs,
+				"https://github.com/darwinia-network/darwinia",
+			);
+
+			match suggestions {
+				None => assert!(result.is_ok(), "`{}`", target),
+				Some(suggestions) => {
+					let e = result.unwrap_err().to_string();
+
+					assert!(e.contains(suggestions), "`{}`: {}", target, e);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn measures_the_distance_between_names() {
+		for (a, b, distance) in [
+			("", "", 0),
+			("main", "main", 0),
+			("main", "mian", 2),
+			("v0.12.3", "v0.12.4", 1),
+			("v0.12.3", "v0.12.3-1", 2),
+			("", "main", 4),
+		] {
+			assert_eq!(super::distance(a, b), distance, "`{}` and `{}`", a, b);
+		}
+	}
+}
@@ -0,0 +1,183 @@
+//! The Rust toolchain the runtime repositories were developed against.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use log::info;
+use serde::Deserialize;
+
+use crate::{settings::Settings, AnyResult};
+
+/// Every runtime is compiled to this target by `substrate-wasm-builder`.
+pub const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+#[derive(Debug, Deserialize)]
+struct ToolchainFile {
+	toolchain: Toolchain,
+}
+
+/// The `[toolchain]` section of a `rust-toolchain.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Toolchain {
+	pub channel: Option<String>,
+	pub components: Vec<String>,
+	pub targets: Vec<String>,
+}
+
+/// The toolchain pinned by the workspace's `rust-toolchain.toml` or `rust-toolchain` file.
+pub fn detect(workspace: &Path) -> AnyResult<Option<Toolchain>> {
+	for name in ["rust-toolchain.toml", "rust-toolchain"] {
+		let path = workspace.join(name);
+
+		if !path.is_file() {
+			continue;
+		}
+
+		let content = fs::read_to_string(&path)
+			.with_context(|| format!("failed to read `{}`", path.display()))?;
+		// The legacy `rust-toolchain` file may only contain the channel name.
+		let toolchain = match toml::from_str::<ToolchainFile>(&content) {
+			Ok(file) => file.toolchain,
+			Err(_) => Toolchain {
+				channel: Some(content.trim().to_owned()).filter(|channel| !channel.is_empty()),
+				..Default::default()
+			},
+		};
+
+		return Ok(Some(toolchain));
+	}
+
+	Ok(None)
+}
+
+/// Install the workspace's toolchain, its components and the wasm target unless they already are.
+pub fn ensure(workspace: &Path, settings: &Settings) -> AnyResult<()> {
+	if settings.dry_run {
+		return Ok(());
+	}
+
+	let toolchain = detect(workspace)?.unwrap_or_default();
+	let channel = toolchain.channel.as_deref();
+	let toolchain_args = channel
+		.map(|channel| vec!["--toolchain", channel])
+		.unwrap_or_default();
+
+	if let Some(channel) = channel {
+		let installed = settings.capture(workspace, "rustup", &["toolchain", "list"])?;
+
+		if !installed.lines().any(|line| is_toolchain(line, channel)) {
+			info!("Installing the {} toolchain", channel);
+			settings.run(
+				workspace,
+				"rustup",
+				"rustup",
+				&["toolchain", "install", channel, "--profile", "minimal"],
+			)?;
+		}
+	}
+
+	let targets = [WASM_TARGET]
+		.into_iter()
+		.chain(toolchain.targets.iter().map(String::as_str))
+		.collect::<Vec<_>>();
+	let components = toolchain
+		.components
+		.iter()
+		.map(String::as_str)
+		.collect::<Vec<_>>();
+
+	install_missing(workspace, settings, "target", &targets, &toolchain_args)?;
+	install_missing(
+		workspace,
+		settings,
+		"component",
+		&components,
+		&toolchain_args,
+	)
+}
+
+/// `rustup <kind> add` whatever `rustup <kind> list --installed` doesn't report.
+fn install_missing(
+	workspace: &Path,
+	settings: &Settings,
+	kind: &str,
+	wanted: &[&str],
+	toolchain_args: &[&str],
+) -> AnyResult<()> {
+	if wanted.is_empty() {
+		return Ok(());
+	}
+
+	let mut args = vec![kind, "list", "--installed"];
+
+	args.extend(toolchain_args);
+
+	let installed = settings.capture(workspace, "rustup", &args)?;
+	// Components are listed with their host triple, e.g. `rust-src` or `clippy-x86_64-unknown-linux-gnu`.
+	let missing = wanted
+		.iter()
+		.filter(|wanted| {
+			!installed
+				.lines()
+				.any(|line| line == **wanted || line.starts_with(&format!("{}-", wanted)))
+		})
+		.copied()
+		.collect::<Vec<_>>();
+
+	if missing.is_empty() {
+		return Ok(());
+	}
+
+	info!("Installing the missing {}s: {}", kind, missing.join(", "));
+
+	let mut args = vec![kind, "add"];
+
+	args.extend(missing);
+	args.extend(toolchain_args);
+
+	settings.run(workspace, "rustup", "rustup", &args)
+}
+
+/// Whether a `rustup toolchain list` line, e.g. `nightly-2022-05-12-x86_64-unknown-linux-gnu
+/// (default)`, is the `channel` toolchain.
+fn is_toolchain(line: &str, channel: &str) -> bool {
+	let name = line.split_whitespace().next().unwrap_or_default();
+
+	match name.strip_prefix(channel) {
+		Some("") => true,
+		// Skip the dated builds of an undated channel, e.g. `nightly` vs `nightly-2022-05-12`.
+		Some(host) => host.starts_with('-') && !host[1..].starts_with(|c: char| c.is_ascii_digit()),
+		None => false,
+	}
+}
+
+/// The `rustc --version` of the toolchain, the default one if none is pinned.
+pub fn rustc_version(
+	workspace: &Path,
+	toolchain: Option<&str>,
+	settings: &Settings,
+) -> AnyResult<String> {
+	match toolchain {
+		Some(toolchain) => settings.capture(
+			workspace,
+			"rustup",
+			&["run", toolchain, "rustc", "--version"],
+		),
+		None => settings.capture(workspace, "rustc", &["--version"]),
+	}
+}
+
+/// Invoke `cargo` through `rustup run` when a toolchain is pinned.
+pub fn cargo<'a>(toolchain: Option<&'a str>, args: &[&'a str]) -> (&'static str, Vec<&'a str>) {
+	match toolchain {
+		Some(toolchain) => (
+			"rustup",
+			["run", toolchain, "cargo"]
+				.into_iter()
+				.chain(args.iter().copied())
+				.collect(),
+		),
+		None => ("cargo", args.to_vec()),
+	}
+}
@@ -0,0 +1,66 @@
+//! Running the built runtimes through `try-runtime` against the state of their live chain, to
+//! catch the ones which would panic or diverge before publishing them.
+
+use std::path::Path;
+
+use anyhow::Context;
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{settings::Settings, AnyResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+	/// Run the migrations of the runtime upgrade.
+	OnRuntimeUpgrade,
+	/// Execute the latest block.
+	ExecuteBlock,
+}
+impl Check {
+	fn subcommand(self) -> &'static str {
+		match self {
+			Self::OnRuntimeUpgrade => "on-runtime-upgrade",
+			Self::ExecuteBlock => "execute-block",
+		}
+	}
+}
+
+/// Fail unless `try-runtime` runs `check` with `wasm` on the state of the chain at `uri`.
+pub fn run(
+	check: Check,
+	wasm: &Path,
+	uri: &str,
+	source_dir: &Path,
+	label: &str,
+	settings: &Settings,
+) -> AnyResult<()> {
+	// The state is downloaded over WebSocket, Substrate nodes serve it on the HTTP port too.
+	let uri = match uri.split_once("://") {
+		Some(("http", rest)) => format!("ws://{}", rest),
+		Some(("https", rest)) => format!("wss://{}", rest),
+		_ => uri.into(),
+	};
+
+	settings
+		.run(
+			source_dir,
+			label,
+			"try-runtime",
+			&[
+				"--runtime",
+				&crate::path_arg(wasm),
+				check.subcommand(),
+				"live",
+				"--uri",
+				&uri,
+			],
+		)
+		.with_context(|| {
+			format!(
+				"`try-runtime {}` failed, {} would break its chain",
+				check.subcommand(),
+				label
+			)
+		})
+}
@@ -0,0 +1,125 @@
+//! Audit of the artifacts already in the output directory.
+
+use std::{collections::BTreeSet, path::Path};
+
+use subwasmlib::Subwasm;
+use wasm_loader::Source;
+
+use crate::{checksums, schema, AnyResult};
+
+pub const WASM_SUFFIX: &str = ".compact.compressed.wasm";
+
+/// Check every digest against the schema, recompute the digest of every wasm and compare it to the
+/// stored one, then check the checksums.
+pub fn run(output_dir: &Path) -> AnyResult<()> {
+	let mut checked = 0;
+	let mut problems = 0;
+
+	for chain_dir in crate::dir_entries(output_dir) {
+		let wasms_dir = chain_dir.join("wasms");
+		let digests_dir = chain_dir.join("digests");
+		let wasms = names(&wasms_dir, WASM_SUFFIX);
+		let digests = names(&digests_dir, ".json");
+
+		for name in wasms.union(&digests) {
+			let wasm = wasms_dir.join(format!("{}{}", name, WASM_SUFFIX));
+			let digest = digests_dir.join(format!("{}.json", name));
+			let problem = match (wasms.contains(name), digests.contains(name)) {
+				(true, false) => Some(format!("`{}` has no digest", wasm.display())),
+				(false, true) => Some(format!(
+					"`{}` is orphaned, its wasm is missing",
+					digest.display()
+				)),
+				_ => check(&wasm, &digest).err(),
+			};
+
+			checked += 1;
+
+			match problem {
+				Some(problem) => {
+					problems += 1;
+
+					println!("✘ {}", problem);
+				}
+				None => println!("✔ {}", wasm.display()),
+			}
+		}
+
+		let (manifests, manifest_problems) = checksums::check(&chain_dir);
+
+		checked += manifests.len();
+		problems += manifest_problems.len();
+
+		for problem in &manifest_problems {
+			println!("✘ {}", problem);
+		}
+		if manifest_problems.is_empty() {
+			for manifest in manifests {
+				println!("✔ {}", manifest.display());
+			}
+		}
+	}
+
+	if problems != 0 {
+		anyhow::bail!(
+			"{} of the {} artifacts failed the verification",
+			problems,
+			checked
+		);
+	}
+
+	Ok(())
+}
+
+/// Validate the stored digest, then compare the recomputed one to it.
+///
+/// The fields added by the builds, e.g. the commit, can't be recomputed and are left out.
+fn check(wasm: &Path, digest: &Path) -> Result<(), String> {
+	let stored = crate::artifact::read_digest(digest)
+		.ok_or_else(|| format!("`{}` can't be parsed", digest.display()))?;
+
+	schema::validate(&stored).map_err(|e| {
+		format!(
+			"`{}` doesn't follow the digest schema:\n{}",
+			digest.display(),
+			e
+		)
+	})?;
+
+	let runtime_info =
+		serde_json::to_value(Subwasm::new(&Source::File(wasm.to_path_buf())).runtime_info())
+			.map_err(|e| format!("`{}` can't be described: {}", wasm.display(), e))?;
+	let stored = runtime_info
+		.as_object()
+		.into_iter()
+		.flat_map(|runtime_info| runtime_info.keys())
+		.filter_map(|key| Some((key.clone(), stored.get(key)?.clone())))
+		.collect::<serde_json::Map<_, _>>();
+	let diff = crate::artifact::digest_diff(&stored.into(), &runtime_info);
+
+	if diff.is_empty() {
+		Ok(())
+	} else {
+		Err(format!(
+			"`{}` doesn't match its digest:\n{}",
+			wasm.display(),
+			diff
+		))
+	}
+}
+
+/// The names of the files of `dir` ending with `suffix`, without it, temporary ones aside.
+pub fn names(dir: &Path, suffix: &str) -> BTreeSet<String> {
+	crate::dir_entries(dir)
+		.iter()
+		.filter_map(|path| {
+			let name = path.file_name()?.to_str()?;
+
+			if name.starts_with('.') {
+				return None;
+			}
+
+			name.strip_suffix(suffix).map(String::from)
+		})
+		.collect()
+}
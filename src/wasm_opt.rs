@@ -0,0 +1,53 @@
+//! Slimming the runtimes down with Binaryen's `wasm-opt`, then compressing them again the way
+//! `substrate-wasm-builder` does.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+
+use crate::{settings::Settings, AnyResult};
+
+/// The passes run without any `--wasm-opt-arg`.
+pub const DEFAULT_ARGS: [&str; 1] = ["-Oz"];
+
+/// Prepended to the zstd compressed code by `sp-maybe-compressed-blob`.
+const ZSTD_PREFIX: [u8; 8] = [82, 188, 83, 118, 70, 219, 142, 5];
+/// The level `substrate-wasm-builder` compresses with.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Run `wasm-opt` with `args` on `compact_wasm` in place and write its compression to
+/// `compressed_wasm`.
+pub fn run(
+	compact_wasm: &Path,
+	compressed_wasm: &Path,
+	args: &[String],
+	source_dir: &Path,
+	label: &str,
+	settings: &Settings,
+) -> AnyResult<()> {
+	let optimized = crate::temporary_path(compact_wasm);
+	let compact_wasm_arg = crate::path_arg(compact_wasm);
+	let optimized_arg = crate::path_arg(&optimized);
+	let mut wasm_opt_args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+	wasm_opt_args.extend([compact_wasm_arg.as_str(), "-o", optimized_arg.as_str()]);
+	settings
+		.run(source_dir, label, "wasm-opt", &wasm_opt_args)
+		.with_context(|| format!("failed to optimize {}", label))?;
+	fs::rename(&optimized, compact_wasm)
+		.with_context(|| format!("failed to write `{}`", compact_wasm.display()))?;
+
+	let code = fs::read(compact_wasm)?;
+	let mut compressed = ZSTD_PREFIX.to_vec();
+
+	compressed.extend(
+		zstd::encode_all(code.as_slice(), ZSTD_LEVEL)
+			.with_context(|| format!("failed to compress `{}`", compact_wasm.display()))?,
+	);
+
+	let tmp = crate::temporary_path(compressed_wasm);
+
+	fs::write(&tmp, compressed)
+		.and_then(|()| fs::rename(&tmp, compressed_wasm))
+		.with_context(|| format!("failed to write `{}`", compressed_wasm.display()))
+}
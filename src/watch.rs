@@ -0,0 +1,133 @@
+//! Building the release tags as they are pushed.
+
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+	sync::Arc,
+	thread,
+};
+
+use clap::ArgEnum;
+use log::{error, info, warn};
+
+use crate::{
+	cli::{BuildArgs, WatchArgs},
+	config::{self, Config},
+	metrics,
+	progress::Progress,
+	registry::{Layout, Registry, Runtime},
+	targets::{self, RefKind},
+	AnyResult, SystemRunner,
+};
+
+/// Poll the repositories every `--interval` and build the tags matching `--pattern` which appeared
+/// since the previous poll, the most recent one already there on start included.
+///
+/// Runs until killed, a failed poll or build is logged and doesn't stop the watch.
+pub fn run(
+	watch: WatchArgs,
+	config: Config,
+	registry: &Registry,
+	build_dir: PathBuf,
+	output_dir: PathBuf,
+	progress: Progress,
+) -> AnyResult<()> {
+	let args = watch.build;
+
+	if args.target.is_some() {
+		anyhow::bail!("`watch` builds the new tags, it takes no `--target`");
+	}
+
+	let runtimes = registry.resolve(&args.runtime)?;
+
+	if let Some(address) = &watch.metrics_listen {
+		metrics::serve(address)?;
+	}
+
+	let mut seen = HashMap::<String, HashSet<String>>::new();
+	let mut first = true;
+
+	loop {
+		for runtime in &runtimes {
+			let tags = match tags(runtime, &watch.pattern, &args, &config) {
+				Ok(tags) => tags,
+				Err(e) => {
+					warn!("Failed to poll {}: {:#}", runtime.name(), e);
+
+					continue;
+				}
+			};
+			let seen = seen.entry(runtime.name().into()).or_default();
+			let mut new = tags
+				.into_iter()
+				.filter(|tag| !seen.contains(tag))
+				.collect::<Vec<_>>();
+
+			seen.extend(new.iter().cloned());
+
+			// The older tags were released before the watch, build the last of them only.
+			if first {
+				new.drain(..new.len().saturating_sub(1));
+			}
+			if new.is_empty() {
+				continue;
+			}
+
+			info!("Building {} at {}", runtime.name(), new.join(", "));
+
+			let mut args = args.clone();
+
+			args.runtime = vec![runtime.name().into()];
+			args.target = Some(new);
+
+			match crate::pipeline::build_artifacts(
+				args,
+				config.clone(),
+				registry,
+				build_dir.clone(),
+				output_dir.clone(),
+				progress.clone(),
+				Arc::new(SystemRunner),
+			) {
+				Ok((_, errors)) => errors.iter().for_each(|e| error!("{:?}", e)),
+				Err(e) => error!("{:?}", e),
+			}
+		}
+
+		first = false;
+
+		info!("Next poll in {}s", watch.interval.as_secs());
+
+		thread::sleep(watch.interval);
+	}
+}
+
+/// The tags of `runtime` matching `pattern`, the most recent last.
+fn tags(
+	runtime: &Runtime,
+	pattern: &str,
+	args: &BuildArgs,
+	config: &Config,
+) -> AnyResult<Vec<String>> {
+	let repo_url = args.repo_url.clone().or_else(|| {
+		config
+			.runtimes
+			.get(runtime.name())
+			.and_then(|r| r.repo_url.clone())
+	});
+	let mut tags = Vec::<String>::new();
+
+	for layout in Layout::value_variants() {
+		let url = repo_url.clone().unwrap_or_else(|| runtime.github(*layout));
+		let git = config::git_options(&config.git, &url);
+
+		for r in targets::remote_refs(&url, &git, &SystemRunner)? {
+			if r.kind == RefKind::Tag && crate::glob_match(pattern, &r.name) {
+				tags.retain(|tag| *tag != r.name);
+				tags.push(r.name);
+			}
+		}
+	}
+
+	Ok(tags)
+}
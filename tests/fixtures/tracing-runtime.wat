@@ -0,0 +1,28 @@
+;; The smallest runtime the pipeline takes for a tracing one, `tracing-runtime.wasm` is assembled
+;; from it, e.g. with `wat2wasm tracing-runtime.wat`.
+;;
+;; It implements `Core` 4, `DebugRuntimeApi` 4 and `TxPoolRuntimeApi` 2, is `crab-1240`, and its V14
+;; metadata has nothing but a `System` pallet with a `set_code` call of index 3.
+(module
+	(import "env" "memory" (memory 2))
+	(global (export "__heap_base") i32 (i32.const 65536))
+
+	;; The encoded `RuntimeVersion`, 64 bytes at 1024.
+	(func (export "Core_version") (param i32 i32) (result i64)
+		(i64.const 274877907968))
+	;; The encoded `OpaqueMetadata`, 44 bytes at 2048.
+	(func (export "Metadata_metadata") (param i32 i32) (result i64)
+		(i64.const 188978563072))
+
+	(data (i32.const 1024)
+		"\10\63\72\61\62\10\63\72\61\62\00\00\00\00\d8\04\00\00\00\00\00\00\0c\df\6a\cb\68\99\07\60\9b"
+		"\04\00\00\00\bd\78\25\5d\4f\ee\ea\1f\04\00\00\00\a3\3d\43\f5\87\31\ad\84\02\00\00\00\01\00\00"
+		"\00\00")
+	(data (i32.const 2048)
+		"\ac\6d\65\74\61\0e\04\00\00\00\01\04\20\73\65\74\5f\63\6f\64\65\00\03\00\00\04\18\53\79\73\74"
+		"\65\6d\00\01\00\00\00\00\00\00\04\00\00")
+
+	;; What `impl_runtime_apis!` emits: the blake2_64 of each API name and its version.
+	(@custom "runtime_apis"
+		"\df\6a\cb\68\99\07\60\9b\04\00\00\00\bd\78\25\5d\4f\ee\ea\1f\04\00\00\00\a3\3d\43\f5\87\31\ad\84"
+		"\02\00\00\00"))
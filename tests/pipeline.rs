@@ -0,0 +1,450 @@
+//! The build pipeline against a fake git and cargo.
+
+use std::{
+	env, fs,
+	path::{Path, PathBuf},
+	process,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use runtime_overrides::{AnyResult, BuildError, CommandRunner, RuntimeBuilder};
+
+const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+const URL: &str = "https://github.com/darwinia-network/darwinia";
+/// A tracing runtime, as small as it gets, see `fixtures/tracing-runtime.wat`.
+const TRACING_RUNTIME: &[u8] = include_bytes!("fixtures/tracing-runtime.wasm");
+
+/// Answers the queries of the pipeline, records the commands it runs and "builds" `wasm`.
+#[derive(Debug, Default)]
+struct FakeRunner {
+	/// Written where cargo would leave the runtime, a failed build if none.
+	wasm: Option<Vec<u8>>,
+	/// Fail the commands starting with this.
+	fail: Option<&'static str>,
+	commands: Arc<Mutex<Vec<String>>>,
+}
+impl CommandRunner for FakeRunner {
+	fn run(
+		&self,
+		dir: &Path,
+		_: &str,
+		program: &str,
+		args: &[&str],
+		_: &[(String, String)],
+		_: Option<Duration>,
+	) -> AnyResult<()> {
+		let command = format!("{} {}", program, args.join(" "));
+
+		self.commands.lock().unwrap().push(command.clone());
+
+		if self.fail.map_or(false, |fail| command.starts_with(fail)) {
+			anyhow::bail!("`{}` failed", command);
+		}
+
+		if program == "git" && args[0] == "clone" {
+			fs::create_dir_all(Path::new(args[args.len() - 1]).join(".git"))?;
+		}
+		if program == "cargo" && args[0] == "clean" {
+			let _ = fs::remove_dir_all(dir.join("target"));
+		}
+		if program == "cargo" && args[0] == "b" {
+			let wasm = match &self.wasm {
+				Some(wasm) => wasm,
+				None => anyhow::bail!("`{}` failed", command),
+			};
+			let wbuild = dir.join("target/release/wbuild/crab-runtime");
+
+			// Like cargo, which doesn't build a fresh crate again.
+			if wbuild.exists() {
+				return Ok(());
+			}
+
+			fs::create_dir_all(&wbuild)?;
+
+			for name in [
+				"crab_runtime.compact.wasm",
+				"crab_runtime.compact.compressed.wasm",
+			] {
+				fs::write(wbuild.join(name), wasm)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn capture(&self, _: &Path, program: &str, args: &[&str]) -> AnyResult<String> {
+		// What `--publish-pr` does to the repository of the output directory.
+		if program == "git" && matches!(args[0], "checkout" | "add" | "commit" | "push") {
+			let command = format!("{} {}", program, args.join(" "));
+
+			self.commands.lock().unwrap().push(command.clone());
+
+			if self.fail.map_or(false, |fail| command.starts_with(fail)) {
+				anyhow::bail!("`{}` failed", command);
+			}
+
+			return Ok(String::new());
+		}
+
+		Ok(match (program, args) {
+			("git", ["ls-remote", ..]) => {
+				format!("{}\trefs/heads/main\n{}\trefs/tags/v0.12.3", COMMIT, COMMIT)
+			}
+			("git", ["rev-parse", "--is-shallow-repository"]) => "true".into(),
+			("git", ["rev-parse", "HEAD"]) => COMMIT.into(),
+			("git", ["rev-parse", "--abbrev-ref", "HEAD"]) => "main".into(),
+			("git", ["rev-parse", "--verify", "--quiet", "HEAD^{commit}"]) => COMMIT.into(),
+			("git", ["remote", "get-url", "origin"]) => URL.into(),
+			("git", ["status", "--porcelain"]) => String::new(),
+			("rustup", ["target", "list", "--installed"]) => "wasm32-unknown-unknown".into(),
+			("rustc", ["--version"]) => "rustc 1.62.0-nightly (a5ad0d29a 2022-05-11)".into(),
+			_ => anyhow::bail!("unexpected `{} {}`", program, args.join(" ")),
+		})
+	}
+}
+
+/// Empty build and output directories, and an empty config not to pick up the user's one.
+fn dirs(test: &str) -> (RuntimeBuilder, PathBuf, PathBuf) {
+	let root = env::temp_dir().join(format!("runtime-overrides-{}-{}", test, process::id()));
+	let build_dir = root.join("build");
+	let output_dir = root.join("output");
+	let config = root.join("runtime-overrides.toml");
+
+	let _ = fs::remove_dir_all(&root);
+
+	fs::create_dir_all(&root).unwrap();
+	fs::write(&config, "").unwrap();
+
+	let builder = RuntimeBuilder::new("crab")
+		.build_dir(&build_dir)
+		.output_dir(&output_dir)
+		.config(config);
+
+	(builder, build_dir, output_dir)
+}
+
+#[test]
+fn clones_checks_out_and_builds_in_order() {
+	let (builder, build_dir, output_dir) = dirs("order");
+	let runner = FakeRunner::default();
+	let commands = runner.commands.clone();
+	let e = builder.runner(runner).run().unwrap_err();
+
+	assert!(matches!(
+		e.downcast_ref(),
+		Some(BuildError::BuildFailed { label }) if label == "crab@main"
+	));
+	assert_eq!(
+		*commands.lock().unwrap(),
+		[
+			format!(
+				"git clone --no-checkout --depth=1 {} {}",
+				URL,
+				build_dir.join("darwinia").display()
+			),
+			"git fetch --depth=1 origin +main:refs/runtime-overrides/main".into(),
+			"git checkout --force --detach refs/runtime-overrides/main".into(),
+			"cargo clean --release --manifest-path runtime/crab/Cargo.toml -p crab-runtime".into(),
+			"cargo b --release --manifest-path runtime/crab/Cargo.toml --locked --features evm-tracing"
+				.into(),
+		]
+	);
+
+	// The log of a failed build is kept, named after the runtime and the target.
+	let log = output_dir.join("crab/logs/crab-main.log");
+
+	assert!(format!("{:#}", e).contains(&log.display().to_string()));
+	assert!(log.is_file());
+}
+
+#[test]
+fn applies_the_patches_to_the_checkout() {
+	let (builder, build_dir, _) = dirs("patch");
+	let patch = build_dir.with_file_name("fix.diff");
+
+	fs::write(&patch, "").unwrap();
+
+	let runner = FakeRunner::default();
+	let commands = runner.commands.clone();
+	let patch = patch.canonicalize().unwrap();
+
+	builder.patch(&patch).runner(runner).run().unwrap_err();
+
+	let commands = commands.lock().unwrap();
+	let checkout = commands
+		.iter()
+		.position(|command| command.starts_with("git checkout"))
+		.unwrap();
+
+	assert_eq!(
+		commands[checkout + 1],
+		format!("git apply {}", patch.display())
+	);
+	// Reverted once built, even if the build failed.
+	assert_eq!(
+		commands.last().unwrap(),
+		&format!("git apply --reverse {}", patch.display())
+	);
+}
+
+#[test]
+fn builds_a_working_tree_as_it_is() {
+	let (builder, build_dir, _) = dirs("source-path");
+	let source_path = build_dir.with_file_name("darwinia");
+
+	fs::create_dir_all(&source_path).unwrap();
+
+	let runner = FakeRunner::default();
+	let commands = runner.commands.clone();
+	let e = builder
+		.source_path(&source_path)
+		.runner(runner)
+		.run()
+		.unwrap_err();
+
+	assert!(matches!(
+		e.downcast_ref(),
+		Some(BuildError::BuildFailed { label }) if label == "crab@local"
+	));
+	// Nothing is cloned nor checked out.
+	assert!(commands
+		.lock()
+		.unwrap()
+		.iter()
+		.all(|command| command.starts_with("cargo ")));
+}
+
+#[test]
+fn tells_network_failures_apart() {
+	let (builder, _, _) = dirs("network");
+	let runner = FakeRunner {
+		fail: Some("git clone"),
+		..Default::default()
+	};
+	let e = builder.retries(0).runner(runner).run().unwrap_err();
+
+	assert!(matches!(
+		e.downcast_ref(),
+		Some(BuildError::CloneFailed { url }) if url == URL
+	));
+	assert_eq!(e.downcast_ref::<BuildError>().unwrap().exit_code(), 10);
+}
+
+#[test]
+fn rejects_unknown_targets_before_cloning() {
+	let (builder, _, _) = dirs("unknown-target");
+	let runner = FakeRunner::default();
+	let commands = runner.commands.clone();
+	let e = builder.target("v0.12.4").runner(runner).run().unwrap_err();
+
+	assert!(format!("{:#}", e).contains("did you mean `v0.12.3`?"));
+	assert!(commands.lock().unwrap().is_empty());
+}
+
+#[test]
+fn looks_the_targets_up_in_the_clones_offline() {
+	let (builder, _, _) = dirs("offline");
+	let runner = FakeRunner::default();
+	let commands = runner.commands.clone();
+	let e = builder.offline(true).runner(runner).run().unwrap_err();
+
+	assert!(format!("{:#}", e).contains("`main` isn't in the clone of darwinia"));
+	// Nothing is cloned or fetched.
+	assert!(commands.lock().unwrap().is_empty());
+}
+
+#[test]
+fn rejects_runtimes_without_the_tracing_apis() {
+	let (builder, _, output_dir) = dirs("tracing-apis");
+	let runner = FakeRunner {
+		// An empty wasm module.
+		wasm: Some(b"\0asm\x01\0\0\0".to_vec()),
+		..Default::default()
+	};
+	let e = builder.runner(runner).run().unwrap_err();
+
+	assert!(format!("{:#}", e).contains("doesn't implement DebugRuntimeApi, TxPoolRuntimeApi"));
+	assert!(!output_dir.join("crab/wasms").exists());
+	assert!(!output_dir.join("crab/digests").exists());
+}
+
+#[test]
+fn writes_the_artifacts() {
+	let (builder, _, output_dir) = dirs("artifacts");
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		..Default::default()
+	};
+	let artifacts = builder.runner(runner).run().unwrap();
+
+	assert_eq!(artifacts.len(), 1);
+
+	let artifact = &artifacts[0];
+
+	assert_eq!(artifact.commit(), COMMIT);
+	assert_eq!(
+		artifact.wasm(),
+		output_dir.join("crab/wasms/crab-main-tracing-runtime.compact.compressed.wasm")
+	);
+	assert_eq!(
+		artifact.digest(),
+		output_dir.join("crab/digests/crab-main-tracing-runtime.json")
+	);
+
+	let digest =
+		serde_json::from_slice::<serde_json::Value>(&fs::read(artifact.digest()).unwrap()).unwrap();
+
+	assert_eq!(digest["schema_version"], 1);
+	assert_eq!(digest["commit"], COMMIT);
+	assert_eq!(digest["features"], "evm-tracing");
+	assert_eq!(digest["core_version"], "crab-1240 (crab-0.tx1.au0)");
+	// Of `[0, 3]`, the `System.set_code` call, then the wasm.
+	assert_eq!(
+		digest["set_code_hash"],
+		"0x2e8f95142a699f6df1a27772ae31fdcc728803eba09e12ffa5ef6755a734628c"
+	);
+	assert_eq!(
+		digest["provenance"]["log"],
+		output_dir
+			.join("crab/logs/crab-main-tracing-runtime.log")
+			.display()
+			.to_string()
+	);
+	assert!(output_dir.join("index.json").is_file());
+}
+
+#[test]
+fn rebuilds_a_fresh_target() {
+	let (builder, build_dir, output_dir) = dirs("rebuild");
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		..Default::default()
+	};
+
+	builder.runner(runner).run().unwrap();
+
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		..Default::default()
+	};
+	let commands = runner.commands.clone();
+	// Same commit, features and profile, so the target directory isn't cleaned.
+	let artifacts = RuntimeBuilder::new("crab")
+		.build_dir(&build_dir)
+		.output_dir(&output_dir)
+		.config(build_dir.with_file_name("runtime-overrides.toml"))
+		.rebuild(true)
+		.force(true)
+		.runner(runner)
+		.run()
+		.unwrap();
+
+	assert!(!commands
+		.lock()
+		.unwrap()
+		.iter()
+		.any(|command| command.starts_with("cargo clean")));
+	assert_eq!(artifacts.len(), 1);
+	assert!(artifacts[0].wasm().is_file());
+}
+
+#[test]
+fn points_to_the_log_of_a_build_refused_late() {
+	let (builder, build_dir, output_dir) = dirs("refused");
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		..Default::default()
+	};
+
+	builder.runner(runner).run().unwrap();
+
+	// A different artifact in the way, refused once its digest is known.
+	let digest_path = output_dir.join("crab/digests/crab-main-tracing-runtime.json");
+	let mut digest =
+		serde_json::from_slice::<serde_json::Value>(&fs::read(&digest_path).unwrap()).unwrap();
+
+	digest["set_code_hash"] = "0x00".into();
+	fs::write(&digest_path, digest.to_string()).unwrap();
+
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		..Default::default()
+	};
+	let e = RuntimeBuilder::new("crab")
+		.build_dir(&build_dir)
+		.output_dir(&output_dir)
+		.config(build_dir.with_file_name("runtime-overrides.toml"))
+		.rebuild(true)
+		.runner(runner)
+		.run()
+		.unwrap_err();
+	let log = output_dir.join("crab/logs/crab-main.log");
+
+	assert!(format!("{:#}", e).contains("refusing to overwrite"));
+	assert!(format!("{:#}", e).contains(&log.display().to_string()));
+	assert!(log.is_file());
+	// The log of the artifact in place is left alone.
+	assert!(output_dir
+		.join("crab/logs/crab-main-tracing-runtime.log")
+		.is_file());
+}
+
+#[test]
+fn commits_the_artifacts_relative_to_the_output_directory() {
+	let (builder, build_dir, output_dir) = dirs("publish-pr");
+
+	fs::write(
+		build_dir.with_file_name("runtime-overrides.toml"),
+		"publish-pr = true\ncontent-addressed = true\ngithub-repository = \"darwinia-network/overrides\"\n",
+	)
+	.unwrap();
+	env::set_var("GITHUB_TOKEN", "token");
+
+	let runner = FakeRunner {
+		wasm: Some(TRACING_RUNTIME.to_vec()),
+		// Not to open the pull request for real.
+		fail: Some("git push"),
+		..Default::default()
+	};
+	let commands = runner.commands.clone();
+	let e = builder.runner(runner).run().unwrap_err();
+
+	assert!(
+		format!("{:#}", e).contains("failed to push the artifacts to runtime-overrides/crab-main")
+	);
+
+	let digest = serde_json::from_slice::<serde_json::Value>(
+		&fs::read(output_dir.join("crab/digests/crab-main-tracing-runtime.json")).unwrap(),
+	)
+	.unwrap();
+	let object = format!(
+		"crab/wasms/by-hash/{}.wasm",
+		digest["blake2_256"]
+			.as_str()
+			.unwrap()
+			.trim_start_matches("0x")
+	);
+	let commands = commands.lock().unwrap();
+	let publish = commands
+		.iter()
+		.skip_while(|command| !command.starts_with("git checkout -B"))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		publish,
+		[
+			"git checkout -B runtime-overrides/crab-main".into(),
+			format!(
+				"git add -- crab/BLAKE2SUMS crab/SHA256SUMS crab/digests/crab-main-tracing-runtime.json {} crab/wasms/crab-main-tracing-runtime.compact.compressed.wasm index.json",
+				object
+			),
+			"git commit -m Add the tracing runtimes of crab@main".into(),
+			"git push --force --set-upstream origin runtime-overrides/crab-main".into(),
+			// Back to where it was.
+			"git checkout main".into(),
+		]
+		.iter()
+		.collect::<Vec<_>>()
+	);
+}